@@ -0,0 +1,34 @@
+//! What the time cache saves per record, see LoggerOpts::set_time_cache.
+//!
+//! `cargo bench --bench timestamp`. Records go to the null log type, so
+//! only formatting is measured.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use little_logger::log::{Logger, LoggerOpts};
+
+const RECORDS: u32 = 200_000;
+
+fn per_record(dt_format: &str, cache: bool) -> Duration {
+    let opts = LoggerOpts::new()
+        .set_log_type("null")
+        .set_dt_format(dt_format)
+        .set_time_cache(cache);
+    let mut logger = Logger::new(opts);
+    let start = Instant::now();
+    for i in 0..RECORDS {
+        logger.info(black_box(format!("request {i} done")));
+    }
+    start.elapsed() / RECORDS
+}
+
+fn main() {
+    for dt_format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S%.3f%:z"] {
+        // A warm up round, so neither side pays for first touches.
+        per_record(dt_format, true);
+        let precise = per_record(dt_format, false);
+        let cached = per_record(dt_format, true);
+        println!("{dt_format:28} precise {precise:>9.2?}  cached {cached:>9.2?}");
+    }
+}
@@ -0,0 +1,38 @@
+//! Log every line read from stdin, `make 2>&1 | llog-ingest --label BUILD`.
+
+use std::io;
+
+use clap::Parser;
+use little_logger::log::{Level, LogFormat, Logger, LoggerOpts};
+
+#[derive(Parser)]
+#[command(about = "Log every line read from stdin")]
+struct Cli {
+    /// The label at the start of each line.
+    #[arg(long, default_value = "LLOG")]
+    label: String,
+    /// The level to log the lines at.
+    #[arg(long, value_enum, default_value = "info")]
+    level: Level,
+    /// The log file to write to.
+    #[arg(long, default_value = "llog.txt")]
+    file: String,
+    /// The format of the log file.
+    #[arg(long, value_enum, default_value = "text")]
+    format: LogFormat,
+    /// Echo the lines to the console as well.
+    #[arg(long)]
+    console: bool,
+}
+
+fn main() -> io::Result<()> {
+    let cli = Cli::parse();
+    let opts = LoggerOpts::new()
+        .set_log_label(&cli.label)
+        .set_logfile_name(&cli.file)
+        .set_file_format(cli.format)
+        .set_log_type(if cli.console { "both" } else { "file" });
+    let mut logger = Logger::new(opts);
+    logger.ingest(io::stdin().lock(), cli.level)?;
+    logger.flush()
+}
@@ -0,0 +1,78 @@
+//! Wire logging into a clap command line.
+
+use clap::{ArgAction, Args};
+
+use crate::log::{Level, LoggerOpts};
+
+/// `-v`/`--verbose` and `-q`/`--quiet` flags to flatten into a clap parser.
+///
+/// The level starts at Info. Each `-v` steps it down towards Trace and each
+/// `-q` steps it up towards Error.
+///
+/// ```no_run
+/// use clap::Parser;
+/// use little_logger::cli::Verbosity;
+/// use little_logger::log::{Logger, LoggerOpts};
+///
+/// #[derive(Parser)]
+/// struct Cli {
+///     #[command(flatten)]
+///     verbosity: Verbosity,
+/// }
+///
+/// let cli = Cli::parse();
+/// let mut logger = Logger::new(LoggerOpts::from_verbosity(&cli.verbosity));
+/// ```
+#[derive(Args, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Verbosity {
+    /// Log more, repeat for even more.
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    verbose: u8,
+    /// Log less, repeat for even less.
+    #[arg(short, long, action = ArgAction::Count, global = true, conflicts_with = "verbose")]
+    quiet: u8,
+}
+
+impl Verbosity {
+    /// The level the flags ask for.
+    pub fn level(&self) -> Level {
+        const LEVELS: [Level; 5] = [
+            Level::Trace,
+            Level::Debug,
+            Level::Info,
+            Level::Warn,
+            Level::Error,
+        ];
+        let step = 2 + self.quiet as isize - self.verbose as isize;
+        LEVELS[step.clamp(0, LEVELS.len() as isize - 1) as usize]
+    }
+}
+
+impl LoggerOpts {
+    /// The default options with the level set from command line flags.
+    pub fn from_verbosity(verbosity: &Verbosity) -> LoggerOpts {
+        LoggerOpts::new().set_level(verbosity.level())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        verbosity: Verbosity,
+    }
+
+    #[test]
+    fn test_flags_step_the_level() {
+        let level = |args: &[&str]| Cli::parse_from(args).verbosity.level();
+        assert_eq!(level(&["app"]), Level::Info);
+        assert_eq!(level(&["app", "-vv"]), Level::Trace);
+        assert_eq!(level(&["app", "-vvvv"]), Level::Trace);
+        assert_eq!(level(&["app", "--quiet"]), Level::Warn);
+        assert_eq!(level(&["app", "-qqq"]), Level::Error);
+    }
+}
@@ -34,10 +34,14 @@
 /// ```
 ///
 /// Output to llog.txt:
-/// ```no_run
-/// [LLOG]::[2024-05-21 18:37:22] -> Enter your message here!
+/// ```text
+/// [LLOG]::[2024-05-21 18:37:22]::[INFO] -> Enter your message here!
 /// ```
 ///
+/// Text lines now carry the level as a third bracketed field. Lines used
+/// to read `[LLOG]::[2024-05-21 18:37:22] -> Enter your message here!`,
+/// so scripts that split on `::` or count columns need updating.
+///
 /////////////////////////////////////////////////////////////////////////////////////////////
 /// # Examples
 /// Set custom logging options:
@@ -53,11 +57,13 @@
 ///    .set_logfile_name("my_log")
 ///    .set_dest_dir("/home/me/logfiles")
 ///    .set_log_label("SERVER")
-///    .set_dt_format("%H-%M-%S);
+///    .set_dt_format("%H-%M-%S");
 /// ```
 /// Create a new logger with the above options and log a message:
 ///
 /// ```no_run
+/// # use little_logger::log::{Logger, LoggerOpts};
+/// # let opts = LoggerOpts::new();
 /// let mut logger = Logger::new(opts);
 ///
 /// logger.log_message("Enter your message here!");
@@ -65,78 +71,932 @@
 ///
 /// Example output to my_log:
 ///
-///     [SERVER]::[18:37:22] -> Enter your message here!
+/// ```text
+/// [SERVER]::[18:37:22]::[INFO] -> Enter your message here!
+/// ```
 ///
 /// Example output to console:
 ///
-///     [SERVER]::[18:37:22] -> Enter your message here!
+/// ```text
+/// [SERVER]::[18:37:22]::[INFO] -> Enter your message here!
+/// ```
 ///
+/////////////////////////////////////////////////////////////////////////////////////////////
+/// # Examples
+/// Give each destination its own format:
+///
+/// ```no_run
+/// use little_logger::log::{LogFormat, Logger, LoggerOpts};
+///
+/// let opts = LoggerOpts::new()
+///    .set_log_type("both")
+///    .set_console_format(LogFormat::Colored)
+///    .set_file_format(LogFormat::Json);
+///
+/// let mut logger = Logger::new(opts);
+/// logger.log_message("Pretty on the terminal, JSON on disk.");
+/// ```
+///
+/// Example output to llog.txt:
+///
+/// ```text
+/// {"label":"LLOG","time":"2024-05-21 18:37:22","level":"INFO","message":"Pretty on the terminal, JSON on disk."}
+/// ```
+///
+/////////////////////////////////////////////////////////////////////////////////////////////
+/// # Examples
+/// Log at a level and keep the level column lined up:
+///
+/// ```no_run
+/// use little_logger::log::{LevelCase, Logger, LoggerOpts};
+///
+/// let opts = LoggerOpts::new()
+///    .set_level_case(LevelCase::Upper)
+///    .set_level_padding(true);
+///
+/// let mut logger = Logger::new(opts);
+/// logger.info("Server started.");
+/// logger.error("Disk full.");
+/// ```
+///
+/// Example output to llog.txt:
+///
+/// ```text
+/// [LLOG]::[2024-05-21 18:37:22]::[INFO ] -> Server started.
+/// [LLOG]::[2024-05-21 18:37:22]::[ERROR] -> Disk full.
+/// ```
+///
+/////////////////////////////////////////////////////////////////////////////////////////////
+/// # Examples
+/// Share one configuration between several loggers:
+///
+/// ```no_run
+/// use little_logger::log::{LogFormat, Logger, LoggerOpts};
+///
+/// let base = LoggerOpts::default().set_file_format(LogFormat::Json);
+///
+/// let mut db = Logger::new(base.clone().set_logfile_name("db.txt"));
+/// let mut net = Logger::new(base.set_logfile_name("net.txt"));
+/// db.log_message("Connected.");
+/// net.log_message("Listening.");
+/// ```
 ///
-
 pub mod log {
 
+    #[cfg(feature = "console")]
+    mod ansi;
+    mod batch;
+    pub mod build_info;
+    mod cbor;
+    mod clock;
+    #[cfg(feature = "aws")]
+    mod cloudwatch;
+    #[cfg(feature = "config")]
+    mod config;
+    mod context;
+    pub mod crash;
+    mod detail;
+    mod error_code;
+    #[cfg(feature = "arrow")]
+    pub mod export;
+    mod ext;
+    pub mod fatal;
+    mod filter;
+    #[cfg(feature = "file")]
+    mod flush;
+    mod format;
+    mod forward;
+    mod header;
+    mod intern;
+    mod internal;
+    mod label;
+    mod level;
+    pub mod library;
+    mod local;
+    mod locale;
+    mod lossy;
+    mod msgpack;
+    mod mute;
+    #[cfg(feature = "console")]
+    mod nowhere;
+    mod pipe;
+    mod preset;
+    mod progress;
+    pub mod reader;
+    mod record;
+    pub mod replay;
+    #[cfg(feature = "file")]
+    mod rotate;
+    #[cfg(feature = "s3")]
+    mod s3;
+    pub mod schema;
+    mod scoped;
+    #[cfg(feature = "sentry")]
+    mod sentry;
+    mod severity;
+    #[cfg(all(unix, feature = "signals"))]
+    mod signals;
+    #[cfg(any(feature = "s3", feature = "aws"))]
+    mod sigv4;
+    mod sink;
+    mod span;
+    mod stats;
+    mod stopwatch;
+    #[cfg(feature = "file")]
+    mod tenant;
+    pub mod testing;
+    mod transaction;
+    mod ulid;
+    #[cfg(feature = "watch")]
+    mod watch;
+    #[cfg(feature = "console")]
+    mod wrap;
+
+    pub use batch::Batch;
+    pub use build_info::BuildInfo;
+    pub use clock::Clock;
+    #[cfg(feature = "aws")]
+    pub use cloudwatch::CloudWatch;
+    pub use error_code::{DuplicateCode, ErrorCodes};
+    pub use ext::LogResultExt;
+    use filter::Filter;
+    pub use format::LogFormat;
+    use format::{Layout, Record};
+    pub use internal::InternalLog;
+    use internal::ProblemFn;
+    use label::LabelFn;
+    pub use label::RecordMeta;
+    use level::LevelStyle;
+    pub use level::{Level, LevelCase, ParseLevelError};
+    pub use library::{LlogHandle, NopLogger};
+    pub use local::LocalHandle;
+    pub use locale::Localization;
+    pub use lossy::InvalidUtf8;
+    use pipe::{PipeSink, PipeSpec};
+    pub use preset::Profile;
+    pub use record::RecordBuilder;
+    #[cfg(feature = "s3")]
+    pub use s3::S3Upload;
+    pub use scoped::Labeled;
+    #[cfg(feature = "sentry")]
+    pub use sentry::Sentry;
+    pub use severity::SeverityMap;
+    use sink::{Isolated, NullSink, QueueLimit, SharedSink, Sink};
+    pub use span::{Span, SpanBuilder};
+    use stats::Threshold;
+    pub use stats::{Histogram, Stats};
+    pub use stopwatch::Stopwatch;
+    pub use transaction::Transaction;
+
     use chrono::Local;
     use std::env::set_current_dir;
     use std::fs::{File, OpenOptions};
-    use std::io::{prelude::*, StdoutLock, Write};
+    use std::io::{prelude::*, Stdout, Write};
+    use std::panic::Location;
     use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
     use std::{fmt, io};
 
+    /// Which sinks a Logger will build. LoggerOpts only keeps this around,
+    /// the sinks themselves are created by Logger::new.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    enum LogTarget {
+        File,
+        Console,
+        Both,
+        Null,
+        Blackhole,
+    }
+
+    impl LogTarget {
+        /// Write to a file unless the file sink was compiled out.
+        fn default_for_features() -> LogTarget {
+            if cfg!(feature = "file") {
+                LogTarget::File
+            } else {
+                LogTarget::Console
+            }
+        }
+
+        fn compiled_in(&self) -> bool {
+            match self {
+                LogTarget::File => cfg!(feature = "file"),
+                LogTarget::Console => cfg!(feature = "console"),
+                LogTarget::Both => cfg!(all(feature = "file", feature = "console")),
+                LogTarget::Null | LogTarget::Blackhole => true,
+            }
+        }
+    }
+
     #[derive(Debug)]
-    enum LogType<'a> {
+    enum LogType {
+        #[cfg(feature = "file")]
         File(Box<LogFile>),
-        Console(Box<LogConsl<'a>>),
-        Both(Box<LogConsl<'a>>, Box<LogFile>),
+        #[cfg(feature = "console")]
+        Console(Box<LogConsl>),
+        #[cfg(all(feature = "console", feature = "file"))]
+        Both(Box<LogConsl>, Box<LogFile>),
+        /// Formats with the file format, then drops the bytes.
+        Null(NullSink),
+        /// Returns before anything is formatted.
+        Blackhole,
     }
 
+    /// An extra log file and the format written to it.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    struct FileSpec {
+        path: String,
+        format: LogFormat,
+    }
+
+    #[cfg(feature = "file")]
+    impl LogType {
+        fn file_mut(&mut self) -> Option<&mut Box<LogFile>> {
+            match self {
+                LogType::File(file) => Some(file),
+                #[cfg(feature = "console")]
+                LogType::Both(_, file) => Some(file),
+                _ => None,
+            }
+        }
+    }
+
+    #[cfg(feature = "file")]
     #[derive(Debug)]
     struct LogFile {
-        out: File,
+        path: PathBuf,
+        out: flush::LiveFile,
+        format: LogFormat,
+        buffer: usize,
+        flush_interval: Option<Duration>,
+        /// Written when the file is new, see set_file_header.
+        header: Option<header::Header>,
+        /// Bytes written to the current file, including what was there.
+        size: u64,
+        /// Zero for never, see set_rotate_size.
+        rotate_size: u64,
+        /// Records in the current file, including what was there.
+        records: u64,
+        /// Zero for never, see set_max_records_per_file.
+        rotate_records: u64,
+        manifest: bool,
+        archive_dir: Option<PathBuf>,
+        keep_rotated: usize,
+        current_link: bool,
+        /// Where the last full file went, until the Logger runs its hooks.
+        rotated: Option<PathBuf>,
+        /// Lines held back while a Batch is open.
+        pending: Option<Vec<u8>>,
+        /// How many records are in pending.
+        pending_records: u64,
+        /// Pending is written out early past this, see set_max_batch_bytes.
+        max_batch: usize,
+        /// Name the file after the thread that first writes to it.
+        per_thread: bool,
+        /// Set on the file of a LocalHandle, whose pending lines go out
+        /// every so often rather than at the end of a batch.
+        handoff: Option<local::Handoff>,
+        /// False to write only the messages, see set_file_decorations.
+        decorations: bool,
+        /// The Logger's own file, which llog_fatal! writes to as well.
+        fatal: bool,
     }
 
+    #[cfg(feature = "file")]
     impl LogFile {
-        fn new(file_name: &str) -> Box<LogFile> {
-            let mut file = OpenOptions::new().append(true).open(file_name);
-            let mut logfile: LogFile = LogFile {
-                out: file.expect("Failed to open log file"),
+        /// Nothing is created on disk until the first line is written.
+        ///
+        /// A file handed over with set_log_file is duplicated instead, so
+        /// every Logger built from the same opts owns its own handle.
+        fn new(opts: &LoggerOpts, format: LogFormat) -> Box<LogFile> {
+            let mut logfile = LogFile::at(opts, &opts.log_file_name, format);
+            logfile.decorations = opts.file_decorations;
+            logfile.fatal = true;
+            if let Some(file) = &opts.log_file {
+                let file = file
+                    .try_clone()
+                    .expect("Failed to duplicate the log file handle");
+                #[cfg(unix)]
+                fatal::set_log_file(&file);
+                logfile.out = Arc::new(Mutex::new(Some(flush::share(
+                    file,
+                    logfile.buffer,
+                    logfile.flush_interval,
+                ))));
+            }
+            logfile
+        }
+
+        /// A log file at `path` with the buffering, header and rotation
+        /// settings of `opts`.
+        fn at(opts: &LoggerOpts, path: &str, format: LogFormat) -> Box<LogFile> {
+            let logfile: LogFile = LogFile {
+                path: PathBuf::from(path),
+                out: flush::LiveFile::default(),
+                format,
+                buffer: opts.buffer,
+                flush_interval: opts.flush_interval,
+                header: opts
+                    .file_header
+                    .then(|| header::Header::new(format, &opts.dt_format)),
+                size: 0,
+                // A handed over file has no name to rotate.
+                rotate_size: if opts.log_file.is_some() {
+                    0
+                } else {
+                    opts.rotate_size
+                },
+                records: 0,
+                rotate_records: if opts.log_file.is_some() {
+                    0
+                } else {
+                    opts.max_records_per_file
+                },
+                manifest: opts.rotation_manifest,
+                archive_dir: opts.archive_dir.clone(),
+                keep_rotated: opts.keep_rotated,
+                current_link: opts.current_link,
+                rotated: None,
+                pending: None,
+                pending_records: 0,
+                max_batch: opts.max_batch_bytes,
+                per_thread: opts.per_thread_files,
+                handoff: None,
+                decorations: true,
+                fatal: false,
             };
             Box::new(logfile)
         }
+
+        fn encode(&self, record: &Record, layout: &Layout) -> Vec<u8> {
+            let raw = record.raw || !self.decorations;
+            self.format.encode(&Record { raw, ..*record }, layout)
+        }
+
+        fn write_all(&mut self, line: &[u8]) -> io::Result<()> {
+            if let Some(pending) = &mut self.pending {
+                pending.extend_from_slice(line);
+                self.pending_records += 1;
+                let full = pending.len() >= self.max_batch;
+                if !full && !self.handoff.as_mut().is_some_and(|h| h.due(pending.len())) {
+                    return Ok(());
+                }
+                let pending = std::mem::take(pending);
+                let records = std::mem::take(&mut self.pending_records);
+                return self.write_out(&pending, records);
+            }
+            self.write_out(line, 1)
+        }
+
+        /// Write `records` encoded records, rotating after them if the file
+        /// is full.
+        fn write_out(&mut self, line: &[u8], records: u64) -> io::Result<()> {
+            // Held while writing, so a rotation can't slip in between and
+            // leave the line in the file it moved aside.
+            let live = Arc::clone(&self.out);
+            let mut out = live.lock().unwrap_or_else(|e| e.into_inner());
+            if out.is_none() {
+                if std::mem::take(&mut self.per_thread) {
+                    self.path = thread_path(&self.path);
+                }
+                *out = Some(self.open()?);
+            }
+            let file = out.as_ref().expect("log file was just opened");
+            file.lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .write_all(line)?;
+            drop(out);
+            self.size += line.len() as u64;
+            self.records += records;
+            let full_size = self.rotate_size > 0 && self.size >= self.rotate_size;
+            let full_records = self.rotate_records > 0 && self.records >= self.rotate_records;
+            if full_size || full_records {
+                self.rotate().map_err(|e| {
+                    io::Error::new(
+                        e.kind(),
+                        format!("rotating {} failed: {e}", self.path.display()),
+                    )
+                })?;
+            }
+            Ok(())
+        }
+
+        /// A second LogFile writing through the same open file, for a
+        /// LocalHandle. It never rotates, since it can't see what the
+        /// others have written, but follows this one when it does.
+        fn local_handle(&mut self, opts: &LoggerOpts) -> io::Result<Box<LogFile>> {
+            let live = Arc::clone(&self.out);
+            let mut out = live.lock().unwrap_or_else(|e| e.into_inner());
+            if out.is_none() {
+                *out = Some(self.open()?);
+            }
+            let mut local = LogFile::at(opts, &self.path.to_string_lossy(), self.format);
+            local.out = Arc::clone(&self.out);
+            local.rotate_size = 0;
+            local.rotate_records = 0;
+            // Whichever writes first after a rotation opens the next file.
+            local.per_thread = false;
+            local.decorations = self.decorations;
+            local.fatal = self.fatal;
+            local.pending = Some(Vec::new());
+            local.handoff = Some(local::Handoff::new());
+            Ok(local)
+        }
+
+        /// Start or end holding lines back. Ending writes what was held in
+        /// one go and flushes.
+        fn set_batching(&mut self, on: bool) -> io::Result<()> {
+            if on {
+                self.pending.get_or_insert_with(Vec::new);
+                return Ok(());
+            }
+            // A LocalHandle keeps buffering after the batch, its flush
+            // hands over what the batch held.
+            if self.handoff.is_none() {
+                if let Some(pending) = self.pending.take() {
+                    let records = std::mem::take(&mut self.pending_records);
+                    if !pending.is_empty() {
+                        self.write_out(&pending, records)?;
+                    }
+                }
+            }
+            self.flush()
+        }
+
+        /// Close the full file and move it aside, the next line starts a
+        /// new one.
+        fn rotate(&mut self) -> io::Result<()> {
+            // Held until the manifest is written, so no LocalHandle adds
+            // to the file after it was counted.
+            let live = Arc::clone(&self.out);
+            let mut out = live.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(file) = out.take() {
+                file.lock().unwrap_or_else(|e| e.into_inner()).flush()?;
+            }
+            self.size = 0;
+            self.records = 0;
+            let archive = self.archive_dir.as_deref();
+            let rotated = rotate::rotate(&self.path, archive)?;
+            if self.manifest {
+                rotate::write_manifest(&rotated, self.format)?;
+            }
+            if self.keep_rotated > 0 {
+                rotate::prune(&self.path, archive, self.keep_rotated)?;
+            }
+            self.rotated = Some(rotated);
+            Ok(())
+        }
+
+        /// Open the file for appending and give a new one its header.
+        ///
+        /// Binary formats can't hold a text line, so their header goes
+        /// into a `.meta` file next to the log instead.
+        fn open(&mut self) -> io::Result<flush::SharedFile> {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+            let is_new = file.metadata().is_ok_and(|meta| meta.len() == 0);
+            if let (true, Some(header)) = (is_new, &self.header) {
+                let line = header.render();
+                if header.format.is_binary() {
+                    let _ = std::fs::write(reader::meta_path(&self.path), line);
+                } else {
+                    let _ = file.write_all(line.as_bytes());
+                }
+            }
+            self.size = file.metadata().map_or(0, |meta| meta.len());
+            self.records = 0;
+            if self.rotate_records > 0 && self.size > 0 {
+                self.records = rotate::count_records(&self.path, self.format)? as u64;
+            }
+            if self.current_link {
+                let _ = rotate::link_current(&self.path);
+            }
+            #[cfg(unix)]
+            if self.fatal {
+                fatal::set_log_file(&file);
+            }
+            Ok(flush::share(file, self.buffer, self.flush_interval))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            if self.handoff.is_some() {
+                let pending = self.pending.as_mut().map(std::mem::take);
+                let records = std::mem::take(&mut self.pending_records);
+                if let Some(pending) = pending.filter(|pending| !pending.is_empty()) {
+                    self.write_out(&pending, records)?;
+                }
+            }
+            match &*self.out.lock().unwrap_or_else(|e| e.into_inner()) {
+                Some(file) => file.lock().unwrap_or_else(|e| e.into_inner()).flush(),
+                None => Ok(()),
+            }
+        }
+
+        /// Flush and wait for the data to reach the disk.
+        fn sync(&mut self) -> io::Result<()> {
+            match &*self.out.lock().unwrap_or_else(|e| e.into_inner()) {
+                Some(file) => {
+                    let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+                    file.flush()?;
+                    file.get_ref().sync_data()
+                }
+                None => Ok(()),
+            }
+        }
+    }
+
+    /// `llog.txt` becomes `llog.worker-3.txt` on a thread named worker-3,
+    /// or `llog.thread-3.txt` on an unnamed one.
+    #[cfg(feature = "file")]
+    fn thread_path(path: &Path) -> PathBuf {
+        let thread = std::thread::current();
+        let name = match thread.name() {
+            Some(name) => name.replace(std::path::MAIN_SEPARATOR, "-"),
+            None => {
+                let id = format!("{:?}", thread.id());
+                let digits: String = id.chars().filter(char::is_ascii_digit).collect();
+                format!("thread-{digits}")
+            }
+        };
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let name = match path.extension() {
+            Some(ext) => format!("{stem}.{name}.{}", ext.to_string_lossy()),
+            None => format!("{stem}.{name}"),
+        };
+        path.with_file_name(name)
+    }
+
+    /// Extra files added with add_file_sink are written like any sink.
+    #[cfg(feature = "file")]
+    impl Sink for LogFile {
+        fn write_record(&mut self, record: &Record, layout: &Layout) -> io::Result<()> {
+            self.write_all(&self.encode(record, layout))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            LogFile::flush(self)
+        }
+        fn name(&self) -> &'static str {
+            "file"
+        }
     }
 
+    #[cfg(feature = "console")]
     #[derive(Debug)]
-    struct LogConsl<'a> {
-        out: StdoutLock<'a>,
+    struct LogConsl {
+        /// Locked for each line rather than held, so the Logger doesn't
+        /// borrow stdout for its whole life.
+        out: Stdout,
+        format: LogFormat,
+        wrap: bool,
+        /// Set when the console can't show colors, so the escape codes
+        /// don't come out as garbage.
+        strip_colors: bool,
+        /// False to write only the messages, see set_console_decorations.
+        decorations: bool,
+        /// Lines held back while a Batch is open.
+        pending: Option<Vec<u8>>,
+        /// Pending is written out early past this, see set_max_batch_bytes.
+        max_batch: usize,
+        /// Where lines go once stdout is closed, see set_console_fallback.
+        fallback: Option<PathBuf>,
+        /// The fallback file, once stdout has been closed.
+        redirected: Option<File>,
+        /// Set when stdout was closed.
+        closed: bool,
+        /// Records at this level and above go to stderr, see
+        /// set_console_split.
+        split: Option<Level>,
+        /// Set when stderr leads nowhere.
+        stderr_off: bool,
+        problems: internal::Problems,
     }
 
-    impl<'a> LogConsl<'a> {
-        fn new() -> Box<LogConsl<'a>> {
-            let mut console: LogConsl<'a> = LogConsl {
-                out: io::stdout().lock(),
+    #[cfg(feature = "console")]
+    impl LogConsl {
+        fn new(
+            opts: &LoggerOpts,
+            format: LogFormat,
+            problems: &internal::Problems,
+        ) -> Box<LogConsl> {
+            let wrap = opts.console_wrap;
+            let mut console: LogConsl = LogConsl {
+                out: io::stdout(),
+                format,
+                wrap,
+                strip_colors: format == LogFormat::Colored && !ansi::enable_colors(),
+                decorations: opts.console_decorations,
+                pending: None,
+                max_batch: opts.max_batch_bytes,
+                fallback: opts.console_fallback.clone(),
+                redirected: None,
+                closed: false,
+                split: opts.console_split,
+                stderr_off: false,
+                problems: problems.clone(),
             };
+            match nowhere::nowhere(1) {
+                Some("closed") => {
+                    if let Err(e) = console.stdout_closed(&[]) {
+                        problems.report(format!("opening the console fallback failed: {e}"));
+                    }
+                }
+                Some(nowhere) => {
+                    console.closed = true;
+                    problems.report(format!(
+                        "stdout is {nowhere}, console lines are dropped from here on"
+                    ));
+                }
+                None => {}
+            }
+            if let Some(split) = console.split {
+                if let Some(nowhere) = nowhere::nowhere(2) {
+                    console.stderr_off = true;
+                    problems.report(format!(
+                        "stderr is {nowhere}, console lines at {split} and above are dropped"
+                    ));
+                }
+            }
             Box::new(console)
         }
+
+        fn to_stderr(&self, level: Level) -> bool {
+            self.split.is_some_and(|split| level >= split)
+        }
+
+        /// Whether lines at `level` can't go anywhere, so there's no point
+        /// formatting them.
+        fn is_off(&self, level: Level) -> bool {
+            match self.to_stderr(level) {
+                true => self.stderr_off,
+                false => self.closed && self.redirected.is_none(),
+            }
+        }
+
+        fn encode(&self, record: &Record, layout: &Layout) -> Vec<u8> {
+            if self.is_off(record.level) {
+                return Vec::new();
+            }
+            let raw = record.raw || !self.decorations;
+            self.format.encode(&Record { raw, ..*record }, layout)
+        }
+
+        /// Lines are wrapped here so the file never sees it.
+        fn write_all(&mut self, level: Level, line: &[u8]) -> io::Result<()> {
+            let (wrapped, stripped);
+            let mut line = line;
+            if self.strip_colors {
+                stripped = ansi::strip(&String::from_utf8_lossy(line));
+                line = stripped.as_bytes();
+            }
+            if self.wrap && !self.format.is_binary() {
+                wrapped = wrap::wrap(&String::from_utf8_lossy(line), wrap::terminal_width());
+                line = wrapped.as_bytes();
+            }
+            // Diagnostics aren't batched, they'd be no use late.
+            if self.to_stderr(level) {
+                return self.write_err(line);
+            }
+            match &mut self.pending {
+                Some(pending) => {
+                    pending.extend_from_slice(line);
+                    if pending.len() < self.max_batch {
+                        return Ok(());
+                    }
+                    let pending = std::mem::take(pending);
+                    self.write_out(&pending)
+                }
+                None => self.write_out(line),
+            }
+        }
+
+        fn write_out(&mut self, line: &[u8]) -> io::Result<()> {
+            if let Some(file) = &mut self.redirected {
+                return file.write_all(line);
+            }
+            if self.closed {
+                return Ok(());
+            }
+            match self.out.write_all(line) {
+                Err(e) if e.kind() == io::ErrorKind::BrokenPipe => self.stdout_closed(line),
+                result => result,
+            }
+        }
+
+        fn write_err(&mut self, line: &[u8]) -> io::Result<()> {
+            if self.stderr_off {
+                return Ok(());
+            }
+            match io::stderr().write_all(line) {
+                Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {
+                    self.stderr_off = true;
+                    Ok(())
+                }
+                result => result,
+            }
+        }
+
+        /// The reader of stdout went away, a collector that died say. Move
+        /// to the fallback file if there is one, or stop writing.
+        fn stdout_closed(&mut self, line: &[u8]) -> io::Result<()> {
+            self.closed = true;
+            let Some(path) = &self.fallback else {
+                self.problems
+                    .report("stdout was closed, console lines are dropped from here on");
+                return Ok(());
+            };
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            self.problems.report(format!(
+                "stdout was closed, console lines go to {} from here on",
+                path.display()
+            ));
+            file.write_all(line)?;
+            self.redirected = Some(file);
+            Ok(())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            match (&mut self.redirected, self.closed) {
+                (Some(file), _) => file.flush(),
+                (None, true) => Ok(()),
+                (None, false) => match self.out.flush() {
+                    Err(e) if e.kind() == io::ErrorKind::BrokenPipe => self.stdout_closed(&[]),
+                    result => result,
+                },
+            }
+        }
+
+        /// See LogFile::set_batching.
+        fn set_batching(&mut self, on: bool) -> io::Result<()> {
+            if on {
+                self.pending.get_or_insert_with(Vec::new);
+                return Ok(());
+            }
+            if let Some(pending) = self.pending.take() {
+                self.write_out(&pending)?;
+            }
+            self.flush()
+        }
     }
 
-    #[derive(Debug)]
-    pub struct LoggerOpts<'a> {
+    /// The settings a Logger is built from.
+    ///
+    /// Nothing is opened until a Logger is created, so a base configuration
+    /// can be cloned and tweaked for each logger that needs it.
+    #[derive(Debug, Clone)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub struct LoggerOpts {
         log_file_name: String,
-        log_type: LogType<'a>,
+        log_target: LogTarget,
         log_label: String,
+        #[cfg_attr(feature = "serde", serde(skip))]
+        label_fn: Option<LabelFn>,
+        internal_log: InternalLog,
+        #[cfg_attr(feature = "serde", serde(skip))]
+        internal_fn: Option<ProblemFn>,
         dt_format: String,
+        clock: Clock,
+        console_clock: Option<Clock>,
+        file_clock: Option<Clock>,
         use_dt: bool,
         use_label: bool,
+        console_format: LogFormat,
+        file_format: LogFormat,
+        console_wrap: bool,
+        console_decorations: bool,
+        console_fallback: Option<PathBuf>,
+        console_split: Option<Level>,
+        file_decorations: bool,
+        json_pretty: bool,
+        level_style: LevelStyle,
+        localization: Localization,
+        layout: Layout,
+        filter: Filter,
+        static_fields: Vec<(String, String)>,
+        #[cfg_attr(feature = "serde", serde(skip))]
+        log_file: Option<Arc<File>>,
+        pipes: Vec<PipeSpec>,
+        pipe_one_line: bool,
+        files: Vec<FileSpec>,
+        #[cfg(feature = "file")]
+        tenants: Vec<tenant::TenantSpec>,
+        #[cfg_attr(feature = "serde", serde(skip))]
+        captures: Vec<testing::Capture>,
+        replays: Vec<PathBuf>,
+        crash_rings: Vec<crash::CrashRingSpec>,
+        #[cfg(feature = "aws")]
+        #[cfg_attr(feature = "serde", serde(skip))]
+        cloudwatch: Vec<CloudWatch>,
+        #[cfg(feature = "sentry")]
+        #[cfg_attr(feature = "serde", serde(skip))]
+        sentry: Option<Sentry>,
+        buffer: usize,
+        flush_interval: Option<Duration>,
+        max_queue_bytes: usize,
+        max_batch_bytes: usize,
+        progress_interval: Duration,
+        /// How many lines to keep and how long to stay verbose after an
+        /// error, see set_error_context.
+        error_context: Option<(usize, Duration)>,
+        /// Where long errors go, see set_error_detail_file.
+        error_detail_file: Option<PathBuf>,
+        invalid_utf8: InvalidUtf8,
+        sync_on: Option<Level>,
+        event_ids: bool,
+        session_ids: bool,
+        file_header: bool,
+        build_info: Option<BuildInfo>,
+        function_names: bool,
+        per_thread_files: bool,
+        deterministic: bool,
+        time_cache: bool,
+        rotate_size: u64,
+        max_records_per_file: u64,
+        rotation_manifest: bool,
+        archive_dir: Option<PathBuf>,
+        keep_rotated: usize,
+        current_link: bool,
+    }
+
+    impl Default for LoggerOpts {
+        fn default() -> LoggerOpts {
+            LoggerOpts::new()
+        }
     }
 
-    impl<'a> LoggerOpts<'a> {
-        pub fn new() -> LoggerOpts<'a> {
+    impl LoggerOpts {
+        pub fn new() -> LoggerOpts {
             LoggerOpts {
                 log_file_name: String::from("llog.txt"),
-                log_type: LogType::File(LogFile::new("llog.txt")),
+                log_target: LogTarget::default_for_features(),
                 log_label: String::from("LLOG"),
+                label_fn: None,
+                internal_log: InternalLog::Stderr,
+                internal_fn: None,
                 dt_format: String::from("%Y-%m-%d %H:%M:%S"),
+                clock: Clock::Wall,
+                console_clock: None,
+                file_clock: None,
                 use_dt: true,
                 use_label: true,
+                console_format: LogFormat::Text,
+                file_format: LogFormat::Text,
+                console_wrap: false,
+                console_decorations: true,
+                console_fallback: None,
+                console_split: None,
+                file_decorations: true,
+                json_pretty: false,
+                level_style: LevelStyle {
+                    case: LevelCase::Upper,
+                    pad: false,
+                },
+                localization: Localization::default(),
+                layout: Layout::default(),
+                filter: Filter::default(),
+                static_fields: Vec::new(),
+                log_file: None,
+                pipes: Vec::new(),
+                pipe_one_line: false,
+                files: Vec::new(),
+                #[cfg(feature = "file")]
+                tenants: Vec::new(),
+                captures: Vec::new(),
+                replays: Vec::new(),
+                crash_rings: Vec::new(),
+                #[cfg(feature = "aws")]
+                cloudwatch: Vec::new(),
+                #[cfg(feature = "sentry")]
+                sentry: None,
+                buffer: 0,
+                flush_interval: None,
+                max_queue_bytes: 16 << 20,
+                max_batch_bytes: 4 << 20,
+                progress_interval: Duration::from_secs(5),
+                error_context: None,
+                error_detail_file: None,
+                invalid_utf8: InvalidUtf8::Replace,
+                sync_on: None,
+                event_ids: false,
+                session_ids: false,
+                file_header: false,
+                build_info: None,
+                function_names: false,
+                per_thread_files: false,
+                deterministic: false,
+                time_cache: true,
+                rotate_size: 0,
+                max_records_per_file: 0,
+                rotation_manifest: false,
+                archive_dir: None,
+                keep_rotated: 0,
+                current_link: false,
             }
         }
         /// Define where log messages are written.
@@ -145,13 +1005,28 @@ pub mod log {
         /// File, Console, and Both. The names are self explanitory.
         /// The LoggerOpts default is to write to a file. This method is used
         /// to change that setting.
+        ///
+        /// The `file` and `console` cargo features decide which of these are
+        /// compiled in. Asking for one that isn't panics.
+        ///
+        /// For benchmarks there are two more. Null formats every line in the
+        /// file format and discards it, Blackhole skips the formatting too
+        /// and writes nothing at all, extra sinks included. Comparing the
+        /// two with a real target shows where the time goes.
         pub fn set_log_type(mut self, log_type: &str) -> Self {
-            self.log_type = match log_type.to_uppercase().as_str() {
-                "FILE" | "FILEONLY" => LogType::File(LogFile::new("llog.txt")),
-                "CONSOLE" | "CONSOLEONLY" => LogType::Console(LogConsl::new()),
-                "BOTH" => LogType::Both(LogConsl::new(), LogFile::new("llog.txt")),
+            self.log_target = match log_type.to_uppercase().as_str() {
+                "FILE" | "FILEONLY" => LogTarget::File,
+                "CONSOLE" | "CONSOLEONLY" => LogTarget::Console,
+                "BOTH" => LogTarget::Both,
+                "NULL" => LogTarget::Null,
+                "BLACKHOLE" => LogTarget::Blackhole,
                 _ => panic!("Invalid log type provided."),
             };
+            assert!(
+                self.log_target.compiled_in(),
+                "Log type {:?} was compiled out, enable its cargo feature.",
+                self.log_target
+            );
             self
         }
 
@@ -162,7 +1037,7 @@ pub mod log {
         /// This method uses std::env::set_current_dir() to change the current directory.
         ///
         /// DOES NOT SUPPORT SYMLINKS.
-        pub fn set_dest_dir(mut self, new_dest: &str) -> Self{
+        pub fn set_dest_dir(mut self, new_dest: &str) -> Self {
             let dest: &Path = Path::new(new_dest);
             assert!(&dest.is_absolute());
             assert!(&dest.is_dir());
@@ -177,179 +1052,3471 @@ pub mod log {
             self.log_file_name = new_name.to_string();
             self
         }
-        /// Redefine the label at the beginning of the log line.
+        /// Write to a file, pipe or socket that is already open instead of
+        /// opening one by name.
         ///
-        /// Default: LLOG
-        pub fn set_log_label(mut self, new_label: &str) -> Self {
-            self.log_label = new_label.to_string();
+        /// Useful when a supervisor hands the process a descriptor. The
+        /// handle is duplicated for each Logger built from these opts.
+        pub fn set_log_file(mut self, file: File) -> Self {
+            self.log_file = Some(Arc::new(file));
             self
         }
-        /// Redefine the date/time display settings.
+        /// Write to an already open file descriptor, see set_log_file.
+        #[cfg(unix)]
+        pub fn set_log_fd(self, fd: std::os::fd::OwnedFd) -> Self {
+            self.set_log_file(File::from(fd))
+        }
+        /// Write to an already open handle, see set_log_file.
+        #[cfg(windows)]
+        pub fn set_log_handle(self, handle: std::os::windows::io::OwnedHandle) -> Self {
+            self.set_log_file(File::from(handle))
+        }
+        /// Also stream every record into the stdin of a command, for example
+        /// `["logger", "-t", "myapp"]` or `["rotatelogs", "app.%Y%m%d", "86400"]`.
         ///
-        /// Default: 2024-05-21 18:37:22
-        pub fn set_dt_format(mut self, new_format: &str) -> Self {
-            self.dt_format = new_format.to_string();
+        /// The command is started on the first write and started again if
+        /// it exits. This is on top of the log type, not instead of it.
+        pub fn add_pipe_sink(mut self, command: &[&str], format: LogFormat) -> Self {
+            self.pipes.push(PipeSpec {
+                command: command.iter().map(|arg| arg.to_string()).collect(),
+                format,
+            });
             self
         }
-    }
-
-    #[derive(Debug)]
-    pub struct Logger<'a> {
-        msg: String,
-        date_time: String,
-        dt_format: String,
-        log_label: String,
-        log_type: LogType<'a>,
-        use_dt: bool,
-        use_label: bool,
-    }
-
-    impl<'a> fmt::Display for Logger<'a> {
-        fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-            writeln!(formatter, "{}", &self.msg)
+        /// Write every record to the pipe sinks on exactly one line, with
+        /// the newlines in messages and errors escaped as `\n`, so a line
+        /// based reader like fluent-bit or vector never splits one up.
+        /// JsonPretty is written as Json, binary formats are left alone.
+        ///
+        /// Default: false
+        pub fn set_pipe_one_line(mut self, one_line: bool) -> Self {
+            self.pipe_one_line = one_line;
+            self
         }
-    }
-
-    impl<'a> Default for Logger<'a> {
-        fn default() -> Logger<'a> {
-            /// Construct the default logger with predefined options.
-            ///
-            /// # Example:
-            ///
-            /// ```no_run
-            /// use little_logger::log;
-            ///
-            /// let logger = log::Logger::default();
-            /// ```
-            ///
-            let opt = LoggerOpts::new();
-            let opts = opt.set_log_type("file");
-            Logger {
-                date_time: Local::now().format(&opts.dt_format).to_string(),
-                msg: opts.log_file_name.to_string(),
-                dt_format: opts.dt_format.to_string(),
-                log_label: opts.log_label.to_string(),
-                log_type: opts.log_type,
-                use_dt: opts.use_dt,
-                use_label: opts.use_label,
-            }
+        /// Also write every record to another file in its own format, for
+        /// example text to `app.log` and NDJSON to `app.json`.
+        ///
+        /// The extra file is buffered, headed and rotated like the main
+        /// one, but on_rotate only hears about the main one.
+        #[cfg(feature = "file")]
+        pub fn add_file_sink(mut self, path: &str, format: LogFormat) -> Self {
+            self.files.push(FileSpec {
+                path: path.to_string(),
+                format,
+            });
+            self
         }
-    }
-
-    impl<'a> Logger<'a> {
-        pub fn new(opts: LoggerOpts) -> Logger {
-            /// Construct a new logger with custom options.
-            ///
-            /// # Example:
-            ///
-            /// ```no_run
-            /// use little_logger::log;
-            ///
-            /// let mut opts =  LoggerOptions::new();
-            /// let logger = log::Logger::new();
-            /// ```
-            ///
-            Logger {
-                date_time: Local::now().format(&opts.dt_format).to_string(),
-                msg: opts.log_file_name.to_string(),
-                dt_format: opts.dt_format.to_string(),
-                log_label: opts.log_label.to_string(),
-                log_type: opts.log_type,
-                use_dt: opts.use_dt,
-                use_label: opts.use_label,
-            }
+        /// Also keep every record, whole, in a replay file. replay::replay
+        /// formats them again later, to check a format change against what
+        /// production really logs.
+        pub fn add_replay_sink<P: AsRef<Path>>(mut self, path: P) -> Self {
+            self.replays.push(path.as_ref().to_path_buf());
+            self
         }
-
-        fn update_time(&mut self) {
-            self.date_time = Local::now().format(&self.dt_format).to_string();
+        /// Also keep the last `kb` KB of output, in `format`, in a file
+        /// mapped into memory. A process killed without warning still
+        /// leaves its final records there, crash::recover reads them
+        /// back. A path under `/dev/shm` keeps them off the disk.
+        ///
+        /// A ring left by the run before is moved to `<path>.prev`, once,
+        /// when the first ring at `path` is made in this process.
+        #[cfg(unix)]
+        pub fn add_crash_ring<P: AsRef<Path>>(
+            mut self,
+            path: P,
+            kb: usize,
+            format: LogFormat,
+        ) -> Self {
+            self.crash_rings.push(crash::CrashRingSpec {
+                path: Some(path.as_ref().to_path_buf()),
+                bytes: kb.max(1) * 1024,
+                format,
+            });
+            self
         }
-
-        fn update_log_line(&mut self, msg: (&str, &str)) {
-            self.update_time();
-            self.msg = format!(
-                "[{}]::[{}] -> {}\n{}",
-                self.log_label, self.date_time, msg.0, msg.1
-            );
+        /// Also keep the last `kb` KB of output, in `format`, in memory,
+        /// where a core dump or minidump of the process carries them. The
+        /// `LLOG_MEMORY_RING` symbol points at them, see the crash module
+        /// for how to get them out.
+        ///
+        /// The symbol points at one ring per process, the first made that
+        /// is still alive.
+        pub fn add_memory_ring(mut self, kb: usize, format: LogFormat) -> Self {
+            self.crash_rings.push(crash::CrashRingSpec {
+                path: None,
+                bytes: kb.max(1) * 1024,
+                format,
+            });
+            self
         }
-
-        pub fn log_message<S: Into<&'a str>>(mut self, msg: S) {
-            /// Use this function to log messages.
-            ///
-            /// How you define log::LoggerOpts defines whether you log to the console,
-            /// a file, or both. If you didn't this will default to a file named llog.txt.
-            ///
-            /// # Examle:
-            /// ```no_run
-            ///
-            /// let message: &str = "Message to be logged";
-            /// let mut logger = Logger::new();
-            ///
-            /// logger.log_message(message, None);
-            ///
-            /// ```
-            
-            self.update_log_line((msg.into(), ""));
-            let msg_to_write: &str = self.msg.as_str();
-            match self.log_type {
-                LogType::Both(ref mut file, ref mut console) => {
-                    file.out.write_all(msg_to_write.as_bytes());
-                    console.out.write_all(msg_to_write.as_bytes());
-                }
-                LogType::File(ref mut file) => {
-                    file.out.write_all(msg_to_write.as_bytes());
-                }
-                LogType::Console(ref mut console) => {
-                    console.out.write_all(msg_to_write.as_bytes());
-                }
-            };
+        /// Also write each record to a file of its tenant's, named by
+        /// putting the value of `field` into `path` in place of `{tenant}`:
+        /// `add_tenant_sink("tenant_id", "logs/{tenant}/app.log", ..)`.
+        ///
+        /// At most `max_open` files are kept open, the least recently used
+        /// one is closed to make room. Records without the field only go to
+        /// the other sinks.
+        #[cfg(feature = "file")]
+        pub fn add_tenant_sink(
+            mut self,
+            field: &str,
+            path: &str,
+            format: LogFormat,
+            max_open: usize,
+        ) -> Self {
+            self.tenants.push(tenant::TenantSpec {
+                field: field.to_string(),
+                path: path.to_string(),
+                format,
+                max_open,
+            });
+            self
         }
-
-        pub fn log_msg_and_error<S: Into<String>>(mut self, msg: S, err: S) {
-            /// Use this function to log messages and include an error.
-            ///
-            /// Msg and err can both be passed as String or &str.
+        /// Also send every record to a CloudWatch Logs stream. See
+        /// CloudWatch.
+        #[cfg(feature = "aws")]
+        pub fn add_cloudwatch_sink(mut self, cloudwatch: CloudWatch) -> Self {
+            self.cloudwatch.push(cloudwatch);
+            self
+        }
+        /// Report error records to Sentry. See Sentry.
+        #[cfg(feature = "sentry")]
+        pub fn set_sentry(mut self, sentry: Sentry) -> Self {
+            self.sentry = Some(sentry);
+            self
+        }
+        /// Also keep every record in `capture`, for tests that check what
+        /// was logged. See the testing module.
+        pub fn set_capture(mut self, capture: &testing::Capture) -> Self {
+            self.captures.push(capture.clone());
+            self
+        }
+        /// Buffer up to this many bytes before writing to the log file.
+        ///
+        /// Much faster for chatty programs, but a crash loses whatever is
+        /// still in the buffer. See set_flush_interval to bound that.
+        ///
+        /// Default: 0, every line is written straight away
+        pub fn set_buffer_size(mut self, bytes: usize) -> Self {
+            self.buffer = bytes;
+            self
+        }
+        /// Flush the log file buffer from a background thread this often.
+        ///
+        /// Only has an effect together with set_buffer_size. A crash then
+        /// loses at most one interval of output.
+        ///
+        /// Default: none, the buffer is flushed when full or on drop
+        pub fn set_flush_interval(mut self, interval: Duration) -> Self {
+            self.flush_interval = Some(interval);
+            self
+        }
+        /// Flush and sync the log file to disk after every line at this
+        /// level or above.
+        ///
+        /// Lines below the level keep the speed of buffering while, say,
+        /// errors are sure to survive a crash right after them.
+        ///
+        /// Default: none
+        pub fn set_sync_on(mut self, level: Level) -> Self {
+            self.sync_on = Some(level);
+            self
+        }
+        /// Stamp every record with a ULID in an `event_id` field.
+        ///
+        /// Every sink gets the same id for the same record, so a line in
+        /// the text file can be found again in the JSON sent elsewhere.
+        ///
+        /// Default: false
+        pub fn set_event_ids(mut self, event_ids: bool) -> Self {
+            self.event_ids = event_ids;
+            self
+        }
+        /// Add the id of this run of the process to every record, as a
+        /// `session` suffix in text and a `session_id` field otherwise.
+        ///
+        /// Tells restarts apart when they append to the same file. See
+        /// log::session_id.
+        ///
+        /// Default: false
+        pub fn set_session_ids(mut self, session_ids: bool) -> Self {
+            self.session_ids = session_ids;
+            self
+        }
+        /// Start every new log file with a `#little_logger` line naming the
+        /// crate version, format and dt_format.
+        ///
+        /// reader::open uses it to pick the right parser. Binary formats
+        /// get a `.meta` file next to the log instead.
+        ///
+        /// Default: false
+        pub fn set_file_header(mut self, header: bool) -> Self {
+            self.file_header = header;
+            self
+        }
+        /// Say which binary is logging: the build info becomes the
+        /// `version`, `git_sha` and `build_ts` fields of every structured
+        /// record and the text of Logger::log_banner.
+        ///
+        /// ```no_run
+        /// use little_logger::build_info;
+        /// use little_logger::log::{Logger, LoggerOpts};
+        ///
+        /// let mut logger = Logger::new(LoggerOpts::new().set_build_info(build_info!()));
+        /// logger.log_banner();
+        /// ```
+        pub fn set_build_info(self, info: BuildInfo) -> Self {
+            let mut opts = self
+                .add_static_field("version", &info.version)
+                .add_static_field("git_sha", &info.git_sha)
+                .add_static_field("build_ts", &info.build_ts);
+            opts.build_info = Some(info);
+            opts
+        }
+        /// Add the calling function as a `fn` field to lines logged through
+        /// the macros, `fn=my_app::db::query`. Handy when file and line
+        /// point into generated code.
+        ///
+        /// Default: false
+        pub fn set_function_names(mut self, function_names: bool) -> Self {
+            self.function_names = function_names;
+            self
+        }
+        /// Give each thread a log file of its own, `llog.worker-3.txt` for a
+        /// thread named worker-3, so threads never wait on each other.
+        ///
+        /// Build one Logger per thread from clones of these opts. The file
+        /// is named after the thread of the first write. reader::merge_files
+        /// puts the files back together in time order.
+        ///
+        /// Default: false
+        pub fn set_per_thread_files(mut self, per_thread: bool) -> Self {
+            self.per_thread_files = per_thread;
+            self
+        }
+        /// Set to false to write just the message to the console, without
+        /// the label, time and level. Structured formats are unaffected.
+        ///
+        /// Default: true
+        pub fn set_console_decorations(mut self, decorations: bool) -> Self {
+            self.console_decorations = decorations;
+            self
+        }
+        /// When stdout is a pipe whose reader goes away, like a log
+        /// collector sidecar that died, write the console lines to this
+        /// file from then on. Without one they are dropped. Either way it
+        /// is reported once, see set_internal_log.
+        ///
+        /// Default: none
+        pub fn set_console_fallback<P: AsRef<Path>>(mut self, path: P) -> Self {
+            self.console_fallback = Some(path.as_ref().to_path_buf());
+            self
+        }
+        /// Write console records at `level` and above to stderr and the
+        /// rest to stdout, so `app | jq` gets only the output it expects
+        /// while warnings and errors still reach the terminal.
+        ///
+        /// Default: none, every console record goes to stdout
+        pub fn set_console_split(mut self, level: Level) -> Self {
+            self.console_split = Some(level);
+            self
+        }
+        /// Set to false to write just the message to the log file, see
+        /// set_console_decorations.
+        ///
+        /// Default: true
+        pub fn set_file_decorations(mut self, decorations: bool) -> Self {
+            self.file_decorations = decorations;
+            self
+        }
+        /// Make the output of the same calls byte-identical from run to run,
+        /// for property tests and fuzzers that compare logs.
+        ///
+        /// Every record is stamped with the Unix epoch, span timings read
+        /// zero, event and session ids are left out and nothing is
+        /// buffered. Whatever else these opts say about those is ignored.
+        pub fn deterministic(mut self) -> Self {
+            self.deterministic = true;
+            self
+        }
+        /// Format the date/time once per second, or per millisecond for a
+        /// dt_format with `%.3f`, and reuse it for the records in between.
+        /// Formats down to the micro or nanosecond are always formatted
+        /// afresh. Turn it off to format every record's time from scratch.
+        ///
+        /// Default: true
+        pub fn set_time_cache(mut self, cache: bool) -> Self {
+            self.time_cache = cache;
+            self
+        }
+        /// Start a new log file once the current one reaches this many
+        /// bytes. The full one is renamed to `llog.txt.1`, then `.2` and so
+        /// on, lower numbers being older.
+        ///
+        /// Default: 0, never rotate
+        pub fn set_rotate_size(mut self, bytes: u64) -> Self {
+            self.rotate_size = bytes;
+            self
+        }
+        /// Start a new log file once the current one holds this many
+        /// records, for when each file is a batch handed on downstream.
+        /// Works alongside set_rotate_size, whichever fills first.
+        ///
+        /// Default: 0, never rotate
+        pub fn set_max_records_per_file(mut self, records: u64) -> Self {
+            self.max_records_per_file = records;
+            self
+        }
+        /// Write a `.manifest` file next to each rotated file with its
+        /// SHA-256, record count and size, so collectors can check they got
+        /// all of it.
+        ///
+        /// Default: false
+        pub fn set_rotation_manifest(mut self, manifest: bool) -> Self {
+            self.rotation_manifest = manifest;
+            self
+        }
+        /// Move rotated files into this directory instead of leaving them
+        /// next to the live log. It is created on the first rotation.
+        ///
+        /// Default: none, rotated files stay beside the log
+        pub fn set_archive_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+            self.archive_dir = Some(dir.as_ref().to_path_buf());
+            self
+        }
+        /// Keep only this many rotated files, deleting the oldest after
+        /// each rotation. Applies in the archive dir when one is set.
+        ///
+        /// Default: 0, keep them all
+        pub fn set_keep_rotated(mut self, count: usize) -> Self {
+            self.keep_rotated = count;
+            self
+        }
+        /// Keep a `llog.txt.current` link next to the log pointing at the
+        /// file being written, so `tail -F llog.txt.current` survives
+        /// rotation.
+        ///
+        /// A symlink on unix, a hard link or copy on other systems.
+        ///
+        /// Default: false
+        pub fn set_current_link(mut self, link: bool) -> Self {
+            self.current_link = link;
+            self
+        }
+        /// Redefine the label at the beginning of the log line.
+        ///
+        /// Default: LLOG
+        pub fn set_log_label(mut self, new_label: &str) -> Self {
+            self.log_label = new_label.to_string();
+            self
+        }
+        /// Work out the label of each record with `label_fn` instead of
+        /// using the fixed one, for example from the tenant a thread is
+        /// serving. A label given with Logger::with_label still wins.
+        ///
+        /// ```no_run
+        /// use little_logger::log::{Logger, LoggerOpts};
+        ///
+        /// thread_local!(static TENANT: String = String::from("acme"));
+        ///
+        /// let opts = LoggerOpts::new().set_label_fn(|_| TENANT.with(|t| t.clone()));
+        /// Logger::new(opts).info("invoice sent");
+        /// ```
+        pub fn set_label_fn<F>(mut self, label_fn: F) -> Self
+        where
+            F: Fn(&RecordMeta) -> String + Send + Sync + 'static,
+        {
+            self.label_fn = Some(LabelFn(Arc::new(label_fn)));
+            self
+        }
+        /// Where the logger reports its own problems: records it had to
+        /// drop, a file it couldn't rotate, a sink failing or coming back.
+        /// InternalLog::Stream writes them into the log as Warn records
+        /// labelled `LLOG-INTERNAL`.
+        ///
+        /// Default: InternalLog::Stderr
+        pub fn set_internal_log(mut self, internal_log: InternalLog) -> Self {
+            self.internal_log = internal_log;
+            self
+        }
+        /// Hand each of the logger's own problems to `callback` instead,
+        /// say to bump a metric.
+        ///
+        /// ```no_run
+        /// use little_logger::log::{Logger, LoggerOpts};
+        ///
+        /// let opts = LoggerOpts::new().on_internal_problem(|problem| {
+        ///     eprintln!("logging is broken: {problem}");
+        /// });
+        /// Logger::new(opts).info("ready");
+        /// ```
+        pub fn on_internal_problem<F>(mut self, callback: F) -> Self
+        where
+            F: Fn(&str) + Send + Sync + 'static,
+        {
+            self.internal_fn = Some(ProblemFn(Arc::new(callback)));
+            self
+        }
+        /// Redefine the date/time display settings.
+        ///
+        /// Default: 2024-05-21 18:37:22
+        pub fn set_dt_format(mut self, new_format: &str) -> Self {
+            self.dt_format = new_format.to_string();
+            self
+        }
+        /// How log_lossy and forwarded streams show bytes that aren't
+        /// UTF-8.
+        ///
+        /// Default: InvalidUtf8::Replace
+        pub fn set_invalid_utf8(mut self, invalid: InvalidUtf8) -> Self {
+            self.invalid_utf8 = invalid;
+            self
+        }
+        /// Keep the last `keep` Debug and Info lines the level filters out
+        /// in memory. When an Error is logged they are written ahead of it,
+        /// at the time they were logged, and for `window` after it Debug
+        /// lines are written as they come. Detail right where it's needed
+        /// without paying for it all the time. Lines turned off by
+        /// set_target_level are never kept.
+        ///
+        /// Default: off
+        pub fn set_error_context(mut self, keep: usize, window: Duration) -> Self {
+            self.error_context = Some((keep, window));
+            self
+        }
+        /// Write errors that run over several lines, like backtraces, or
+        /// past 160 characters to this file, `errors-detail.log` say. The
+        /// log line keeps the first line of the error and a `detail` field
+        /// with the id of the entry in the file. Each entry is headed by
+        /// the record as the log file writes it, without the error.
+        ///
+        /// Default: none, errors are written in full
+        pub fn set_error_detail_file<P: AsRef<Path>>(mut self, path: P) -> Self {
+            self.error_detail_file = Some(path.as_ref().to_path_buf());
+            self
+        }
+        /// The most bytes a remote sink, CloudWatch or Sentry, may have
+        /// waiting for its sending thread. Past it records for that sink
+        /// are dropped and counted in Logger::stats, so a slow or
+        /// unreachable endpoint can't run the process out of memory.
+        ///
+        /// Default: 16 MiB per sink
+        pub fn set_max_queue_bytes(mut self, bytes: usize) -> Self {
+            self.max_queue_bytes = bytes;
+            self
+        }
+        /// The most bytes an open Batch holds back for the console or log
+        /// file. Past it what is held is written out early, so a long
+        /// batch costs a few more writes rather than unbounded memory.
+        ///
+        /// Default: 4 MiB
+        pub fn set_max_batch_bytes(mut self, bytes: usize) -> Self {
+            self.max_batch_bytes = bytes;
+            self
+        }
+        /// The least time between two Logger::progress lines for the same
+        /// name.
+        ///
+        /// Default: 5 seconds
+        pub fn set_progress_interval(mut self, interval: Duration) -> Self {
+            self.progress_interval = interval;
+            self
+        }
+        /// Show the time since the Logger was created in place of, or next
+        /// to, the wall clock.
+        ///
+        /// Default: Clock::Wall
+        pub fn set_clock(mut self, clock: Clock) -> Self {
+            self.clock = clock;
+            self
+        }
+        /// Use a different clock for the console than set_clock, say
+        /// Clock::Monotonic while the file keeps the wall clock.
+        ///
+        /// Default: the set_clock clock
+        pub fn set_console_clock(mut self, clock: Clock) -> Self {
+            self.console_clock = Some(clock);
+            self
+        }
+        /// Use a different clock for the log file than set_clock.
+        ///
+        /// Default: the set_clock clock
+        pub fn set_file_clock(mut self, clock: Clock) -> Self {
+            self.file_clock = Some(clock);
+            self
+        }
+        /// Redefine the layout of lines written to the console.
+        ///
+        /// Default: LogFormat::Text
+        pub fn set_console_format(mut self, format: LogFormat) -> Self {
+            self.console_format = format;
+            self
+        }
+        /// Redefine the layout of lines written to the log file.
+        ///
+        /// Default: LogFormat::Text
+        pub fn set_file_format(mut self, format: LogFormat) -> Self {
+            self.file_format = format;
+            self
+        }
+        /// Write indented, multi-line JSON wherever a sink uses LogFormat::Json.
+        ///
+        /// A single sink can be made pretty with LogFormat::JsonPretty instead.
+        ///
+        /// Default: false
+        pub fn set_json_pretty(mut self, pretty: bool) -> Self {
+            self.json_pretty = pretty;
+            self
+        }
+        /// Soft wrap long console lines at the terminal width.
+        ///
+        /// Continuation lines are indented. The width comes from `COLUMNS`
+        /// or the terminal itself. File output is never wrapped.
+        ///
+        /// Default: false
+        pub fn set_console_wrap(mut self, wrap: bool) -> Self {
+            self.console_wrap = wrap;
+            self
+        }
+        /// Redefine how level names are spelled: INFO, info or I.
+        ///
+        /// Default: LevelCase::Upper
+        pub fn set_level_case(mut self, case: LevelCase) -> Self {
+            self.level_style.case = case;
+            self
+        }
+        /// Pad level names to the same width so the columns line up.
+        ///
+        /// Default: false
+        pub fn set_level_padding(mut self, pad: bool) -> Self {
+            self.level_style.pad = pad;
+            self
+        }
+        /// Write level names and the crate's own words in another language.
+        /// See Localization.
+        ///
+        /// Default: English
+        pub fn set_localization(mut self, localization: Localization) -> Self {
+            self.localization = localization;
+            self
+        }
+        /// Redefine the separators of the text formats.
+        ///
+        /// `label_sep` goes between the bracketed fields and `msg_sep` goes
+        /// in front of the message. Spaces are not added for you.
+        ///
+        /// Default: "::" and " -> "
+        pub fn set_separators(mut self, label_sep: &str, msg_sep: &str) -> Self {
+            self.layout.label_sep = label_sep.to_string();
+            self.layout.msg_sep = msg_sep.to_string();
+            self
+        }
+        /// Name the levels on another scale in the formats meant for other
+        /// systems, LogFormat::Gcp and LogFormat::Datadog.
+        ///
+        /// Default: each format's own, SeverityMap::gcp and
+        /// SeverityMap::datadog
+        pub fn set_severity_map(mut self, map: SeverityMap) -> Self {
+            self.layout.severity = Some(map);
+            self
+        }
+        /// Add a field to every record written in a structured format.
+        ///
+        /// Handy for tagging every line with the service or environment.
+        /// Setting the same key twice keeps the last value.
+        pub fn add_static_field(mut self, key: &str, value: &str) -> Self {
+            match self.static_fields.iter_mut().find(|(k, _)| k == key) {
+                Some(field) => field.1 = value.to_string(),
+                None => self
+                    .static_fields
+                    .push((key.to_string(), value.to_string())),
+            }
+            self
+        }
+        /// Drop every line below this level.
+        ///
+        /// Default: Level::Trace
+        pub fn set_level(mut self, level: Level) -> Self {
+            self.filter.default = level;
+            self
+        }
+        /// Override the level for one target and everything below it.
+        ///
+        /// Targets are module paths, so `"my_app::db"` also covers
+        /// `"my_app::db::pool"`. The longest matching target wins.
+        /// Only lines logged with a target, e.g. through the macros, are
+        /// matched against these.
+        pub fn set_target_level(mut self, target: &str, level: Level) -> Self {
+            self.filter.set(target, level);
+            self
+        }
+        /// Only write lines carrying one of these labels.
+        ///
+        /// Labels include those given to Logger::with_label.
+        ///
+        /// Default: every label
+        pub fn set_label_allowlist<I, S>(mut self, labels: I) -> Self
+        where
+            I: IntoIterator<Item = S>,
+            S: AsRef<str>,
+        {
+            let labels = labels.into_iter().map(|l| l.as_ref().to_string());
+            self.filter.label_allow = Some(labels.collect());
+            self
+        }
+        /// Never write lines carrying one of these labels, even if they
+        /// are on the allowlist.
+        ///
+        /// Default: none
+        pub fn set_label_denylist<I, S>(mut self, labels: I) -> Self
+        where
+            I: IntoIterator<Item = S>,
+            S: AsRef<str>,
+        {
+            let labels = labels.into_iter().map(|l| l.as_ref().to_string());
+            self.filter.label_deny = labels.collect();
+            self
+        }
+
+        /// The format a sink really uses once set_json_pretty is applied.
+        fn sink_format(&self, format: LogFormat) -> LogFormat {
+            match format {
+                LogFormat::Json if self.json_pretty => LogFormat::JsonPretty,
+                format => format,
+            }
+        }
+    }
+
+    /// The id of this run of the process, a ULID made on first use.
+    ///
+    /// Every Logger with set_session_ids puts it on its records.
+    pub fn session_id() -> &'static str {
+        ulid::session_id()
+    }
+
+    /// What a single call asks to be logged, before any formatting.
+    #[derive(Debug, Clone, Copy)]
+    struct Line<'m> {
+        level: Level,
+        target: Option<&'m str>,
+        /// Replaces the logger's label for this line only.
+        label: Option<&'m str>,
+        msg: &'m str,
+        err: Option<&'m str>,
+        err_kind: Option<&'m str>,
+        err_code: Option<&'m str>,
+        /// What msg was filled in from, see log_t.
+        template: Option<&'m str>,
+        kv: &'m [(&'m str, &'m str)],
+        /// The kv keys that are numbers or bools.
+        typed: &'m [&'m str],
+        /// The enter or exit line of the innermost span, which sits at the
+        /// depth of its parent.
+        span_edge: bool,
+        /// The calling function, from the macros.
+        function: Option<&'m str>,
+        /// Written as is by the text formats, see log_raw.
+        raw: bool,
+        /// The old and new value, see log_change.
+        change: Option<(&'m str, &'m str)>,
+        /// Where the macro that logged it was called.
+        location: Option<&'static Location<'static>>,
+        /// Overrides the dt_format for this line only.
+        ts_format: Option<&'m str>,
+    }
+
+    impl<'m> Line<'m> {
+        fn new(level: Level, msg: &'m str) -> Line<'m> {
+            Line {
+                level,
+                target: None,
+                label: None,
+                msg,
+                err: None,
+                err_kind: None,
+                err_code: None,
+                template: None,
+                kv: &[],
+                typed: &[],
+                span_edge: false,
+                function: None,
+                raw: false,
+                change: None,
+                location: None,
+                ts_format: None,
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct Logger {
+        msg: String,
+        date_time: String,
+        dt_format: String,
+        log_label: Arc<str>,
+        log_type: LogType,
+        use_dt: bool,
+        use_label: bool,
+        level_style: LevelStyle,
+        layout: Layout,
+        opts: LoggerOpts,
+        thresholds: Vec<Threshold>,
+        mutes: Vec<mute::Mute>,
+        /// Written after the console and file, each on its own so one
+        /// failing doesn't stop the rest. LocalHandles write to the same
+        /// ones.
+        sinks: Vec<SharedSink>,
+        #[cfg(feature = "watch")]
+        config_watch: Option<watch::ConfigWatch>,
+        /// The ids of the spans entered, innermost last.
+        spans: Vec<u64>,
+        next_span_id: u64,
+        event_ids: ulid::UlidGenerator,
+        /// Whether a Batch is holding back output.
+        batching: bool,
+        #[cfg(feature = "file")]
+        rotate_hooks: Vec<rotate::RotateHook>,
+        /// When the Logger was created, for Clock::Elapsed.
+        started: Instant,
+        /// What went wrong while writing, see report_problems.
+        problems: internal::Problems,
+        /// Write times of the console and log file, see Logger::stats.
+        stats: Stats,
+        /// When a failing log file or console is tried again, as the
+        /// sinks are.
+        #[cfg(feature = "file")]
+        file_backoff: sink::Backoff,
+        #[cfg(feature = "console")]
+        console_backoff: sink::Backoff,
+        /// Set while the problems are being reported.
+        reporting: bool,
+        progress: Vec<progress::Progress>,
+        /// What open Transactions are holding back.
+        held: Option<Vec<transaction::HeldLine>>,
+        /// The time a held line was logged at, while it is written.
+        replay_time: Option<(chrono::DateTime<Local>, Duration)>,
+        time_cache: clock::TimeCache,
+        /// Labels and targets the records share, see HeldLine. Handles
+        /// made from this Logger share it too.
+        strings: intern::Shared,
+        /// Filtered lines kept in case an error follows.
+        error_context: Option<context::ErrorContext>,
+        error_detail: Option<detail::ErrorDetail>,
+    }
+
+    impl fmt::Display for Logger {
+        fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            writeln!(formatter, "{}", &self.msg)
+        }
+    }
+
+    impl Default for Logger {
+        fn default() -> Logger {
+            /// Construct the default logger with predefined options.
             ///
-            /// How you define log::LoggerOpts defines whether you log to the console,
-            /// a file, or both. If you didn't this will default to a file named llog.txt.
+            /// # Example:
             ///
-            /// # Examle:
             /// ```no_run
+            /// use little_logger::log;
             ///
-            /// let message: &str = "Message to be logged";
-            /// let mut logger = Logger::new();
-            /// let err = match some_result = {
-            ///      Ok(val) => val,
-            ///      Err(e) => e,
-            /// }
+            /// let logger = log::Logger::default();
+            /// ```
             ///
-            /// logger.log_message(message, err);
+            Logger::new(LoggerOpts::new())
+        }
+    }
+
+    impl Logger {
+        pub fn new(opts: LoggerOpts) -> Logger {
+            Logger::with_problems(opts, internal::Problems::default())
+        }
+
+        /// A Logger reporting to `problems`, which reconfiguring keeps so
+        /// the config watch and rotate hooks still reach it.
+        fn with_problems(mut opts: LoggerOpts, problems: internal::Problems) -> Logger {
+            /// Construct a new logger with custom options.
+            ///
+            /// # Example:
+            ///
+            /// ```no_run
+            /// use little_logger::log;
             ///
+            /// let mut opts =  LoggerOptions::new();
+            /// let logger = log::Logger::new();
             /// ```
-            
-            self.update_log_line((&msg.into(), &err.into()));
-            let msg_to_write: &str = self.msg.as_str();
-            match self.log_type {
-                LogType::Both(ref mut file, ref mut console) => {
-                    file.out.write_all(msg_to_write.as_bytes());
-                    console.out.write_all(msg_to_write.as_bytes());
-                }
-                LogType::File(ref mut file) => {
-                    file.out.write_all(msg_to_write.as_bytes());
-                }
-                LogType::Console(ref mut console) => {
-                    console.out.write_all(msg_to_write.as_bytes());
+            ///
+            if opts.deterministic {
+                opts.event_ids = false;
+                opts.session_ids = false;
+                opts.buffer = 0;
+                opts.flush_interval = None;
+            }
+            let console_format = opts.sink_format(opts.console_format);
+            let file_format = opts.sink_format(opts.file_format);
+            let log_type = match opts.log_target {
+                #[cfg(feature = "file")]
+                LogTarget::File => LogType::File(LogFile::new(&opts, file_format)),
+                #[cfg(feature = "console")]
+                LogTarget::Console => {
+                    LogType::Console(LogConsl::new(&opts, console_format, &problems))
                 }
+                #[cfg(all(feature = "console", feature = "file"))]
+                LogTarget::Both => LogType::Both(
+                    LogConsl::new(&opts, console_format, &problems),
+                    LogFile::new(&opts, file_format),
+                ),
+                LogTarget::Null => LogType::Null(NullSink {
+                    format: file_format,
+                }),
+                LogTarget::Blackhole => LogType::Blackhole,
+                // set_log_type never lets a compiled out target through.
+                #[allow(unreachable_patterns)]
+                _ => unreachable!(),
             };
+            let mut sinks: Vec<Box<dyn Sink>> = opts
+                .pipes
+                .iter()
+                .map(|spec| {
+                    Box::new(PipeSink::new(spec.clone(), opts.pipe_one_line)) as Box<dyn Sink>
+                })
+                .collect();
+            #[cfg(feature = "file")]
+            for spec in &opts.files {
+                let format = opts.sink_format(spec.format);
+                sinks.push(LogFile::at(&opts, &spec.path, format));
+            }
+            #[cfg(feature = "file")]
+            for spec in &opts.tenants {
+                sinks.push(Box::new(tenant::TenantSink::new(spec.clone(), &opts)));
+            }
+            for capture in &opts.captures {
+                sinks.push(Box::new(capture.clone()));
+            }
+            for path in &opts.replays {
+                sinks.push(Box::new(replay::ReplaySink::new(path.clone())));
+            }
+            for spec in &opts.crash_rings {
+                let format = opts.sink_format(spec.format);
+                sinks.push(Box::new(crash::CrashRingSink::new(crash::CrashRingSpec {
+                    format,
+                    ..spec.clone()
+                })));
+            }
+            #[cfg(feature = "aws")]
+            for spec in &opts.cloudwatch {
+                let queue = QueueLimit::new(opts.max_queue_bytes);
+                sinks.push(Box::new(cloudwatch::CloudWatchSink::new(
+                    spec.clone(),
+                    queue,
+                    problems.clone(),
+                )));
+            }
+            #[cfg(feature = "sentry")]
+            if let Some(spec) = &opts.sentry {
+                let queue = QueueLimit::new(opts.max_queue_bytes);
+                sinks.push(Box::new(sentry::SentrySink::new(
+                    spec.clone(),
+                    queue,
+                    problems.clone(),
+                )));
+            }
+            let sinks = sinks
+                .into_iter()
+                .map(|sink| SharedSink::new(Isolated::new(sink, problems.clone())))
+                .collect();
+            let strings = intern::Shared::default();
+            Logger {
+                date_time: Local::now().format(&opts.dt_format).to_string(),
+                msg: opts.log_file_name.to_string(),
+                dt_format: opts.dt_format.to_string(),
+                log_label: strings.intern(&opts.log_label),
+                log_type,
+                use_dt: opts.use_dt,
+                use_label: opts.use_label,
+                level_style: opts.level_style,
+                layout: opts.layout.clone(),
+                error_context: opts
+                    .error_context
+                    .map(|(keep, window)| context::ErrorContext::new(keep, window)),
+                error_detail: opts.error_detail_file.clone().map(detail::ErrorDetail::new),
+                opts,
+                thresholds: Vec::new(),
+                mutes: Vec::new(),
+                sinks,
+                #[cfg(feature = "watch")]
+                config_watch: None,
+                spans: Vec::new(),
+                next_span_id: 0,
+                event_ids: ulid::UlidGenerator::default(),
+                batching: false,
+                #[cfg(feature = "file")]
+                rotate_hooks: Vec::new(),
+                started: Instant::now(),
+                problems,
+                reporting: false,
+                stats: Stats::default(),
+                #[cfg(feature = "file")]
+                file_backoff: sink::Backoff::default(),
+                #[cfg(feature = "console")]
+                console_backoff: sink::Backoff::default(),
+                progress: Vec::new(),
+                held: None,
+                replay_time: None,
+                strings,
+                time_cache: clock::TimeCache::default(),
+            }
         }
 
-    }
+        /// Run `callback` when `count` lines at `level` or above are logged
+        /// within `window`.
+        ///
+        /// The callback gets the number of lines seen and the count starts
+        /// over afterwards, so a burst of errors only raises one alert.
+        ///
+        /// ```no_run
+        /// use std::time::Duration;
+        /// use little_logger::log::{Level, Logger};
+        ///
+        /// let mut logger = Logger::default();
+        /// logger.on_threshold(Level::Error, 50, Duration::from_secs(60), |seen| {
+        ///     eprintln!("{} errors in the last minute", seen);
+        /// });
+        /// ```
+        pub fn on_threshold<F>(&mut self, level: Level, count: usize, window: Duration, callback: F)
+        where
+            F: FnMut(usize) + Send + 'static,
+        {
+            self.thresholds
+                .push(Threshold::new(level, count, window, callback));
+        }
+
+        /// Drop the records `matches` picks for the next `duration`, to quiet
+        /// a known noisy source during an incident without a redeploy.
+        ///
+        /// When the mute runs out the number of records it dropped is
+        /// reported like the logger's other problems, see set_internal_log.
+        ///
+        /// ```no_run
+        /// use std::time::Duration;
+        /// use little_logger::log::Logger;
+        ///
+        /// let mut logger = Logger::default();
+        /// logger.mute_for(Duration::from_secs(300), |r| r.label == "HEALTHCHECK");
+        /// ```
+        pub fn mute_for<F>(&mut self, duration: Duration, matches: F)
+        where
+            F: Fn(&RecordMeta) -> bool + Send + 'static,
+        {
+            self.mutes
+                .push(mute::Mute::new(Instant::now() + duration, matches));
+        }
+
+        /// End every mute early, see mute_for.
+        pub fn unmute(&mut self) {
+            for mute in std::mem::take(&mut self.mutes) {
+                mute.end(&self.problems);
+            }
+            self.report_problems();
+        }
+
+        /// Run `callback` after each rotation with the log path and where
+        /// the full file was moved to.
+        ///
+        /// Runs on the logging thread, so hand slow work like uploads off
+        /// to another thread.
+        ///
+        /// ```no_run
+        /// use little_logger::log::{Logger, LoggerOpts};
+        ///
+        /// let mut logger = Logger::new(LoggerOpts::new().set_rotate_size(1 << 20));
+        /// logger.on_rotate(|live, rotated| {
+        ///     println!("{} is full, it is now {}", live.display(), rotated.display());
+        /// });
+        /// ```
+        #[cfg(feature = "file")]
+        pub fn on_rotate<F>(&mut self, callback: F)
+        where
+            F: FnMut(&Path, &Path) + Send + 'static,
+        {
+            self.rotate_hooks
+                .push(rotate::RotateHook(Box::new(callback)));
+        }
+
+        /// Upload each rotated file, and its manifest if there is one, to
+        /// an S3-compatible bucket. See S3Upload.
+        ///
+        /// Uploads run on a thread of their own so logging never waits on
+        /// the network.
+        #[cfg(feature = "s3")]
+        pub fn upload_rotated(&mut self, upload: S3Upload) {
+            let problems = self.problems.clone();
+            self.on_rotate(move |_, rotated| upload.spawn(rotated, problems.clone()));
+        }
+
+        /// Log the next lines with a different label.
+        ///
+        /// ```no_run
+        /// use little_logger::log::Logger;
+        ///
+        /// let mut logger = Logger::default();
+        /// logger.with_label("BILLING").warn("charge failed");
+        /// ```
+        pub fn with_label(&mut self, label: &str) -> Labeled<'_> {
+            Labeled {
+                label: self.strings.intern(label),
+                logger: self,
+            }
+        }
+
+        /// Hold back the console and file output of everything logged
+        /// through the returned guard, then write it with one call each and
+        /// flush when the guard is dropped.
+        ///
+        /// Cuts the locking and syscalls for bursts of related lines, and
+        /// keeps them together when several loggers share a file.
+        ///
+        /// ```no_run
+        /// use little_logger::log::Logger;
+        ///
+        /// let mut logger = Logger::default();
+        /// let mut batch = logger.batch();
+        /// for row in 0..100 {
+        ///     batch.debug(format!("imported row {}", row));
+        /// }
+        /// ```
+        pub fn batch(&mut self) -> Batch<'_> {
+            Batch::new(self)
+        }
+
+        /// A Logger for the calling thread that shares this one's log file
+        /// but buffers on its own, so many threads logging heavily don't
+        /// all queue up on one lock. See LocalHandle.
+        ///
+        /// Console output is unchanged and pipe sinks stay with this
+        /// Logger only.
+        ///
+        /// ```no_run
+        /// use little_logger::log::Logger;
+        ///
+        /// let mut logger = Logger::default();
+        /// let workers: Vec<_> = (0..32)
+        ///     .map(|n| {
+        ///         let mut handle = logger.thread_local_handle();
+        ///         std::thread::spawn(move || handle.info(format!("worker {} up", n)))
+        ///     })
+        ///     .collect();
+        /// ```
+        pub fn thread_local_handle(&mut self) -> LocalHandle {
+            // The sinks are this Logger's, a handle only gets its own file.
+            let mut opts = self.opts.clone();
+            opts.pipes.clear();
+            opts.files.clear();
+            #[cfg(feature = "file")]
+            opts.tenants.clear();
+            opts.captures.clear();
+            opts.replays.clear();
+            opts.crash_rings.clear();
+            #[cfg(feature = "aws")]
+            opts.cloudwatch.clear();
+            #[cfg(feature = "sentry")]
+            {
+                opts.sentry = None;
+            }
+            let mut handle = Logger::new(opts);
+            handle.sinks = self.sinks.clone();
+            handle.opts = self.opts.clone();
+            handle.strings = self.strings.clone();
+            handle.log_label = Arc::clone(&self.log_label);
+            #[cfg(feature = "file")]
+            if let (Some(file), Some(local)) =
+                (self.log_type.file_mut(), handle.log_type.file_mut())
+            {
+                if let Ok(shared) = file.local_handle(&handle.opts) {
+                    *local = shared;
+                }
+            }
+            LocalHandle::new(handle)
+        }
+
+        /// Log every line read from `src` at `level` until it ends, and
+        /// return how many there were.
+        ///
+        /// The `llog-ingest` binary, built with the `clap` feature, does this
+        /// for stdin: `make 2>&1 | llog-ingest --label BUILD`.
+        pub fn ingest<R: BufRead>(&mut self, src: R, level: Level) -> io::Result<usize> {
+            forward::log_lines(src, self, level, &[])
+        }
+
+        /// Log each line the child writes to its stdout and stderr, tagged
+        /// with `stream` and `pid` fields, from a thread per pipe.
+        ///
+        /// Only pipes the child was spawned with `Stdio::piped()` are read.
+        /// Join the returned threads after the child exits to be sure its
+        /// last lines were written.
+        ///
+        /// ```no_run
+        /// use std::process::{Command, Stdio};
+        /// use little_logger::log::{Level, Logger};
+        ///
+        /// let mut logger = Logger::default();
+        /// let mut child = Command::new("make")
+        ///     .stdout(Stdio::piped())
+        ///     .stderr(Stdio::piped())
+        ///     .spawn()
+        ///     .unwrap();
+        /// let readers = logger.forward_child(&mut child, Level::Info, Level::Warn);
+        /// child.wait().unwrap();
+        /// for reader in readers {
+        ///     reader.join().unwrap();
+        /// }
+        /// ```
+        pub fn forward_child(
+            &mut self,
+            child: &mut std::process::Child,
+            level_out: Level,
+            level_err: Level,
+        ) -> Vec<std::thread::JoinHandle<()>> {
+            let pid = child.id().to_string();
+            let mut readers = Vec::new();
+            if let Some(stdout) = child.stdout.take() {
+                let handle = self.thread_local_handle();
+                readers.push(forward::spawn_reader(
+                    handle,
+                    stdout,
+                    level_out,
+                    "stdout",
+                    pid.clone(),
+                ));
+            }
+            if let Some(stderr) = child.stderr.take() {
+                let handle = self.thread_local_handle();
+                readers.push(forward::spawn_reader(
+                    handle, stderr, level_err, "stderr", pid,
+                ));
+            }
+            readers
+        }
+
+        /// Turn batching on or off, returning whether it was on.
+        fn set_batching(&mut self, on: bool) -> bool {
+            let was_on = std::mem::replace(&mut self.batching, on);
+            if was_on == on {
+                return was_on;
+            }
+            let written = match self.log_type {
+                #[cfg(all(feature = "console", feature = "file"))]
+                LogType::Both(ref mut console, ref mut file) => {
+                    let to_file = file.set_batching(on);
+                    to_file.and(console.set_batching(on))
+                }
+                #[cfg(feature = "file")]
+                LogType::File(ref mut file) => file.set_batching(on),
+                #[cfg(feature = "console")]
+                LogType::Console(ref mut console) => console.set_batching(on),
+                LogType::Null(_) | LogType::Blackhole => Ok(()),
+            };
+            if let Err(e) = written {
+                self.problems
+                    .report(format!("writing out a batch failed, it was dropped: {e}"));
+            }
+            #[cfg(feature = "file")]
+            self.run_rotate_hooks();
+            self.report_problems();
+            was_on
+        }
+
+        /// Log a `starting` line at Info naming the binary, from
+        /// set_build_info. Call it once at startup so every log file says
+        /// what wrote it.
+        pub fn log_banner(&mut self) {
+            let starting = self.opts.localization.get("starting");
+            let msg = match &self.opts.build_info {
+                Some(info) => format!("{starting} {}", info.describe()),
+                None => starting.to_string(),
+            };
+            self.info(msg);
+        }
+
+        /// Write out anything the sinks are still holding on to.
+        pub fn flush(&mut self) -> io::Result<()> {
+            match self.log_type {
+                #[cfg(all(feature = "console", feature = "file"))]
+                LogType::Both(ref mut console, ref mut file) => {
+                    console.flush()?;
+                    file.flush()?;
+                }
+                #[cfg(feature = "file")]
+                LogType::File(ref mut file) => file.flush()?,
+                #[cfg(feature = "console")]
+                LogType::Console(ref mut console) => console.flush()?,
+                LogType::Null(_) | LogType::Blackhole => {}
+            }
+            for sink in &self.sinks {
+                sink.lock().flush()?;
+            }
+            Ok(())
+        }
+
+        /// Reload the options whenever the TOML file at `path` changes.
+        ///
+        /// Levels, formats and destinations all follow the file. A file
+        /// that fails to parse is reported on stderr and the old options
+        /// stay. The new options apply from the next line logged.
+        ///
+        /// Only available with the `watch` feature.
+        #[cfg(feature = "watch")]
+        pub fn watch_config<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+            let problems = self.problems.clone();
+            self.config_watch = Some(watch::ConfigWatch::new(path.as_ref(), problems)?);
+            Ok(())
+        }
+
+        /// Flush the console and every log file, the extra file and tenant
+        /// sinks' too, and sync the files to disk when SIGINT or SIGTERM
+        /// arrives, then let the signal do what it would have. A CLI
+        /// stopped with Ctrl-C keeps its last lines.
+        ///
+        /// Files opened later, after a rotation say, are covered as well.
+        /// Lines an open Batch is holding back are lost all the same.
+        ///
+        /// Only available with the `signals` feature, on unix.
+        #[cfg(all(unix, feature = "signals"))]
+        pub fn flush_on_signals(&mut self) -> io::Result<()> {
+            signals::register()
+        }
+
+        /// How long writes to each kind of sink have taken and how big
+        /// records are, to find out whether the disk or a remote sink is
+        /// holding things up. Stats::to_prometheus gives them in a form
+        /// Prometheus can scrape.
+        ///
+        /// ```no_run
+        /// use little_logger::log::Logger;
+        ///
+        /// let mut logger = Logger::default();
+        /// logger.info("ready");
+        /// let stats = logger.stats();
+        /// if let Some(writes) = stats.write_seconds("file") {
+        ///     println!("{} writes took {}s", writes.count(), writes.sum());
+        /// }
+        /// ```
+        pub fn stats(&self) -> Stats {
+            let mut stats = self.stats.clone();
+            for sink in &self.sinks {
+                let sink = sink.lock();
+                stats.merge_writes(sink.name(), sink.write_seconds());
+                stats.count_dropped(sink.name(), sink.dropped_total());
+            }
+            stats
+        }
+
+        /// Start a span that logs when it is entered and when it ends.
+        ///
+        /// ```no_run
+        /// use little_logger::log::Logger;
+        ///
+        /// let mut logger = Logger::default();
+        /// let mut span = logger.span("handle_request").field("path", "/").enter();
+        /// span.info("looking up user");
+        /// {
+        ///     let mut query = span.span("query").enter();
+        ///     query.debug("SELECT 1");
+        /// }
+        /// // The exit line with elapsed_ms is logged when `span` is dropped.
+        /// ```
+        pub fn span(&mut self, name: &str) -> SpanBuilder<'_> {
+            SpanBuilder::new(self, name)
+        }
+
+        /// Build up a record at `level` before logging it, for the odd
+        /// one that needs more than the plain methods give.
+        ///
+        /// ```no_run
+        /// use little_logger::log::{Level, Logger};
+        ///
+        /// let mut logger = Logger::default();
+        /// logger
+        ///     .record(Level::Trace)
+        ///     .target("perf")
+        ///     .field("stage", "decode")
+        ///     .ts_format("%H:%M:%S%.9f")
+        ///     .log("frame done");
+        /// ```
+        pub fn record(&mut self, level: Level) -> RecordBuilder<'_> {
+            RecordBuilder::new(self, level)
+        }
+
+        /// Hold back what is logged through the returned Transaction and
+        /// only write it on commit, say to log every step of an operation
+        /// only when it ends up failing.
+        ///
+        /// ```no_run
+        /// use little_logger::log::Logger;
+        ///
+        /// let mut logger = Logger::default();
+        /// let mut txn = logger.transaction();
+        /// txn.debug("fetched 3 rows");
+        /// let failed = true;
+        /// if failed {
+        ///     txn.commit();
+        /// }
+        /// ```
+        pub fn transaction(&mut self) -> Transaction<'_> {
+            Transaction::new(self)
+        }
+
+        /// Time an operation with several stages.
+        ///
+        /// ```no_run
+        /// use little_logger::log::Logger;
+        ///
+        /// let mut logger = Logger::default();
+        /// let mut sw = logger.stopwatch("import");
+        /// // read the file
+        /// sw.lap("parse");
+        /// // write the rows
+        /// sw.lap("insert");
+        /// sw.finish();
+        /// ```
+        ///
+        /// Logs `import parse` and `import insert` with `lap_ms` and
+        /// `total_ms`, then `import finished` with `total_ms`.
+        pub fn stopwatch(&mut self, name: &str) -> Stopwatch<'_> {
+            Stopwatch::new(self, name)
+        }
+
+        /// Change the options of a running logger.
+        ///
+        /// `change` gets a copy of the current options and returns the new
+        /// ones. The sinks are rebuilt in one go before the next line, and
+        /// thresholds and watches registered on the logger are kept. Share
+        /// the logger as a `Mutex<Logger>` to reconfigure it from another
+        /// thread, e.g. an admin endpoint.
+        ///
+        /// ```no_run
+        /// use little_logger::log::{Level, Logger};
+        ///
+        /// let mut logger = Logger::default();
+        /// logger.reconfigure(|opts| opts.set_level(Level::Debug));
+        /// ```
+        pub fn reconfigure<F>(&mut self, change: F)
+        where
+            F: FnOnce(LoggerOpts) -> LoggerOpts,
+        {
+            let opts = change(self.opts.clone());
+            self.apply_opts(opts);
+        }
+
+        /// Rebuild the sinks from `opts`, keeping what was registered on
+        /// the Logger itself.
+        fn apply_opts(&mut self, mut opts: LoggerOpts) {
+            if opts.log_file.is_none() {
+                opts.log_file = self.opts.log_file.clone();
+            }
+            // A config file can't hold a closure.
+            if opts.label_fn.is_none() {
+                opts.label_fn = self.opts.label_fn.clone();
+            }
+            if opts.internal_fn.is_none() {
+                opts.internal_fn = self.opts.internal_fn.clone();
+            }
+            // Write out what an open Batch is holding before the sinks go.
+            let batching = self.set_batching(false);
+            let mut fresh = Logger::with_problems(opts, self.problems.clone());
+            fresh.set_batching(batching);
+            fresh.thresholds = std::mem::take(&mut self.thresholds);
+            fresh.mutes = std::mem::take(&mut self.mutes);
+            fresh.spans = std::mem::take(&mut self.spans);
+            fresh.next_span_id = self.next_span_id;
+            fresh.started = self.started;
+            fresh.stats = std::mem::take(&mut self.stats);
+            fresh.progress = std::mem::take(&mut self.progress);
+            fresh.held = self.held.take();
+            fresh.log_label = self.strings.intern(&fresh.log_label);
+            fresh.strings = self.strings.clone();
+            #[cfg(feature = "file")]
+            {
+                fresh.rotate_hooks = std::mem::take(&mut self.rotate_hooks);
+            }
+            #[cfg(feature = "watch")]
+            {
+                fresh.config_watch = self.config_watch.take();
+            }
+            *self = fresh;
+        }
+
+        /// The options this logger was built from.
+        ///
+        /// With the `serde` feature enabled these can be serialized, which
+        /// is handy for dumping the logging setup when nothing shows up.
+        pub fn effective_config(&self) -> &LoggerOpts {
+            &self.opts
+        }
+
+        /// Stamp date_time with the logger's clock and return the wall
+        /// clock and elapsed time it came from, for sinks with their own.
+        /// The wall clock is in `ts_format` when the line has one.
+        fn update_time(&mut self, ts_format: Option<&str>) -> (String, Duration) {
+            let dt_format = ts_format.unwrap_or(&self.dt_format);
+            let (wall, elapsed) = if self.opts.deterministic {
+                // Zoned, so formats with an offset like `%:z` have one.
+                let epoch = chrono::DateTime::<chrono::Utc>::UNIX_EPOCH;
+                (epoch.format(dt_format).to_string(), Duration::ZERO)
+            } else if let Some((now, elapsed)) = self.replay_time {
+                (now.format(dt_format).to_string(), elapsed)
+            } else if self.opts.time_cache {
+                let elapsed = self.started.elapsed();
+                (self.time_cache.now(dt_format).to_string(), elapsed)
+            } else {
+                let elapsed = self.started.elapsed();
+                (Local::now().format(dt_format).to_string(), elapsed)
+            };
+            self.date_time = self.opts.clock.stamp(&wall, elapsed);
+            (wall, elapsed)
+        }
+
+        fn write_log_line(&mut self, line: Line) {
+            if let Some(held) = &mut self.held {
+                held.push(transaction::HeldLine::new(
+                    &line,
+                    self.started.elapsed(),
+                    &mut self.strings.lock(),
+                ));
+                return;
+            }
+            let Line {
+                level,
+                target,
+                label,
+                msg,
+                err,
+                err_kind,
+                err_code,
+                template,
+                kv,
+                typed,
+                span_edge,
+                function,
+                raw,
+                change,
+                location,
+                ts_format,
+            } = line;
+            #[cfg(feature = "watch")]
+            if let Some(opts) = self.config_watch.as_ref().and_then(|w| w.take()) {
+                self.apply_opts(opts);
+            }
+            let computed = match (label, &self.opts.label_fn) {
+                (None, Some(label_fn)) => Some((label_fn.0)(&RecordMeta {
+                    level,
+                    label: &self.log_label,
+                    target,
+                    message: msg,
+                })),
+                _ => None,
+            };
+            let label = label.or(computed.as_deref());
+            let filter = &self.opts.filter;
+            if !filter.label_enabled(label.unwrap_or(&self.log_label)) {
+                return;
+            }
+            let now = Instant::now();
+            let meta = RecordMeta {
+                level,
+                label: label.unwrap_or(&self.log_label),
+                target,
+                message: msg,
+            };
+            if mute::is_muted(&mut self.mutes, &meta, now, &self.problems) {
+                self.report_problems();
+                return;
+            }
+            if !filter.enabled(level, target) {
+                // Only what the level leaves out is context, not what a
+                // target was turned down for.
+                let context_line = level >= Level::Debug && filter.below_default(level, target);
+                match &mut self.error_context {
+                    Some(context) if context_line && !context.is_open(now) => {
+                        context.hold(transaction::HeldLine::new(
+                            &line,
+                            self.started.elapsed(),
+                            &mut self.strings.lock(),
+                        ));
+                        return;
+                    }
+                    Some(_) if context_line => {}
+                    _ => return,
+                }
+            }
+            if level == Level::Error {
+                if let Some(context) = &mut self.error_context {
+                    for held in context.trigger(now) {
+                        held.replay(self);
+                    }
+                }
+            }
+            for threshold in &mut self.thresholds {
+                threshold.record(level, now);
+            }
+            if let LogType::Blackhole = self.log_type {
+                return;
+            }
+            let (wall, elapsed) = self.update_time(ts_format);
+            let console_time = self.opts.console_clock.map(|c| c.stamp(&wall, elapsed));
+            let file_time = self.opts.file_clock.map(|c| c.stamp(&wall, elapsed));
+            let level_name = self.level_style.name(level, &self.opts.localization);
+            let span_id = self.spans.last().map(|id| id.to_string());
+            let event_id = self.opts.event_ids.then(|| self.event_ids.next());
+            let span_depth = self.spans.len() - usize::from(span_edge && !self.spans.is_empty());
+            let mut with_fn;
+            let kv = match function.filter(|_| self.opts.function_names) {
+                Some(function) => {
+                    with_fn = kv.to_vec();
+                    with_fn.push(("fn", function));
+                    &with_fn[..]
+                }
+                None => kv,
+            };
+            let record = Record {
+                level,
+                level_name: &level_name,
+                target,
+                label: label.unwrap_or(&self.log_label),
+                date_time: &self.date_time,
+                msg,
+                err,
+                err_kind,
+                err_code,
+                template,
+                kv,
+                typed,
+                static_fields: &self.opts.static_fields,
+                span_id: span_id.as_deref(),
+                span_depth,
+                event_id: event_id.as_deref(),
+                session_id: self.opts.session_ids.then(ulid::session_id),
+                raw,
+                change,
+                source: location.map(|l| (l.file(), l.line(), function.unwrap_or_default())),
+            };
+            let detail_id;
+            let mut with_detail;
+            let record = match (err, &mut self.error_detail) {
+                (Some(full), Some(detail)) if detail::ErrorDetail::is_long(full) => {
+                    // Headed the way the log file writes the record, so
+                    // the entry reads like the line it belongs to.
+                    let heading = Record {
+                        date_time: file_time.as_deref().unwrap_or(record.date_time),
+                        err: None,
+                        ..record
+                    };
+                    let heading = self
+                        .opts
+                        .sink_format(self.opts.file_format)
+                        .render(&heading, &self.layout);
+                    match detail.write(heading.trim_end(), full) {
+                        Ok(id) => {
+                            detail_id = id;
+                            with_detail = kv.to_vec();
+                            with_detail.push(("detail", &detail_id));
+                            Record {
+                                err: Some(detail::ErrorDetail::summary(full)),
+                                kv: &with_detail,
+                                ..record
+                            }
+                        }
+                        Err(e) => {
+                            self.problems.report(format!(
+                                "writing an error detail failed, it stays in the log: {e}"
+                            ));
+                            record
+                        }
+                    }
+                }
+                _ => record,
+            };
+            self.msg = LogFormat::Text.render(&record, &self.layout);
+            let console_record = Record {
+                date_time: console_time.as_deref().unwrap_or(record.date_time),
+                ..record
+            };
+            let file_record = Record {
+                date_time: file_time.as_deref().unwrap_or(record.date_time),
+                ..record
+            };
+            let stats = &mut self.stats;
+            let problems = &self.problems;
+            match self.log_type {
+                #[cfg(all(feature = "console", feature = "file"))]
+                LogType::Both(ref mut console, ref mut file) => {
+                    let line = file.encode(&file_record, &self.layout);
+                    stats.observe_record(line.len());
+                    self.file_backoff
+                        .write("file", stats, problems, || file.write_all(&line));
+                    let line = console.encode(&console_record, &self.layout);
+                    self.console_backoff.write("console", stats, problems, || {
+                        console.write_all(level, &line)
+                    });
+                }
+                #[cfg(feature = "file")]
+                LogType::File(ref mut file) => {
+                    let line = file.encode(&file_record, &self.layout);
+                    stats.observe_record(line.len());
+                    self.file_backoff
+                        .write("file", stats, problems, || file.write_all(&line));
+                }
+                #[cfg(feature = "console")]
+                LogType::Console(ref mut console) => {
+                    let line = console.encode(&console_record, &self.layout);
+                    stats.observe_record(line.len());
+                    self.console_backoff.write("console", stats, problems, || {
+                        console.write_all(level, &line)
+                    });
+                }
+                LogType::Null(ref mut null) => {
+                    let start = Instant::now();
+                    let written = null.write_record(&file_record, &self.layout);
+                    stats.observe_write("null", start, &written);
+                }
+                LogType::Blackhole => unreachable!("returned before formatting"),
+            }
+            for sink in &self.sinks {
+                sink.lock().write_record(&record, &self.layout);
+            }
+            #[cfg(feature = "file")]
+            self.run_rotate_hooks();
+            if self.opts.sync_on.is_some_and(|sync_on| level >= sync_on) {
+                if let Err(e) = self.sync() {
+                    self.problems.report(format!("syncing the log failed: {e}"));
+                }
+            }
+            self.report_problems();
+        }
+
+        /// Pass on what went wrong while writing, as set_internal_log
+        /// and on_internal_problem say. Problems writing the reports
+        /// themselves go to stderr, rather than round and round.
+        fn report_problems(&mut self) {
+            if self.reporting {
+                return;
+            }
+            let problems = self.problems.take();
+            if problems.is_empty() {
+                return;
+            }
+            self.reporting = true;
+            for problem in &problems {
+                match (&self.opts.internal_fn, self.opts.internal_log) {
+                    (Some(callback), _) => (callback.0)(problem),
+                    (None, InternalLog::Stream) => self.write_log_line(Line {
+                        label: Some(InternalLog::LABEL),
+                        ..Line::new(Level::Warn, problem)
+                    }),
+                    (None, InternalLog::Stderr) => eprintln!("little_logger: {problem}"),
+                }
+            }
+            for problem in self.problems.take() {
+                eprintln!("little_logger: {problem}");
+            }
+            self.reporting = false;
+        }
+
+        #[cfg(feature = "file")]
+        fn run_rotate_hooks(&mut self) {
+            let Some(file) = self.log_type.file_mut() else {
+                return;
+            };
+            if let Some(rotated) = file.rotated.take() {
+                for hook in &mut self.rotate_hooks {
+                    (hook.0)(&file.path, &rotated);
+                }
+            }
+        }
+
+        /// Flush everything and sync the log file to disk.
+        fn sync(&mut self) -> io::Result<()> {
+            self.flush()?;
+            match self.log_type {
+                #[cfg(feature = "file")]
+                LogType::File(ref mut file) => file.sync(),
+                #[cfg(all(feature = "console", feature = "file"))]
+                LogType::Both(_, ref mut file) => file.sync(),
+                #[allow(unreachable_patterns)]
+                _ => Ok(()),
+            }
+        }
+
+        pub fn log_message<S: Into<String>>(&mut self, msg: S) {
+            /// Use this function to log messages.
+            ///
+            /// How you define log::LoggerOpts defines whether you log to the console,
+            /// a file, or both. If you didn't this will default to a file named llog.txt.
+            ///
+            /// # Examle:
+            /// ```no_run
+            ///
+            /// let message: &str = "Message to be logged";
+            /// let mut logger = Logger::new();
+            ///
+            /// logger.log_message(message, None);
+            ///
+            /// ```
+            self.write_log_line(Line::new(Level::Info, &msg.into()));
+        }
+
+        /// Log a message at the given level.
+        pub fn log<S: Into<String>>(&mut self, level: Level, msg: S) {
+            self.write_log_line(Line::new(level, &msg.into()));
+        }
+        /// Write `line` as it is plus a newline, for passing on lines that
+        /// are already formatted, like another program's output.
+        ///
+        /// Text formats get nothing else. Structured formats still write a
+        /// whole record at Info, so they stay parseable.
+        pub fn log_raw<S: Into<String>>(&mut self, line: S) {
+            self.write_log_line(Line {
+                raw: true,
+                ..Line::new(Level::Info, &line.into())
+            });
+        }
+        /// Log bytes from outside, like a network frame or a file name, at
+        /// Level::Info. Whatever isn't UTF-8 is written as set_invalid_utf8
+        /// says instead of the line being lost.
+        pub fn log_lossy(&mut self, bytes: &[u8]) {
+            let msg = self.opts.invalid_utf8.decode(bytes).into_owned();
+            self.write_log_line(Line::new(Level::Info, &msg));
+        }
+        /// Log that the setting `key` went from `old` to `new`, e.g. on a
+        /// config reload. Text shows `config.max_conns: 10 -> 20`, with the
+        /// values colored on a Colored console, structured formats get
+        /// `old` and `new` fields.
+        pub fn log_change<O, N>(&mut self, key: &str, old: O, new: N)
+        where
+            O: fmt::Display,
+            N: fmt::Display,
+        {
+            let (old, new) = (old.to_string(), new.to_string());
+            self.write_log_line(Line {
+                change: Some((&old, &new)),
+                ..Line::new(Level::Info, key)
+            });
+        }
+        /// Log a state machine moving `entity` from one state to another.
+        ///
+        /// Every transition is the same record, `state transition` with
+        /// `entity`, `from` and `to` fields, the states in their Debug
+        /// form, so they can all be found with one query.
+        ///
+        /// ```no_run
+        /// use little_logger::log::Logger;
+        ///
+        /// #[derive(Debug)]
+        /// enum Order {
+        ///     Placed,
+        ///     Paid,
+        /// }
+        ///
+        /// let mut logger = Logger::default();
+        /// logger.log_transition("order:1234", Order::Placed, Order::Paid);
+        /// ```
+        pub fn log_transition<T: fmt::Debug>(&mut self, entity: &str, from: T, to: T) {
+            let (from, to) = (format!("{:?}", from), format!("{:?}", to));
+            self.log_kv(
+                Level::Info,
+                "state transition",
+                &[("entity", entity), ("from", &from), ("to", &to)],
+            );
+        }
+        /// Log a message at the given level on behalf of a target.
+        pub fn log_target<S: Into<String>>(&mut self, target: &str, level: Level, msg: S) {
+            self.write_log_line(Line {
+                target: Some(target),
+                ..Line::new(level, &msg.into())
+            });
+        }
+        /// Log on behalf of a target from a function, which becomes the `fn`
+        /// field when set_function_names is on.
+        ///
+        /// The macros call this with the caller's `module_path!()` and
+        /// `function_name!()`. The file and line it is called from go to
+        /// formats with a source location, like LogFormat::Gcp.
+        #[track_caller]
+        pub fn log_from<S: Into<String>>(
+            &mut self,
+            target: &str,
+            function: &str,
+            level: Level,
+            msg: S,
+        ) {
+            self.write_log_line(Line {
+                target: Some(target),
+                function: Some(function),
+                location: Some(Location::caller()),
+                ..Line::new(level, &msg.into())
+            });
+        }
+        /// Log a message at the given level with key-value fields.
+        ///
+        /// Text formats append the fields as ` key=value`, structured
+        /// formats write them as fields of their own.
+        ///
+        /// ```no_run
+        /// use little_logger::log::{Level, Logger};
+        ///
+        /// let mut logger = Logger::default();
+        /// logger.log_kv(Level::Info, "login", &[("user", "bob"), ("via", "ssh key")]);
+        /// ```
+        pub fn log_kv<S: Into<String>>(&mut self, level: Level, msg: S, kv: &[(&str, &str)]) {
+            self.write_log_line(Line {
+                kv,
+                ..Line::new(level, &msg.into())
+            });
+        }
+        /// Log a message filled in from a template, keeping the template
+        /// itself so structured formats can be searched by it.
+        ///
+        /// ```no_run
+        /// use little_logger::log::{Level, Logger};
+        ///
+        /// let mut logger = Logger::default();
+        /// logger.info_t("user {user_id} bought {item}", &[("user_id", "42"), ("item", "sku-7")]);
+        /// ```
+        ///
+        /// Text formats write `user 42 bought sku-7`, JSON adds
+        /// `"template":"user {user_id} bought {item}"` and the two fields.
+        pub fn log_t(&mut self, level: Level, template: &str, kv: &[(&str, &str)]) {
+            let msg = format::fill_template(template, kv);
+            self.write_log_line(Line {
+                template: Some(template),
+                kv,
+                ..Line::new(level, &msg)
+            });
+        }
+        /// Log a template at Level::Trace, see log_t.
+        pub fn trace_t(&mut self, template: &str, kv: &[(&str, &str)]) {
+            self.log_t(Level::Trace, template, kv);
+        }
+        /// Log a template at Level::Debug, see log_t.
+        pub fn debug_t(&mut self, template: &str, kv: &[(&str, &str)]) {
+            self.log_t(Level::Debug, template, kv);
+        }
+        /// Log a template at Level::Info, see log_t.
+        pub fn info_t(&mut self, template: &str, kv: &[(&str, &str)]) {
+            self.log_t(Level::Info, template, kv);
+        }
+        /// Log a template at Level::Warn, see log_t.
+        pub fn warn_t(&mut self, template: &str, kv: &[(&str, &str)]) {
+            self.log_t(Level::Warn, template, kv);
+        }
+        /// Log a template at Level::Error, see log_t.
+        pub fn error_t(&mut self, template: &str, kv: &[(&str, &str)]) {
+            self.log_t(Level::Error, template, kv);
+        }
+        /// Report that `current` of `total` items are `name`, at most once
+        /// per set_progress_interval.
+        ///
+        /// `logger.progress("indexed", 450, 1000)` logs
+        /// `indexed 450/1000 (45.0%)` with the average `rate` per second.
+        /// The first call and the one reaching `total` are always logged.
+        pub fn progress(&mut self, name: &str, current: u64, total: u64) {
+            let now = match self.opts.deterministic {
+                true => self.started,
+                false => Instant::now(),
+            };
+            let interval = self.opts.progress_interval;
+            if let Some((msg, rate)) =
+                progress::update(&mut self.progress, name, current, total, interval, now)
+            {
+                self.log_kv(Level::Info, msg, &[("rate", &rate)]);
+            }
+        }
+        /// Log a message at Level::Trace.
+        pub fn trace<S: Into<String>>(&mut self, msg: S) {
+            self.log(Level::Trace, msg);
+        }
+        /// Log a message at Level::Debug.
+        pub fn debug<S: Into<String>>(&mut self, msg: S) {
+            self.log(Level::Debug, msg);
+        }
+        /// Log a message at Level::Info.
+        pub fn info<S: Into<String>>(&mut self, msg: S) {
+            self.log(Level::Info, msg);
+        }
+        /// Log a message at Level::Warn.
+        pub fn warn<S: Into<String>>(&mut self, msg: S) {
+            self.log(Level::Warn, msg);
+        }
+        /// Log a message at Level::Error.
+        pub fn error<S: Into<String>>(&mut self, msg: S) {
+            self.log(Level::Error, msg);
+        }
+
+        /// Log a value with `{:#?}` at Level::Debug.
+        ///
+        /// The label goes on the log line and the value follows on
+        /// indented continuation lines.
+        pub fn debug_value<T: fmt::Debug + ?Sized>(&mut self, label: &str, value: &T) {
+            let mut msg = label.to_string();
+            for line in format!("{:#?}", value).lines() {
+                msg.push_str("\n    ");
+                msg.push_str(line);
+            }
+            self.log(Level::Debug, msg);
+        }
+
+        pub fn log_msg_and_error<S: Into<String>>(&mut self, msg: S, err: S) {
+            /// Use this function to log messages and include an error.
+            ///
+            /// Msg and err can both be passed as String or &str.
+            ///
+            /// How you define log::LoggerOpts defines whether you log to the console,
+            /// a file, or both. If you didn't this will default to a file named llog.txt.
+            ///
+            /// # Examle:
+            /// ```no_run
+            ///
+            /// let message: &str = "Message to be logged";
+            /// let mut logger = Logger::new();
+            /// let err = match some_result = {
+            ///      Ok(val) => val,
+            ///      Err(e) => e,
+            /// }
+            ///
+            /// logger.log_message(message, err);
+            ///
+            /// ```
+            self.write_log_line(Line {
+                err: Some(&err.into()),
+                ..Line::new(Level::Error, &msg.into())
+            });
+        }
+
+        /// Log a message at Level::Error together with an error value.
+        ///
+        /// Structured formats write the message, the error and the error's
+        /// type as separate `message`, `error` and `error.kind` fields.
+        pub fn log_error<S, E>(&mut self, msg: S, err: &E)
+        where
+            S: Into<String>,
+            E: std::error::Error + ?Sized,
+        {
+            self.write_log_line(Line {
+                err: Some(&err.to_string()),
+                err_kind: Some(std::any::type_name::<E>()),
+                ..Line::new(Level::Error, &msg.into())
+            });
+        }
+
+        /// Log a message at Level::Error together with an error value and
+        /// a stable code for it, written as its own `error.code` field and
+        /// as ` code=E1042` in text, so failures can be counted by code
+        /// however their messages change. Keep the codes in an ErrorCodes
+        /// to be sure none is used for two things.
+        ///
+        /// ```no_run
+        /// use little_logger::log::Logger;
+        ///
+        /// let mut logger = Logger::default();
+        /// if let Err(err) = std::fs::read("pool.conf") {
+        ///     logger.error_code("E1042", "reading pool config", &err);
+        /// }
+        /// ```
+        pub fn error_code<S, E>(&mut self, code: &str, msg: S, err: &E)
+        where
+            S: Into<String>,
+            E: std::error::Error + ?Sized,
+        {
+            self.write_log_line(Line {
+                err: Some(&err.to_string()),
+                err_kind: Some(std::any::type_name::<E>()),
+                err_code: Some(code),
+                ..Line::new(Level::Error, &msg.into())
+            });
+        }
+
+        /// Log `context` and the error when `result` is an Err, then hand
+        /// the result back untouched.
+        ///
+        /// ```no_run
+        /// use little_logger::log::{Level, Logger};
+        ///
+        /// fn save(logger: &mut Logger) -> std::io::Result<()> {
+        ///     logger.log_err(std::fs::write("user.txt", "me"), Level::Error, "saving user")?;
+        ///     Ok(())
+        /// }
+        /// ```
+        pub fn log_err<T, E: fmt::Display>(
+            &mut self,
+            result: Result<T, E>,
+            level: Level,
+            context: &str,
+        ) -> Result<T, E> {
+            if let Err(ref err) = result {
+                self.write_log_line(Line {
+                    err: Some(&err.to_string()),
+                    err_kind: Some(std::any::type_name::<E>()),
+                    ..Line::new(level, context)
+                });
+            }
+            result
+        }
+    }
+}
+
+#[cfg(feature = "clap")]
+pub mod cli;
+
+#[cfg(not(any(feature = "console", feature = "file")))]
+compile_error!("little_logger needs at least one of the `console` or `file` features.");
+
+/// Log through a Logger with the calling module as the target, or the
+/// one given with `target:` as the `log` crate's macros take it.
+///
+/// ```no_run
+/// use little_logger::llog;
+/// use little_logger::log::{Level, Logger, LoggerOpts};
+///
+/// let mut logger = Logger::new(LoggerOpts::new());
+/// llog!(logger, Level::Warn, "{} retries left", 3);
+/// llog!(logger, target: "my_app::cache", Level::Info, "{} entries", 512);
+/// ```
+#[macro_export]
+macro_rules! llog {
+    ($logger:expr, target: $target:expr, $level:expr, $($arg:tt)+) => {
+        $logger.log_from($target, $crate::function_name!(), $level, format!($($arg)+))
+    };
+    ($logger:expr, $level:expr, $($arg:tt)+) => {
+        $logger.log_from(module_path!(), $crate::function_name!(), $level, format!($($arg)+))
+    };
+}
+
+/// Log an unrecoverable error to stderr and the log file, then abort.
+///
+/// For invariants whose breaking leaves nothing to trust: no Logger is
+/// needed, no lock is taken and the line is written before this returns
+/// (it never does). The log file is the one the last Logger opened.
+///
+/// ```no_run
+/// use little_logger::llog_fatal;
+///
+/// let (used, cap) = (9, 8);
+/// if used > cap {
+///     llog_fatal!("arena overrun: {used} of {cap} slots");
+/// }
+/// ```
+#[macro_export]
+macro_rules! llog_fatal {
+    ($($arg:tt)+) => {
+        $crate::log::fatal::fatal(module_path!(), file!(), line!(), format_args!($($arg)+))
+    };
+}
+
+/// A RecordBuilder with the calling module as its target, which
+/// `.target(..)` still overrides.
+///
+/// ```no_run
+/// use little_logger::record;
+/// use little_logger::log::{Level, Logger, LoggerOpts};
+///
+/// let mut logger = Logger::new(LoggerOpts::new());
+/// record!(logger, Level::Info).field("hits", 12).log("cache warmed");
+/// ```
+#[macro_export]
+macro_rules! record {
+    ($logger:expr, $level:expr) => {
+        $logger.record($level).target(module_path!())
+    };
 }
 
-#[cfg(test)]
-mod tests {
+/// The BuildInfo of the crate calling it: its package version plus the
+/// commit and build time if its build script calls build_info::emit.
+#[macro_export]
+macro_rules! build_info {
+    () => {
+        $crate::log::BuildInfo {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_sha: option_env!("LLOG_GIT_SHA").unwrap_or_default().to_string(),
+            build_ts: option_env!("LLOG_BUILD_TS").unwrap_or_default().to_string(),
+        }
+    };
+}
+
+/// The path of the enclosing function, `my_app::db::query`.
+///
+/// Closures are skipped over, so inside one this still names the function
+/// it was written in.
+#[macro_export]
+macro_rules! function_name {
+    () => {{
+        fn f() {}
+        fn type_name_of<T>(_: T) -> &'static str {
+            ::std::any::type_name::<T>()
+        }
+        let mut name = type_name_of(f);
+        name = name.strip_suffix("::f").unwrap_or(name);
+        while let Some(outer) = name.strip_suffix("::{{closure}}") {
+            name = outer;
+        }
+        name
+    }};
+}
+
+/// Log at Level::Trace with the calling module as the target, or the one
+/// given with `target:`, like `trace!(logger, target: "my_app::cache", ..)`.
+#[macro_export]
+macro_rules! trace {
+    ($logger:expr, target: $target:expr, $($arg:tt)+) => {
+        $crate::llog!($logger, target: $target, $crate::log::Level::Trace, $($arg)+)
+    };
+    ($logger:expr, $($arg:tt)+) => {
+        $crate::llog!($logger, $crate::log::Level::Trace, $($arg)+)
+    };
+}
+
+/// Log at Level::Debug with the calling module as the target.
+#[macro_export]
+macro_rules! debug {
+    ($logger:expr, target: $target:expr, $($arg:tt)+) => {
+        $crate::llog!($logger, target: $target, $crate::log::Level::Debug, $($arg)+)
+    };
+    ($logger:expr, $($arg:tt)+) => {
+        $crate::llog!($logger, $crate::log::Level::Debug, $($arg)+)
+    };
+}
+
+/// Log at Level::Info with the calling module as the target.
+#[macro_export]
+macro_rules! info {
+    ($logger:expr, target: $target:expr, $($arg:tt)+) => {
+        $crate::llog!($logger, target: $target, $crate::log::Level::Info, $($arg)+)
+    };
+    ($logger:expr, $($arg:tt)+) => {
+        $crate::llog!($logger, $crate::log::Level::Info, $($arg)+)
+    };
+}
+
+/// Log at Level::Warn with the calling module as the target.
+#[macro_export]
+macro_rules! warn {
+    ($logger:expr, target: $target:expr, $($arg:tt)+) => {
+        $crate::llog!($logger, target: $target, $crate::log::Level::Warn, $($arg)+)
+    };
+    ($logger:expr, $($arg:tt)+) => {
+        $crate::llog!($logger, $crate::log::Level::Warn, $($arg)+)
+    };
+}
+
+/// Log at Level::Error with the calling module as the target.
+#[macro_export]
+macro_rules! error {
+    ($logger:expr, target: $target:expr, $($arg:tt)+) => {
+        $crate::llog!($logger, target: $target, $crate::log::Level::Error, $($arg)+)
+    };
+    ($logger:expr, $($arg:tt)+) => {
+        $crate::llog!($logger, $crate::log::Level::Error, $($arg)+)
+    };
+}
+
+/// Assert that a testing::Capture holds a record at a level whose message
+/// contains some text. Prints the snapshot when it doesn't.
+///
+/// ```no_run
+/// use little_logger::assert_log;
+/// use little_logger::log::testing::Capture;
+/// use little_logger::log::Level;
+///
+/// let capture = Capture::new();
+/// assert_log!(capture, Level::Warn, contains "retrying");
+/// ```
+#[macro_export]
+macro_rules! assert_log {
+    ($capture:expr, $level:expr, contains $needle:expr) => {
+        assert!(
+            $capture.contains($level, $needle),
+            "no {} record containing {:?}, captured:\n{}",
+            $level,
+            $needle,
+            $capture.snapshot()
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::log::{Clock, Level, LogFormat, Logger, LoggerOpts};
+    use std::fs;
+
+    #[test]
+    fn test_default_opts() {}
+
+    #[test]
+    fn test_logger_can_live_in_a_static() {
+        fn assert_send<T: Send + 'static>() {}
+        assert_send::<Logger>();
+    }
+
+    #[test]
+    fn test_file_created_on_first_write() {
+        let path = std::env::temp_dir().join("llog_lazy_test.txt");
+        let _ = fs::remove_file(&path);
+
+        let mut logger = Logger::new(LoggerOpts::new().set_logfile_name(path.to_str().unwrap()));
+        assert!(!path.exists());
+
+        logger.log_message("hello");
+        assert!(fs::read_to_string(&path).unwrap().ends_with("-> hello\n"));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_cloned_opts_are_independent() {
+        let base = LoggerOpts::default().set_log_label("BASE");
+        let first = base.clone().set_logfile_name("first.txt");
+        let second = base.set_logfile_name("second.txt");
+
+        let first = format!("{:?}", first);
+        let second = format!("{:?}", second);
+        assert!(first.contains("first.txt") && !first.contains("second.txt"));
+        assert!(second.contains("second.txt") && second.contains("BASE"));
+    }
+
+    #[test]
+    fn test_debug_value_indents_payload() {
+        let path = std::env::temp_dir().join("llog_debug_value_test.txt");
+        let _ = fs::remove_file(&path);
+
+        let mut logger = Logger::new(LoggerOpts::new().set_logfile_name(path.to_str().unwrap()));
+        logger.debug_value("point", &(1, 2));
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .ends_with("-> point\n    (\n        1,\n        2,\n    )\n"));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_log_error_splits_structured_fields() {
+        let path = std::env::temp_dir().join("llog_log_error_test.txt");
+        let _ = fs::remove_file(&path);
+
+        let opts = LoggerOpts::new()
+            .set_logfile_name(path.to_str().unwrap())
+            .set_file_format(LogFormat::Json);
+        let mut logger = Logger::new(opts);
+        let err = "x".parse::<u8>().unwrap_err();
+        logger.log_error("bad port", &err);
+
+        let line = fs::read_to_string(&path).unwrap();
+        assert!(
+            line.contains("\"message\":\"bad port\",\"error\":\"invalid digit found in string\"")
+        );
+        assert!(line.contains("\"error.kind\":\"core::num::error::ParseIntError\""));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_log_err_passes_result_through() {
+        let path = std::env::temp_dir().join("llog_log_err_test.txt");
+        let _ = fs::remove_file(&path);
+
+        let mut logger = Logger::new(LoggerOpts::new().set_logfile_name(path.to_str().unwrap()));
+        assert_eq!(
+            logger.log_err(Ok::<u8, String>(1), Level::Warn, "ok"),
+            Ok(1)
+        );
+        assert!(!path.exists());
+
+        let result = logger.log_err("x".parse::<u8>(), Level::Warn, "parsing");
+        assert!(result.is_err());
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .ends_with("[WARN] -> parsing\ninvalid digit found in string\n"));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_with_label_overrides_one_line() {
+        let path = std::env::temp_dir().join("llog_with_label_test.txt");
+        let _ = fs::remove_file(&path);
+
+        let mut logger = Logger::new(LoggerOpts::new().set_logfile_name(path.to_str().unwrap()));
+        logger.with_label("BILLING").warn("charge failed");
+        logger.info("back to normal");
+
+        let log = fs::read_to_string(&path).unwrap();
+        let mut lines = log.lines();
+        assert!(lines.next().unwrap().starts_with("[BILLING]::"));
+        assert!(lines.next().unwrap().starts_with("[LLOG]::"));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_writes_to_preopened_file() {
+        let path = std::env::temp_dir().join("llog_preopened_test.txt");
+        let file = fs::File::create(&path).unwrap();
+
+        let mut logger = Logger::new(
+            LoggerOpts::new()
+                .set_logfile_name("never_created.txt")
+                .set_log_file(file),
+        );
+        logger.log_message("handed over");
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .ends_with("-> handed over\n"));
+        assert!(!std::path::Path::new("never_created.txt").exists());
+        let _ = fs::remove_file(&path);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_pipe_sink_feeds_child_stdin() {
+        let path = std::env::temp_dir().join("llog_pipe_test.txt");
+        let _ = fs::remove_file(&path);
+        let redirect = format!("cat >> {}", path.display());
+
+        let opts = LoggerOpts::new()
+            .set_log_type("console")
+            .add_pipe_sink(&["sh", "-c", &redirect], LogFormat::Json);
+        let mut logger = Logger::new(opts);
+        logger.log_message("through the pipe");
+        drop(logger);
+
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .contains("\"message\":\"through the pipe\""));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_sync_on_flushes_buffered_errors() {
+        let path = std::env::temp_dir().join("llog_sync_on_test.txt");
+        let _ = fs::remove_file(&path);
+
+        let opts = LoggerOpts::new()
+            .set_logfile_name(path.to_str().unwrap())
+            .set_buffer_size(4096)
+            .set_sync_on(Level::Error);
+        let mut logger = Logger::new(opts);
+        logger.info("buffered");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+        logger.error("synced");
+        assert!(fs::read_to_string(&path).unwrap().ends_with("-> synced\n"));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[cfg(feature = "watch")]
+    #[test]
+    fn test_watch_config_applies_edits() {
+        let dir = std::env::temp_dir().join("llog_watch_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let config = dir.join("log.toml");
+        let log = dir.join("llog.txt");
+        let toml = |label: &str| format!("log_file_name = {:?}\nlog_label = {:?}\n", log, label);
+        fs::write(&config, toml("OLD")).unwrap();
+
+        let mut logger = Logger::new(LoggerOpts::from_toml_file(&config).unwrap());
+        logger.watch_config(&config).unwrap();
+        logger.info("before");
+        fs::write(&config, toml("NEW")).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        logger.info("after");
+
+        let written = fs::read_to_string(&log).unwrap();
+        assert!(written.lines().next().unwrap().starts_with("[OLD]"));
+        assert!(written.lines().last().unwrap().starts_with("[NEW]"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(feature = "watch")]
+    #[test]
+    fn test_watch_config_reports_a_bad_edit() {
+        let dir = std::env::temp_dir().join("llog_watch_bad_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let config = dir.join("log.toml");
+        fs::write(&config, "log_label = \"OLD\"\n").unwrap();
+
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        let opts = LoggerOpts::from_toml_file(&config)
+            .unwrap()
+            .set_log_type("null")
+            .on_internal_problem(move |problem| sink.lock().unwrap().push(problem.to_string()));
+        let mut logger = Logger::new(opts);
+        logger.watch_config(&config).unwrap();
+        // Swapped in whole, so the watch never sees it empty.
+        let edit = dir.join("log.toml.new");
+        fs::write(&edit, "log_label = \n").unwrap();
+        fs::rename(&edit, &config).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        logger.info("after");
+
+        let seen = seen.lock().unwrap();
+        assert!(seen.iter().any(|p| p.starts_with("keeping the old config")));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_reconfigure_changes_level_and_label() {
+        let path = std::env::temp_dir().join("llog_reconfigure_test.txt");
+        let _ = fs::remove_file(&path);
+
+        let opts = LoggerOpts::new()
+            .set_logfile_name(path.to_str().unwrap())
+            .set_level(Level::Info);
+        let mut logger = Logger::new(opts);
+        logger.debug("dropped");
+        logger.reconfigure(|opts| opts.set_level(Level::Debug).set_log_label("NEW"));
+        logger.debug("kept");
+
+        let written = fs::read_to_string(&path).unwrap();
+        assert_eq!(written.lines().count(), 1);
+        assert!(written.starts_with("[NEW]") && written.ends_with("-> kept\n"));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_spans_nest_and_time() {
+        let path = std::env::temp_dir().join("llog_span_test.txt");
+        let _ = fs::remove_file(&path);
+
+        let mut logger = Logger::new(LoggerOpts::new().set_logfile_name(path.to_str().unwrap()));
+        {
+            let mut span = logger.span("request").field("path", "/").enter();
+            span.info("outer");
+            let mut child = span.span("query").enter();
+            child.info("inner");
+        }
+        logger.info("after");
+
+        let written = fs::read_to_string(&path).unwrap();
+        let msgs: Vec<&str> = written
+            .lines()
+            .map(|line| line.split(" -> ").nth(1).unwrap())
+            .collect();
+        assert_eq!(msgs[0], "enter request path=/");
+        assert_eq!(msgs[1], "  outer");
+        assert_eq!(msgs[2], "  enter query parent_span_id=1");
+        assert_eq!(msgs[3], "    inner");
+        assert!(msgs[4].starts_with("  exit query elapsed_ms="));
+        assert!(msgs[5].starts_with("exit request path=/ elapsed_ms="));
+        assert_eq!(msgs[6], "after");
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_session_id_is_stable() {
+        let path = std::env::temp_dir().join("llog_session_test.txt");
+        let _ = fs::remove_file(&path);
+
+        let opts = LoggerOpts::new()
+            .set_logfile_name(path.to_str().unwrap())
+            .set_session_ids(true);
+        Logger::new(opts.clone()).info("first run");
+        Logger::new(opts).info("second logger");
+
+        let suffix = format!(" session={}", crate::log::session_id());
+        let written = fs::read_to_string(&path).unwrap();
+        assert!(written.lines().all(|line| line.ends_with(&suffix)));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_header_lets_reader_pick_format() {
+        let path = std::env::temp_dir().join("llog_header_test.bin");
+        let _ = fs::remove_file(&path);
+
+        let opts = LoggerOpts::new()
+            .set_logfile_name(path.to_str().unwrap())
+            .set_file_format(LogFormat::Cbor)
+            .set_file_header(true);
+        Logger::new(opts).info("described");
+
+        let meta = crate::log::reader::meta_path(&path);
+        assert!(fs::read_to_string(&meta).unwrap().contains("format=cbor"));
+        let entries: Vec<_> = crate::log::reader::open(&path).unwrap().collect();
+        assert_eq!(
+            entries[0].as_ref().unwrap().get("message"),
+            Some("described")
+        );
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_file(&meta);
+    }
+
+    #[test]
+    fn test_rotates_at_size_with_manifest() {
+        let path = std::env::temp_dir().join("llog_rotate_size_test.txt");
+        let rotated = std::env::temp_dir().join("llog_rotate_size_test.txt.1");
+        let manifest = std::env::temp_dir().join("llog_rotate_size_test.txt.1.manifest");
+        for path in [&path, &rotated, &manifest] {
+            let _ = fs::remove_file(path);
+        }
+
+        let opts = LoggerOpts::new()
+            .set_logfile_name(path.to_str().unwrap())
+            .set_rotate_size(80)
+            .set_rotation_manifest(true);
+        let mut logger = Logger::new(opts);
+        for n in 0..3 {
+            logger.info(format!("line {n}"));
+        }
+        drop(logger);
+
+        assert_eq!(fs::read_to_string(&rotated).unwrap().lines().count(), 2);
+        assert!(fs::read_to_string(&path).unwrap().contains("line 2"));
+        assert!(fs::read_to_string(&manifest)
+            .unwrap()
+            .contains("records=2\n"));
+        for path in [&path, &rotated, &manifest] {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn test_rotates_at_record_count() {
+        let path = std::env::temp_dir().join("llog_rotate_records_test.txt");
+        let rotated = std::env::temp_dir().join("llog_rotate_records_test.txt.1");
+        for path in [&path, &rotated] {
+            let _ = fs::remove_file(path);
+        }
+        fs::write(&path, "[LLOG]::[INFO] -> from before\n").unwrap();
+
+        let opts = LoggerOpts::new()
+            .set_logfile_name(path.to_str().unwrap())
+            .set_max_records_per_file(3);
+        let mut logger = Logger::new(opts);
+        for n in 0..3 {
+            logger.info(format!("line {n}"));
+        }
+        drop(logger);
+
+        assert_eq!(fs::read_to_string(&rotated).unwrap().lines().count(), 3);
+        assert!(fs::read_to_string(&path).unwrap().ends_with("line 2\n"));
+        for path in [&path, &rotated] {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn test_resumed_count_takes_multi_line_records_once() {
+        let path = std::env::temp_dir().join("llog_rotate_multi_line_test.txt");
+        let rotated = std::env::temp_dir().join("llog_rotate_multi_line_test.txt.1");
+        for path in [&path, &rotated] {
+            let _ = fs::remove_file(path);
+        }
+        fs::write(
+            &path,
+            "[LLOG]::[ERROR] -> query failed\n  caused by: timeout\n  at db.rs:12\n",
+        )
+        .unwrap();
+
+        let opts = LoggerOpts::new()
+            .set_logfile_name(path.to_str().unwrap())
+            .set_max_records_per_file(3);
+        let mut logger = Logger::new(opts);
+        logger.info("line 0");
+        assert!(!rotated.exists());
+        logger.info("line 1");
+        logger.info("line 2");
+        drop(logger);
+
+        assert!(fs::read_to_string(&rotated).unwrap().ends_with("line 1\n"));
+        assert!(fs::read_to_string(&path).unwrap().ends_with("line 2\n"));
+        for path in [&path, &rotated] {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[cfg(feature = "file")]
+    #[test]
+    fn test_on_rotate_gets_both_paths() {
+        let path = std::env::temp_dir().join("llog_on_rotate_test.txt");
+        let rotated = std::env::temp_dir().join("llog_on_rotate_test.txt.1");
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_file(&rotated);
+
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let opts = LoggerOpts::new()
+            .set_logfile_name(path.to_str().unwrap())
+            .set_rotate_size(1);
+        let mut logger = Logger::new(opts);
+        let hook_seen = seen.clone();
+        logger.on_rotate(move |live, rotated| {
+            hook_seen
+                .lock()
+                .unwrap()
+                .push((live.to_path_buf(), rotated.to_path_buf()));
+        });
+        logger.info("fills the file");
+
+        assert_eq!(*seen.lock().unwrap(), vec![(path.clone(), rotated.clone())]);
+        let _ = fs::remove_file(&rotated);
+    }
+
+    #[cfg(feature = "file")]
+    #[test]
+    fn test_file_sink_gets_its_own_format() {
+        let text = std::env::temp_dir().join("llog_tee_test.txt");
+        let json = std::env::temp_dir().join("llog_tee_test.json");
+        let _ = fs::remove_file(&text);
+        let _ = fs::remove_file(&json);
+
+        let opts = LoggerOpts::new()
+            .set_logfile_name(text.to_str().unwrap())
+            .add_file_sink(json.to_str().unwrap(), LogFormat::Json);
+        Logger::new(opts).warn("both ways");
+
+        assert!(fs::read_to_string(&text)
+            .unwrap()
+            .contains("[WARN] -> both ways"));
+        assert!(fs::read_to_string(&json)
+            .unwrap()
+            .contains("\"message\":\"both ways\""));
+        let _ = fs::remove_file(&text);
+        let _ = fs::remove_file(&json);
+    }
+
+    #[test]
+    fn test_deterministic_output_repeats() {
+        let path = std::env::temp_dir().join("llog_deterministic_test.txt");
+        let _ = fs::remove_file(&path);
+
+        let run = || {
+            let opts = LoggerOpts::new()
+                .set_logfile_name(path.to_str().unwrap())
+                .set_event_ids(true)
+                .set_buffer_size(4096)
+                .deterministic();
+            let mut logger = Logger::new(opts);
+            logger.span("load").enter().info("loading");
+            drop(logger);
+            fs::read_to_string(&path).unwrap()
+        };
+        let first = run();
+        let _ = fs::remove_file(&path);
+        assert_eq!(first, run());
+        assert!(first.starts_with("[LLOG]::[1970-01-01 00:00:00]::[INFO] -> enter load\n"));
+        assert!(first.contains("elapsed_ms=0.000"));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_deterministic_time_has_an_offset() {
+        let path = std::env::temp_dir().join("llog_deterministic_offset_test.txt");
+        let _ = fs::remove_file(&path);
+
+        let opts = LoggerOpts::new()
+            .set_logfile_name(path.to_str().unwrap())
+            .set_dt_format("%Y-%m-%dT%H:%M:%S%:z")
+            .deterministic();
+        let mut logger = Logger::new(opts);
+        logger.info("stamped");
+        drop(logger);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.starts_with("[LLOG]::[1970-01-01T00:00:00+00:00]::"));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_null_and_blackhole_write_nothing() {
+        let path = std::env::temp_dir().join("llog_null_test.txt");
+        let _ = fs::remove_file(&path);
+
+        for log_type in ["null", "blackhole"] {
+            let opts = LoggerOpts::new()
+                .set_logfile_name(path.to_str().unwrap())
+                .set_log_type(log_type);
+            let mut logger = Logger::new(opts);
+            logger.info("gone");
+            assert!(logger.flush().is_ok());
+        }
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_batch_writes_on_drop() {
+        let path = std::env::temp_dir().join("llog_batch_test.txt");
+        let _ = fs::remove_file(&path);
+
+        let mut logger = Logger::new(LoggerOpts::new().set_logfile_name(path.to_str().unwrap()));
+        let mut batch = logger.batch();
+        batch.info("one");
+        batch.info("two");
+        assert!(!path.exists());
+        drop(batch);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("one") && contents.contains("two"));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_per_thread_files_merge_back() {
+        let dir = std::env::temp_dir();
+        let paths: Vec<_> = (1..=2)
+            .map(|n| dir.join(format!("llog_per_thread_test.worker-{n}.json")))
+            .collect();
+        let opts = LoggerOpts::new()
+            .set_logfile_name(dir.join("llog_per_thread_test.json").to_str().unwrap())
+            .set_file_format(LogFormat::Json)
+            .set_per_thread_files(true);
+        for n in 1..=2 {
+            let opts = opts.clone();
+            std::thread::Builder::new()
+                .name(format!("worker-{n}"))
+                .spawn(move || Logger::new(opts).info(format!("from {n}")))
+                .unwrap()
+                .join()
+                .unwrap();
+        }
+
+        let merged = crate::log::reader::merge_files(&paths).unwrap();
+        assert_eq!(merged.count(), 2);
+        for path in &paths {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn test_per_thread_text_files_merge_back() {
+        let dir = std::env::temp_dir();
+        let paths: Vec<_> = (1..=2)
+            .map(|n| dir.join(format!("llog_per_thread_text_test.worker-{n}.txt")))
+            .collect();
+        let opts = LoggerOpts::new()
+            .set_logfile_name(dir.join("llog_per_thread_text_test.txt").to_str().unwrap())
+            .set_per_thread_files(true);
+        for n in 1..=2 {
+            let opts = opts.clone();
+            std::thread::Builder::new()
+                .name(format!("worker-{n}"))
+                .spawn(move || Logger::new(opts).info(format!("from {n}")))
+                .unwrap()
+                .join()
+                .unwrap();
+        }
+
+        let merged = crate::log::reader::merge_files(&paths).unwrap();
+        assert_eq!(merged.map(Result::unwrap).count(), 2);
+        for path in &paths {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn test_local_handles_share_the_file() {
+        let path = std::env::temp_dir().join("llog_local_handle_test.txt");
+        let _ = fs::remove_file(&path);
+
+        let mut logger = Logger::new(LoggerOpts::new().set_logfile_name(path.to_str().unwrap()));
+        let workers: Vec<_> = (0..4)
+            .map(|n| {
+                let mut handle = logger.thread_local_handle();
+                std::thread::spawn(move || {
+                    for line in 0..100 {
+                        handle.info(format!("worker {n} line {line}"));
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        logger.info("main");
+        drop(logger);
+
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 401);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_local_handle_follows_rotation() {
+        let dir = std::env::temp_dir().join("llog_local_rotate_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("llog.txt");
+
+        let opts = LoggerOpts::new()
+            .set_logfile_name(path.to_str().unwrap())
+            .set_rotate_size(1)
+            .set_rotation_manifest(true);
+        let mut logger = Logger::new(opts);
+        let mut handle = logger.thread_local_handle();
+        logger.info("fills the file");
+        handle.info("from the handle");
+        handle.flush().unwrap();
+
+        let rotated = dir.join("llog.txt.1");
+        let old = fs::read_to_string(&rotated).unwrap();
+        assert!(old.ends_with("fills the file\n"));
+        let manifest = fs::read_to_string(dir.join("llog.txt.1.manifest")).unwrap();
+        assert!(manifest.contains(&format!("bytes={}\n", old.len())));
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .ends_with("from the handle\n"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_function_name_field() {
+        let path = std::env::temp_dir().join("llog_function_name_test.txt");
+        let _ = fs::remove_file(&path);
+
+        let opts = LoggerOpts::new()
+            .set_logfile_name(path.to_str().unwrap())
+            .set_function_names(true);
+        let mut logger = Logger::new(opts);
+        let mut log = || crate::info!(logger, "from a closure");
+        log();
+        drop(logger);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.ends_with(" fn=little_logger::tests::test_function_name_field\n"));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_label_fn_runs_per_record() {
+        let path = std::env::temp_dir().join("llog_label_fn_test.txt");
+        let _ = fs::remove_file(&path);
+
+        let opts = LoggerOpts::new()
+            .set_logfile_name(path.to_str().unwrap())
+            .set_label_fn(|meta| format!("T-{}", meta.level));
+        let mut logger = Logger::new(opts);
+        logger.warn("computed");
+        logger.with_label("FIXED").info("given");
+        drop(logger);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.starts_with("[T-WARN]"));
+        assert!(contents.contains("\n[FIXED]"));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[cfg(feature = "file")]
+    #[test]
+    fn test_tenant_sink_splits_by_field() {
+        let dir = std::env::temp_dir().join("llog_tenant_test");
+        let _ = fs::remove_dir_all(&dir);
+        let template = dir.join("{tenant}").join("app.log");
+
+        let opts = LoggerOpts::new().set_log_type("null").add_tenant_sink(
+            "tenant_id",
+            template.to_str().unwrap(),
+            LogFormat::Text,
+            1,
+        );
+        let mut logger = Logger::new(opts);
+        for tenant in ["acme", "globex", "acme"] {
+            logger.log_kv(Level::Info, "hello", &[("tenant_id", tenant)]);
+        }
+        drop(logger);
+
+        let acme = fs::read_to_string(dir.join("acme").join("app.log")).unwrap();
+        assert_eq!(acme.lines().count(), 2);
+        assert!(dir.join("globex").join("app.log").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
     #[test]
-    fn test_default_opts() {
-        
+    fn test_localized_level_names() {
+        use crate::log::Localization;
+        let path = std::env::temp_dir().join("llog_locale_test.txt");
+        let _ = fs::remove_file(&path);
+
+        let german = Localization::new()
+            .level(Level::Warn, "WARNUNG")
+            .text("starting", "Start");
+        let opts = LoggerOpts::new()
+            .set_logfile_name(path.to_str().unwrap())
+            .set_level_padding(true)
+            .set_localization(german);
+        let mut logger = Logger::new(opts);
+        logger.log_banner();
+        logger.warn("voll");
+        drop(logger);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.contains("[INFO   ] -> Start\n"));
+        assert!(contents.contains("[WARNUNG] -> voll\n"));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_raw_lines_and_undecorated_file() {
+        let path = std::env::temp_dir().join("llog_raw_test.txt");
+        let _ = fs::remove_file(&path);
+
+        let mut logger = Logger::new(LoggerOpts::new().set_logfile_name(path.to_str().unwrap()));
+        logger.log_raw("make: *** [all] Error 2");
+        drop(logger);
+        let opts = LoggerOpts::new()
+            .set_logfile_name(path.to_str().unwrap())
+            .set_file_decorations(false);
+        Logger::new(opts).warn("plain");
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert_eq!(contents, "make: *** [all] Error 2\nplain\n");
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_ingest_logs_each_line() {
+        let path = std::env::temp_dir().join("llog_ingest_test.txt");
+        let _ = fs::remove_file(&path);
+
+        let opts = LoggerOpts::new()
+            .set_logfile_name(path.to_str().unwrap())
+            .set_log_label("BUILD");
+        let mut logger = Logger::new(opts);
+        let src = "compiling\r\nlinking\n".as_bytes();
+        assert_eq!(logger.ingest(src, Level::Warn).unwrap(), 2);
+        drop(logger);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.ends_with("[WARN] -> linking\n"));
+        assert!(contents.starts_with("[BUILD]"));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_elapsed_clock_replaces_wall_time() {
+        let path = std::env::temp_dir().join("llog_clock_test.txt");
+        let _ = fs::remove_file(&path);
+
+        let opts = LoggerOpts::new()
+            .set_logfile_name(path.to_str().unwrap())
+            .set_clock(Clock::Elapsed);
+        let mut logger = Logger::new(opts);
+        logger.info("booted");
+        drop(logger);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.starts_with("[LLOG]::[+0000."));
+        assert!(contents.ends_with("s]::[INFO] -> booted\n"));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_file_clock_overrides_logger_clock() {
+        let path = std::env::temp_dir().join("llog_file_clock_test.txt");
+        let _ = fs::remove_file(&path);
+
+        let opts = LoggerOpts::new()
+            .set_logfile_name(path.to_str().unwrap())
+            .set_file_clock(Clock::Monotonic)
+            .deterministic();
+        let mut logger = Logger::new(opts);
+        logger.info("measured");
+        assert!(logger.to_string().contains("[1970-01-01 00:00:00]"));
+        drop(logger);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert_eq!(contents, "[LLOG]::[0.000000]::[INFO] -> measured\n");
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_stopwatch_logs_laps_and_total() {
+        let path = std::env::temp_dir().join("llog_stopwatch_test.txt");
+        let _ = fs::remove_file(&path);
+
+        let opts = LoggerOpts::new()
+            .set_logfile_name(path.to_str().unwrap())
+            .deterministic();
+        let mut logger = Logger::new(opts);
+        let mut sw = logger.stopwatch("import");
+        sw.lap("parse");
+        sw.finish();
+        drop(logger);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert!(lines[0].ends_with("import parse lap_ms=0.000 total_ms=0.000"));
+        assert!(lines[1].ends_with("import finished total_ms=0.000"));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_log_change_shows_old_and_new() {
+        let path = std::env::temp_dir().join("llog_change_test.json");
+        let _ = fs::remove_file(&path);
+
+        let opts = LoggerOpts::new()
+            .set_logfile_name(path.to_str().unwrap())
+            .set_file_format(LogFormat::Json);
+        let mut logger = Logger::new(opts);
+        logger.log_change("config.max_conns", 10, 20);
+        assert!(logger
+            .to_string()
+            .ends_with("config.max_conns: 10 -> 20\n\n"));
+        drop(logger);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.contains(r#""message":"config.max_conns","old":"10","new":"20""#));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_log_transition_fields() {
+        let capture = crate::log::testing::Capture::new();
+        let opts = LoggerOpts::new().set_log_type("null").set_capture(&capture);
+        let mut logger = Logger::new(opts);
+        logger.log_transition("order:1234", Some(1), None);
+
+        assert_eq!(
+            capture.snapshot(),
+            "[LLOG]::[INFO] -> state transition entity=order:1234 from=Some(1) to=None\n"
+        );
+    }
+
+    #[test]
+    fn test_gcp_source_location_is_the_macro_call() {
+        let path = std::env::temp_dir().join("llog_gcp_test.json");
+        let _ = fs::remove_file(&path);
+
+        let opts = LoggerOpts::new()
+            .set_logfile_name(path.to_str().unwrap())
+            .set_file_format(LogFormat::Gcp);
+        let mut logger = Logger::new(opts);
+        let line = line!() + 1;
+        crate::warn!(logger, "disk at {}%", 91);
+        drop(logger);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.starts_with(r#"{"severity":"WARNING","#));
+        assert!(contents.contains(&format!(
+            r#"{{"file":"src/lib.rs","line":"{}","function":"little_logger::tests::"#,
+            line
+        )));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_transaction_writes_only_on_commit() {
+        let capture = crate::log::testing::Capture::new();
+        let opts = LoggerOpts::new().set_log_type("null").set_capture(&capture);
+        let mut logger = Logger::new(opts);
+
+        let mut txn = logger.transaction();
+        txn.info("step one");
+        let mut inner = txn.transaction();
+        inner.log_kv(Level::Warn, "step two", &[("rows", "3")]);
+        inner.abort();
+        assert!(capture.records().is_empty());
+        txn.commit();
+        let mut txn = logger.transaction();
+        txn.info("never written");
+        drop(txn);
+
+        assert_eq!(capture.snapshot(), "[LLOG]::[INFO] -> step one\n");
+    }
+
+    #[test]
+    fn test_error_writes_the_debug_context() {
+        let capture = crate::log::testing::Capture::new();
+        let opts = LoggerOpts::new()
+            .set_log_type("null")
+            .set_capture(&capture)
+            .set_level(Level::Warn)
+            .set_error_context(2, std::time::Duration::from_secs(60));
+        let mut logger = Logger::new(opts);
+
+        logger.debug("dropped, only two are kept");
+        logger.debug("connecting");
+        logger.trace("never kept");
+        logger.info("retrying");
+        logger.error("gave up");
+        logger.debug("closing");
+
+        assert_eq!(
+            capture.snapshot(),
+            "[LLOG]::[DEBUG] -> connecting\n\
+             [LLOG]::[INFO] -> retrying\n\
+             [LLOG]::[ERROR] -> gave up\n\
+             [LLOG]::[DEBUG] -> closing\n"
+        );
+    }
+
+    #[test]
+    fn test_error_context_leaves_target_filtered_lines_out() {
+        let capture = crate::log::testing::Capture::new();
+        let opts = LoggerOpts::new()
+            .set_log_type("null")
+            .set_capture(&capture)
+            .set_level(Level::Warn)
+            .set_target_level("my_app::cache", Level::Error)
+            .set_error_context(4, std::time::Duration::from_secs(60));
+        let mut logger = Logger::new(opts);
+
+        logger.log_target("my_app::cache", Level::Warn, "evicted");
+        logger.log_target("my_app::db", Level::Info, "connecting");
+        logger.error("gave up");
+        logger.log_target("my_app::cache", Level::Info, "cleared");
+        logger.debug("closing");
+
+        assert_eq!(
+            capture.snapshot(),
+            "[LLOG]::[INFO]::[my_app::db] -> connecting\n\
+             [LLOG]::[ERROR] -> gave up\n\
+             [LLOG]::[DEBUG] -> closing\n"
+        );
+    }
+
+    #[test]
+    fn test_log_lossy_escapes_bad_bytes() {
+        let capture = crate::log::testing::Capture::new();
+        let opts = LoggerOpts::new()
+            .set_log_type("null")
+            .set_capture(&capture)
+            .set_invalid_utf8(crate::log::InvalidUtf8::Escape);
+        let mut logger = Logger::new(opts);
+
+        logger.log_lossy(b"frame \x00\x9f ok");
+        logger.ingest(&b"name \xc0.txt\n"[..], Level::Warn).unwrap();
+
+        assert!(capture.contains(Level::Info, "frame \x00\\x9f ok"));
+        assert!(capture.contains(Level::Warn, "name \\xc0.txt"));
+    }
+
+    #[test]
+    fn test_error_code_is_its_own_field() {
+        let path = std::env::temp_dir().join("llog_error_code_test.txt");
+        let _ = fs::remove_file(&path);
+
+        let opts = LoggerOpts::new()
+            .set_logfile_name(path.to_str().unwrap())
+            .set_file_format(LogFormat::Json);
+        let mut logger = Logger::new(opts);
+        let err = std::io::Error::new(std::io::ErrorKind::TimedOut, "no reply in 5s");
+        logger.error_code("E1042", "db timeout", &err);
+
+        let line = fs::read_to_string(&path).unwrap();
+        assert!(line.contains(
+            "\"message\":\"db timeout\",\"error\":\"no reply in 5s\",\"error.kind\":\"std::io::error::Error\",\"error.code\":\"E1042\"}"
+        ));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_record_ts_format_is_for_that_record_only() {
+        let path = std::env::temp_dir().join("llog_ts_format_test.txt");
+        let _ = fs::remove_file(&path);
+
+        let opts = LoggerOpts::new()
+            .set_logfile_name(path.to_str().unwrap())
+            .deterministic();
+        let mut logger = Logger::new(opts);
+        logger
+            .record(Level::Info)
+            .target("perf")
+            .field("stage", "decode")
+            .ts_format("%H:%M:%S%.9f")
+            .log("frame done");
+        logger.info("next");
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "[LLOG]::[00:00:00.000000000]::[INFO]::[perf] -> frame done stage=decode\n\
+             [LLOG]::[1970-01-01 00:00:00]::[INFO] -> next\n"
+        );
+        let _ = fs::remove_file(&path);
+    }
+
+    #[cfg(feature = "file")]
+    #[test]
+    fn test_typed_fields_are_json_numbers() {
+        let path = std::env::temp_dir().join("llog_typed_fields_test.txt");
+        let _ = fs::remove_file(&path);
+
+        let opts = LoggerOpts::new()
+            .set_logfile_name(path.to_str().unwrap())
+            .set_file_format(LogFormat::Json)
+            .deterministic();
+        let mut logger = Logger::new(opts);
+        logger
+            .record(Level::Info)
+            .field_i64("latency_ms", 12)
+            .field_f64("ratio", 0.5)
+            .field_f64("nan", f64::NAN)
+            .field_bool("cached", true)
+            .field("id", 7)
+            .log("served");
+        drop(logger);
+
+        let json = fs::read_to_string(&path).unwrap();
+        assert!(json.contains(r#""latency_ms":12,"ratio":0.5,"nan":"NaN","cached":true,"id":"7"}"#));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[cfg(feature = "file")]
+    #[test]
+    fn test_long_errors_go_to_the_detail_file() {
+        let path = std::env::temp_dir().join("llog_error_detail_test.txt");
+        let detail = std::env::temp_dir().join("llog_error_detail_test.detail.txt");
+        for path in [&path, &detail] {
+            let _ = fs::remove_file(path);
+        }
+
+        let opts = LoggerOpts::new()
+            .set_logfile_name(path.to_str().unwrap())
+            .set_error_detail_file(&detail)
+            .deterministic();
+        let mut logger = Logger::new(opts);
+        logger.log_msg_and_error("query failed", "timed out");
+        logger.log_msg_and_error("query failed", "timed out\n   0: app::db::query");
+        drop(logger);
+
+        let log = fs::read_to_string(&path).unwrap();
+        let id = log
+            .rsplit("detail=")
+            .next()
+            .unwrap()
+            .lines()
+            .next()
+            .unwrap();
+        assert_eq!(
+            log,
+            format!(
+                "[LLOG]::[1970-01-01 00:00:00]::[ERROR] -> query failed\ntimed out\n\
+                 [LLOG]::[1970-01-01 00:00:00]::[ERROR] -> query failed detail={id}\ntimed out\n"
+            )
+        );
+        assert_eq!(
+            fs::read_to_string(&detail).unwrap(),
+            format!(
+                "--- {id} [LLOG]::[1970-01-01 00:00:00]::[ERROR] -> query failed\n\
+                 timed out\n   0: app::db::query\n\n"
+            )
+        );
+        for path in [&path, &detail] {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[cfg(feature = "file")]
+    #[test]
+    fn test_detail_heading_follows_a_json_log() {
+        let path = std::env::temp_dir().join("llog_error_detail_json_test.txt");
+        let detail = std::env::temp_dir().join("llog_error_detail_json_test.detail.txt");
+        for path in [&path, &detail] {
+            let _ = fs::remove_file(path);
+        }
+
+        let opts = LoggerOpts::new()
+            .set_logfile_name(path.to_str().unwrap())
+            .set_file_format(LogFormat::Json)
+            .set_error_detail_file(&detail)
+            .deterministic();
+        let mut logger = Logger::new(opts);
+        logger.log_msg_and_error("query failed", "timed out\n   0: app::db::query");
+        drop(logger);
+
+        let entry = fs::read_to_string(&detail).unwrap();
+        let heading = entry.lines().next().unwrap();
+        let (_, json) = heading.split_once(' ').unwrap().1.split_once(' ').unwrap();
+        assert!(json.starts_with('{') && json.ends_with('}'));
+        assert!(json.contains("\"message\":\"query failed\""));
+        assert!(!json.contains("timed out"));
+        for path in [&path, &detail] {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn test_internal_problems_go_to_the_stream() {
+        let dir = std::env::temp_dir().join("llog_internal_test");
+        let _ = fs::remove_dir_all(&dir);
+        let capture = crate::log::testing::Capture::new();
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        // The log file's dir doesn't exist, so every write fails.
+        let missing = dir.join("llog.txt");
+        let opts = LoggerOpts::new()
+            .set_logfile_name(missing.to_str().unwrap())
+            .set_capture(&capture)
+            .set_internal_log(crate::log::InternalLog::Stream);
+        let mut logger = Logger::new(opts.clone());
+        logger.info("lost");
+
+        let records = capture.records();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].label, "LLOG-INTERNAL");
+        assert_eq!(records[1].level, Level::Warn);
+        assert!(records[1]
+            .message
+            .starts_with("sink file failed, degraded until it recovers: "));
+
+        // The file is left alone until its retry time, without a report
+        // for every record.
+        logger.info("lost too");
+        assert_eq!(capture.records().len(), 3);
+        // The warning went to the file as well.
+        assert_eq!(logger.stats().dropped("file"), 3);
+
+        let sink = seen.clone();
+        let opts = opts.on_internal_problem(move |problem| {
+            sink.lock().unwrap().push(problem.to_string());
+        });
+        Logger::new(opts).info("lost again");
+        assert_eq!(seen.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_mute_drops_matching_records_until_it_ends() {
+        let capture = crate::log::testing::Capture::new();
+        let opts = LoggerOpts::new()
+            .set_log_type("null")
+            .set_capture(&capture)
+            .set_internal_log(crate::log::InternalLog::Stream);
+        let mut logger = Logger::new(opts);
+        logger.mute_for(std::time::Duration::from_secs(3600), |r| {
+            r.label == "HEALTHCHECK"
+        });
+        logger.with_label("HEALTHCHECK").info("ok");
+        logger.with_label("HEALTHCHECK").info("ok");
+        logger.info("served");
+        logger.unmute();
+        logger.with_label("HEALTHCHECK").info("ok");
+
+        let messages: Vec<_> = capture
+            .records()
+            .into_iter()
+            .map(|record| record.message)
+            .collect();
+        assert_eq!(
+            messages,
+            ["served", "a mute ended after dropping 2 records", "ok"]
+        );
+    }
+
+    #[test]
+    fn test_stats_time_each_kind_of_sink() {
+        let capture = crate::log::testing::Capture::new();
+        let opts = LoggerOpts::new()
+            .set_log_type("null")
+            .set_file_format(LogFormat::Json)
+            .set_capture(&capture);
+        let mut logger = Logger::new(opts);
+        logger.info("one");
+        logger.info("two");
+
+        let stats = logger.stats();
+        assert_eq!(stats.sinks().collect::<Vec<_>>(), ["null", "capture"]);
+        assert_eq!(stats.write_seconds("capture").unwrap().count(), 2);
+        assert!(stats
+            .to_prometheus()
+            .contains("llog_write_seconds_count{sink=\"null\"} 2\n"));
+    }
+
+    #[cfg(feature = "file")]
+    #[test]
+    fn test_batch_is_written_out_at_max_batch_bytes() {
+        let path = std::env::temp_dir().join("llog_max_batch_test.txt");
+        let _ = fs::remove_file(&path);
+
+        let opts = LoggerOpts::new()
+            .set_logfile_name(path.to_str().unwrap())
+            .set_max_batch_bytes(64);
+        let mut logger = Logger::new(opts);
+        let mut batch = logger.batch();
+        for n in 0..4 {
+            batch.info(format!("a line long enough to matter {n}"));
+        }
+        assert!(fs::read_to_string(&path).unwrap().contains("matter 0"));
+        drop(batch);
+        drop(logger);
+
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 4);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_libraries_log_through_the_injected_logger() {
+        fn library_call() {
+            crate::log::library::with(|log| crate::info!(log, "from the library"));
+        }
+        library_call();
+
+        let capture = crate::log::testing::Capture::new();
+        let opts = LoggerOpts::new().set_log_type("null").set_capture(&capture);
+        crate::log::library::inject(Logger::new(opts.set_level(Level::Info)));
+        assert!(crate::log::library::with(
+            |log| !log.enabled(Level::Debug, "lib")
+        ));
+        library_call();
+        crate::log::library::take();
+        library_call();
+
+        let records = capture.records();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].message, "from the library");
+    }
+
+    #[test]
+    fn test_template_is_kept_beside_the_message() {
+        let capture = crate::log::testing::Capture::new();
+        let opts = LoggerOpts::new()
+            .set_log_type("null")
+            .set_capture(&capture)
+            .deterministic();
+        let mut logger = Logger::new(opts);
+        logger.info_t(
+            "user {user_id} bought {item}",
+            &[("user_id", "42"), ("item", "sku-7")],
+        );
+
+        let records = capture.records();
+        assert_eq!(records[0].message, "user 42 bought sku-7");
+        assert_eq!(records[0].fields.len(), 2);
+        assert_eq!(
+            records[0].template.as_deref(),
+            Some("user {user_id} bought {item}")
+        );
+        // The snapshot is the Text line without its time.
+        let text = logger.to_string().replace("::[1970-01-01 00:00:00]", "");
+        assert_eq!(text.trim_end(), "[LLOG]::[INFO] -> user 42 bought sku-7");
+        assert_eq!(capture.snapshot().trim_end(), text.trim_end());
+
+        capture.clear();
+        logger.log_kv(Level::Info, "user bought", &[("user_id", "42")]);
+        let text = logger.to_string().replace("::[1970-01-01 00:00:00]", "");
+        assert_eq!(capture.snapshot().trim_end(), text.trim_end());
+    }
+
+    #[test]
+    fn test_macros_take_a_target() {
+        let capture = crate::log::testing::Capture::new();
+        let opts = LoggerOpts::new()
+            .set_log_type("null")
+            .set_level(Level::Debug)
+            .set_target_level("my_app::cache", Level::Warn)
+            .set_capture(&capture);
+        let mut logger = Logger::new(opts);
+        crate::info!(logger, target: "my_app::cache::lru", "filtered out");
+        crate::warn!(logger, target: "my_app::cache", "{} evictions", 3);
+        crate::record!(logger, Level::Info)
+            .field("hits", 12)
+            .log("warmed");
+        crate::record!(logger, Level::Info)
+            .target("my_app::cache")
+            .log("filtered out too");
+
+        let records = capture.records();
+        let targets: Vec<_> = records.iter().map(|r| r.target.as_deref()).collect();
+        assert_eq!(
+            targets,
+            [Some("my_app::cache"), Some("little_logger::tests")]
+        );
+        assert_eq!(records[0].message, "3 evictions");
     }
 
+    #[test]
+    fn test_effective_config_matches_opts() {
+        let logger = Logger::new(LoggerOpts::new().set_log_label("CFG"));
+        assert!(format!("{:?}", logger.effective_config()).contains("\"CFG\""));
+    }
 }
@@ -0,0 +1,73 @@
+//! Making the Colored format show up as colors on every console.
+
+/// Get the console ready for ANSI color codes, false if it can't take them.
+///
+/// Unix terminals always can. On Windows virtual terminal processing is
+/// switched on for stdout, which cmd.exe on Windows 10 and later supports.
+/// Output that isn't a console, like a pipe or file, is left alone.
+#[cfg(windows)]
+pub(crate) fn enable_colors() -> bool {
+    use std::ffi::c_void;
+
+    const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+    const ENABLE_VIRTUAL_TERMINAL_PROCESSING: u32 = 0x0004;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetStdHandle(std_handle: u32) -> *mut c_void;
+        fn GetConsoleMode(console: *mut c_void, mode: *mut u32) -> i32;
+        fn SetConsoleMode(console: *mut c_void, mode: u32) -> i32;
+    }
+
+    // SAFETY: the handle comes straight from GetStdHandle and the mode is
+    // only written by GetConsoleMode.
+    unsafe {
+        let console = GetStdHandle(STD_OUTPUT_HANDLE);
+        let mut mode = 0;
+        if GetConsoleMode(console, &mut mode) == 0 {
+            // Not a console, the bytes go somewhere that can keep them.
+            return true;
+        }
+        mode & ENABLE_VIRTUAL_TERMINAL_PROCESSING != 0
+            || SetConsoleMode(console, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING) != 0
+    }
+}
+
+#[cfg(not(windows))]
+pub(crate) fn enable_colors() -> bool {
+    true
+}
+
+/// Drop the ANSI escape sequences from `text`.
+pub(crate) fn strip(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            out.push(c);
+            continue;
+        }
+        // CSI sequences end with a letter, `\x1b[1;36m`.
+        if chars.next() == Some('[') {
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_leaves_plain_text() {
+        assert_eq!(
+            strip("\x1b[1;36m[LLOG]\x1b[0m::\x1b[31m[ERROR]\x1b[0m -> x"),
+            "[LLOG]::[ERROR] -> x"
+        );
+    }
+}
@@ -0,0 +1,41 @@
+use std::ops::{Deref, DerefMut};
+
+use super::Logger;
+
+/// Groups the lines logged through it into one write and one flush, see
+/// `Logger::batch`.
+#[derive(Debug)]
+pub struct Batch<'l> {
+    logger: &'l mut Logger,
+    /// False when nested in another batch, which does the writing.
+    outer: bool,
+}
+
+impl<'l> Batch<'l> {
+    pub(crate) fn new(logger: &'l mut Logger) -> Batch<'l> {
+        let outer = !logger.set_batching(true);
+        Batch { logger, outer }
+    }
+}
+
+impl Deref for Batch<'_> {
+    type Target = Logger;
+
+    fn deref(&self) -> &Logger {
+        self.logger
+    }
+}
+
+impl DerefMut for Batch<'_> {
+    fn deref_mut(&mut self) -> &mut Logger {
+        self.logger
+    }
+}
+
+impl Drop for Batch<'_> {
+    fn drop(&mut self) {
+        if self.outer {
+            self.logger.set_batching(false);
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! Which binary wrote a log.
+//!
+//! Call `emit` from the build script so the `build_info!` macro can pick
+//! up the git commit and build time:
+//!
+//! ```no_run
+//! // In build.rs, with little_logger under [build-dependencies].
+//! little_logger::log::build_info::emit();
+//! ```
+
+use std::process::Command;
+
+/// The version, commit and build time of the running binary, see
+/// LoggerOpts::set_build_info. Build one with `build_info!()`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BuildInfo {
+    pub version: String,
+    pub git_sha: String,
+    pub build_ts: String,
+}
+
+impl BuildInfo {
+    /// `1.4.2 (3f9c2ab, built 2024-05-21T18:37:00Z)`
+    pub fn describe(&self) -> String {
+        let mut out = self.version.clone();
+        let details: Vec<String> = [
+            (!self.git_sha.is_empty()).then(|| self.git_sha.clone()),
+            (!self.build_ts.is_empty()).then(|| format!("built {}", self.build_ts)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        if !details.is_empty() {
+            out.push_str(&format!(" ({})", details.join(", ")));
+        }
+        out
+    }
+}
+
+/// Tell cargo the commit and build time as `LLOG_GIT_SHA` and
+/// `LLOG_BUILD_TS`, for `build_info!` to read. Meant for build scripts.
+///
+/// Outside a git checkout the commit is left empty.
+pub fn emit() {
+    let sha = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_default();
+    let built = chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ");
+    println!("cargo:rustc-env=LLOG_GIT_SHA={sha}");
+    println!("cargo:rustc-env=LLOG_BUILD_TS={built}");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe_skips_missing_parts() {
+        let mut info = BuildInfo {
+            version: String::from("1.4.2"),
+            ..BuildInfo::default()
+        };
+        assert_eq!(info.describe(), "1.4.2");
+        info.git_sha = String::from("3f9c2ab");
+        assert_eq!(info.describe(), "1.4.2 (3f9c2ab)");
+    }
+}
@@ -0,0 +1,108 @@
+//! Just enough CBOR for flat maps of strings.
+
+use std::io::{self, Read};
+
+use super::format::Record;
+
+/// The self-describe tag (55799) every record starts with.
+const SELF_DESCRIBE: [u8; 3] = [0xd9, 0xd9, 0xf7];
+
+const MAJOR_TEXT: u8 = 3;
+const MAJOR_MAP: u8 = 5;
+const MAJOR_TAG: u8 = 6;
+
+/// Encode `record` as a CBOR map wrapped in the self-describe tag.
+///
+/// CBOR items carry their own length, so records are simply written one
+/// after another.
+pub(crate) fn encode(record: &Record) -> Vec<u8> {
+    let fields = record.fields();
+
+    let mut out = SELF_DESCRIBE.to_vec();
+    push_head(&mut out, MAJOR_MAP, fields.len() as u64);
+    for (key, value) in fields {
+        push_head(&mut out, MAJOR_TEXT, key.len() as u64);
+        out.extend_from_slice(key.as_bytes());
+        push_head(&mut out, MAJOR_TEXT, value.len() as u64);
+        out.extend_from_slice(value.as_bytes());
+    }
+    out
+}
+
+fn push_head(out: &mut Vec<u8>, major: u8, arg: u64) {
+    let major = major << 5;
+    match arg {
+        0..=23 => out.push(major | arg as u8),
+        24..=0xff => out.extend_from_slice(&[major | 24, arg as u8]),
+        0x100..=0xffff => {
+            out.push(major | 25);
+            out.extend_from_slice(&(arg as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(major | 26);
+            out.extend_from_slice(&(arg as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 27);
+            out.extend_from_slice(&arg.to_be_bytes());
+        }
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Decode the next record from `input`, or None at a clean end of input.
+pub(crate) fn decode<R: Read>(input: &mut R) -> io::Result<Option<Vec<(String, String)>>> {
+    let mut first = [0u8; 1];
+    if input.read(&mut first)? == 0 {
+        return Ok(None);
+    }
+    let (mut major, mut arg) = read_head(first[0], input)?;
+    // The self-describe tag is optional when reading.
+    while major == MAJOR_TAG {
+        input.read_exact(&mut first)?;
+        (major, arg) = read_head(first[0], input)?;
+    }
+    if major != MAJOR_MAP {
+        return Err(invalid("expected a CBOR map"));
+    }
+    let mut fields = Vec::new();
+    for _ in 0..arg {
+        let key = read_text(input)?;
+        let value = read_text(input)?;
+        fields.push((key, value));
+    }
+    Ok(Some(fields))
+}
+
+fn read_head<R: Read>(initial: u8, input: &mut R) -> io::Result<(u8, u64)> {
+    let major = initial >> 5;
+    let arg = match initial & 0x1f {
+        info @ 0..=23 => info as u64,
+        info @ 24..=27 => {
+            let mut buf = [0u8; 8];
+            let len = 1 << (info - 24);
+            input.read_exact(&mut buf[8 - len..])?;
+            u64::from_be_bytes(buf)
+        }
+        _ => return Err(invalid("indefinite length CBOR items are not supported")),
+    };
+    Ok((major, arg))
+}
+
+fn read_text<R: Read>(input: &mut R) -> io::Result<String> {
+    let mut initial = [0u8; 1];
+    input.read_exact(&mut initial)?;
+    let (major, len) = read_head(initial[0], input)?;
+    if major != MAJOR_TEXT {
+        return Err(invalid("expected a CBOR text string"));
+    }
+    let mut buf = Vec::new();
+    input.take(len).read_to_end(&mut buf)?;
+    if buf.len() as u64 != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    String::from_utf8(buf).map_err(|_| invalid("string is not UTF-8"))
+}
@@ -0,0 +1,116 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, Local};
+
+/// What goes in the date/time column, see LoggerOpts::set_clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Clock {
+    /// The wall clock in the dt_format.
+    #[default]
+    Wall,
+    /// Time since the Logger was created, `+0003.482s`. Handy for startup
+    /// sequences and devices without a real-time clock.
+    Elapsed,
+    /// The wall clock followed by the elapsed time.
+    Both,
+    /// Seconds since the Logger was created to the microsecond, `3.482117`.
+    /// Never jumps with NTP or daylight saving, so subtracting two of
+    /// these gives a true duration.
+    Monotonic,
+}
+
+impl Clock {
+    /// The date/time column given the formatted wall clock and the time
+    /// since the Logger was created.
+    pub(crate) fn stamp(self, wall: &str, elapsed: Duration) -> String {
+        match self {
+            Clock::Wall => wall.to_string(),
+            Clock::Elapsed => format_elapsed(elapsed),
+            Clock::Both => format!("{} {}", wall, format_elapsed(elapsed)),
+            Clock::Monotonic => format!("{}.{:06}", elapsed.as_secs(), elapsed.subsec_micros()),
+        }
+    }
+}
+
+fn format_elapsed(elapsed: Duration) -> String {
+    format!("+{:04}.{:03}s", elapsed.as_secs(), elapsed.subsec_millis())
+}
+
+/// The wall clock in a dt_format, formatted again only when the finest
+/// unit the format shows has moved on, see LoggerOpts::set_time_cache.
+#[derive(Debug, Default)]
+pub(crate) struct TimeCache {
+    format: String,
+    /// The second or millisecond since the epoch `text` shows.
+    tick: Option<u128>,
+    text: String,
+}
+
+impl TimeCache {
+    pub(crate) fn now(&mut self, dt_format: &str) -> &str {
+        let now = SystemTime::now();
+        let Some(unit) = resolution(dt_format) else {
+            self.tick = None;
+            self.text = DateTime::<Local>::from(now).format(dt_format).to_string();
+            return &self.text;
+        };
+        let since = now.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let tick = since.as_nanos() / unit.as_nanos();
+        if self.tick != Some(tick) || self.format != dt_format {
+            self.tick = Some(tick);
+            self.format = dt_format.to_string();
+            self.text = DateTime::<Local>::from(now).format(dt_format).to_string();
+        }
+        &self.text
+    }
+}
+
+/// How long a time formatted with `dt_format` stays the same, or None
+/// when it shows micro or nanoseconds and is worth no cache. `%+` is
+/// RFC 3339 with as many fractional digits as it takes.
+fn resolution(dt_format: &str) -> Option<Duration> {
+    let fine = ["%f", "%.f", "%.6f", "%6f", "%.9f", "%9f", "%+"];
+    if fine.iter().any(|spec| dt_format.contains(spec)) {
+        None
+    } else if dt_format.contains("%.3f") || dt_format.contains("%3f") {
+        Some(Duration::from_millis(1))
+    } else {
+        Some(Duration::from_secs(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_elapsed_is_zero_padded() {
+        let elapsed = Duration::from_millis(3482);
+        assert_eq!(Clock::Elapsed.stamp("18:37:22", elapsed), "+0003.482s");
+        assert_eq!(
+            Clock::Both.stamp("18:37:22", elapsed),
+            "18:37:22 +0003.482s"
+        );
+        assert_eq!(Clock::Wall.stamp("18:37:22", elapsed), "18:37:22");
+        assert_eq!(Clock::Monotonic.stamp("18:37:22", elapsed), "3.482000");
+    }
+
+    #[test]
+    fn test_resolution_follows_the_finest_unit() {
+        assert_eq!(
+            resolution("%Y-%m-%d %H:%M:%S"),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(resolution("%H:%M:%S%.3f"), Some(Duration::from_millis(1)));
+        assert_eq!(resolution("%H:%M:%S%.f"), None);
+        assert_eq!(resolution("%H:%M:%S%.9f"), None);
+        assert_eq!(resolution("%H:%M:%S%6f"), None);
+        assert_eq!(resolution("%+"), None);
+
+        let mut cache = TimeCache::default();
+        let first = cache.now("%Y").to_string();
+        assert_eq!(cache.now("%Y"), first);
+        assert_ne!(cache.now("%Y %m"), first);
+    }
+}
@@ -0,0 +1,474 @@
+//! Shipping records straight to CloudWatch Logs.
+
+use std::io;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+
+use super::format::{push_json_str, Layout, Record};
+use super::internal::Problems;
+use super::reader::parse_json_object;
+use super::sigv4::{self, Credentials, Request};
+use super::sink::{self, QueueLimit, Sink};
+use super::LogFormat;
+
+/// PutLogEvents limits, see the CloudWatch Logs API reference.
+const MAX_BATCH_BYTES: usize = 1_048_576;
+const MAX_EVENTS: usize = 10_000;
+/// Counted against the batch size for every event.
+const EVENT_OVERHEAD: usize = 26;
+const MAX_EVENT_BYTES: usize = 256 * 1024 - EVENT_OVERHEAD;
+/// The events in one batch can't be further apart than this.
+const MAX_SPAN_MS: i64 = 24 * 60 * 60 * 1000;
+/// The longest a flush waits for the batch to be sent. The thread keeps
+/// sending after that, the logging thread just stops waiting for it.
+const FLUSH_WAIT: Duration = Duration::from_secs(2);
+/// The longest dropping the sink waits for the last batch.
+const DROP_WAIT: Duration = Duration::from_secs(2);
+/// The longest one call to the endpoint may take.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A CloudWatch Logs stream to send every record to, see
+/// LoggerOpts::add_cloudwatch_sink.
+///
+/// Records are batched into PutLogEvents calls on a thread of their own,
+/// sent when a batch is full or `flush_interval` after its first record.
+/// Credentials are looked up like the AWS SDKs do, from the environment,
+/// `~/.aws/credentials`, the ECS container endpoint or the EC2 instance
+/// role, unless set with `credentials`. The stream is created if it
+/// doesn't exist, the group has to.
+///
+/// ```no_run
+/// use little_logger::log::{CloudWatch, Logger, LoggerOpts};
+///
+/// let opts = LoggerOpts::new()
+///     .add_cloudwatch_sink(CloudWatch::new("/app/web", "web-1", "eu-west-1"));
+/// let mut logger = Logger::new(opts);
+/// ```
+#[derive(Debug, Clone)]
+pub struct CloudWatch {
+    group: String,
+    stream: String,
+    region: String,
+    endpoint: String,
+    format: LogFormat,
+    credentials: Option<(String, String)>,
+    flush_interval: Duration,
+}
+
+impl CloudWatch {
+    pub fn new(group: &str, stream: &str, region: &str) -> CloudWatch {
+        CloudWatch {
+            group: group.to_string(),
+            stream: stream.to_string(),
+            region: region.to_string(),
+            endpoint: format!("https://logs.{region}.amazonaws.com"),
+            format: LogFormat::Json,
+            credentials: None,
+            flush_interval: Duration::from_secs(5),
+        }
+    }
+    /// Send to another endpoint, like LocalStack.
+    ///
+    /// Default: `https://logs.<region>.amazonaws.com`
+    pub fn endpoint(mut self, endpoint: &str) -> Self {
+        self.endpoint = endpoint.trim_end_matches('/').to_string();
+        self
+    }
+    /// The layout of each event's message.
+    ///
+    /// Default: LogFormat::Json
+    pub fn format(mut self, format: LogFormat) -> Self {
+        self.format = format;
+        self
+    }
+    pub fn credentials(mut self, access_key: &str, secret_key: &str) -> Self {
+        self.credentials = Some((access_key.to_string(), secret_key.to_string()));
+        self
+    }
+    /// The longest a record waits before its batch is sent.
+    ///
+    /// Default: 5 seconds
+    pub fn flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = interval;
+        self
+    }
+}
+
+enum Message {
+    Event(i64, String),
+    /// Send what's batched, then answer.
+    Flush(Sender<()>),
+}
+
+/// Hands records to the thread that sends them.
+#[derive(Debug)]
+pub(crate) struct CloudWatchSink {
+    format: LogFormat,
+    tx: Option<Sender<Message>>,
+    worker: Option<JoinHandle<()>>,
+    queue: QueueLimit,
+}
+
+impl CloudWatchSink {
+    /// Dropped events and failed calls are reported to `problems`.
+    pub(crate) fn new(spec: CloudWatch, queue: QueueLimit, problems: Problems) -> CloudWatchSink {
+        let (tx, rx) = mpsc::channel();
+        let format = spec.format;
+        let taken = queue.clone();
+        let worker = thread::spawn(move || run(Client::new(spec, problems), rx, taken));
+        CloudWatchSink {
+            format,
+            tx: Some(tx),
+            worker: Some(worker),
+            queue,
+        }
+    }
+
+    fn send(&self, message: Message) -> io::Result<()> {
+        self.tx
+            .as_ref()
+            .and_then(|tx| tx.send(message).ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::BrokenPipe, "CloudWatch sender stopped"))
+    }
+}
+
+impl Sink for CloudWatchSink {
+    fn write_record(&mut self, record: &Record, layout: &Layout) -> io::Result<()> {
+        let line = self.format.render(record, layout);
+        let message = line.trim_end_matches('\n').to_string();
+        self.queue.reserve(message.len())?;
+        self.send(Message::Event(Utc::now().timestamp_millis(), message))
+    }
+
+    /// The batch goes out on the sink's thread, so a slow or hanging
+    /// endpoint holds a flush up for FLUSH_WAIT at most.
+    fn flush(&mut self) -> io::Result<()> {
+        let (ack, done) = mpsc::channel();
+        self.send(Message::Flush(ack))?;
+        let _ = done.recv_timeout(FLUSH_WAIT);
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "cloudwatch"
+    }
+}
+
+impl Drop for CloudWatchSink {
+    /// Let the thread send what's left, for DROP_WAIT at most.
+    fn drop(&mut self) {
+        self.tx = None;
+        if let Some(worker) = self.worker.take() {
+            sink::join_within(worker, DROP_WAIT);
+        }
+    }
+}
+
+fn run(mut client: Client, rx: Receiver<Message>, queue: QueueLimit) {
+    let mut batch = Batch::default();
+    loop {
+        let wait = match batch.started {
+            Some(started) => client.spec.flush_interval.saturating_sub(started.elapsed()),
+            None => Duration::from_secs(3600),
+        };
+        match rx.recv_timeout(wait) {
+            Ok(Message::Event(timestamp, message)) => {
+                queue.release(message.len());
+                if !batch.fits(timestamp, &message) {
+                    client.put(&mut batch);
+                }
+                batch.push(timestamp, message);
+            }
+            Ok(Message::Flush(ack)) => {
+                client.put(&mut batch);
+                let _ = ack.send(());
+            }
+            Err(RecvTimeoutError::Timeout) => client.put(&mut batch),
+            Err(RecvTimeoutError::Disconnected) => {
+                client.put(&mut batch);
+                return;
+            }
+        }
+    }
+}
+
+/// The events of one PutLogEvents call.
+#[derive(Debug, Default)]
+struct Batch {
+    events: Vec<(i64, String)>,
+    bytes: usize,
+    started: Option<Instant>,
+}
+
+impl Batch {
+    /// Whether one more event stays within the PutLogEvents limits.
+    fn fits(&self, timestamp: i64, message: &str) -> bool {
+        let Some((first, _)) = self.events.first() else {
+            return true;
+        };
+        self.events.len() < MAX_EVENTS
+            && self.bytes + message.len().min(MAX_EVENT_BYTES) + EVENT_OVERHEAD <= MAX_BATCH_BYTES
+            && timestamp - first < MAX_SPAN_MS
+    }
+
+    /// Add an event, cutting it down to the largest one CloudWatch takes.
+    fn push(&mut self, timestamp: i64, mut message: String) {
+        if message.len() > MAX_EVENT_BYTES {
+            let mut end = MAX_EVENT_BYTES;
+            while !message.is_char_boundary(end) {
+                end -= 1;
+            }
+            message.truncate(end);
+        }
+        self.bytes += message.len() + EVENT_OVERHEAD;
+        self.started.get_or_insert_with(Instant::now);
+        self.events.push((timestamp, message));
+    }
+
+    fn clear(&mut self) {
+        *self = Batch::default();
+    }
+}
+
+/// Talks to the CloudWatch Logs API for one stream.
+struct Client {
+    spec: CloudWatch,
+    credentials: Option<(Credentials, Option<DateTime<Utc>>)>,
+    sequence_token: Option<String>,
+    problems: Problems,
+}
+
+/// Why a call failed, the `__type` of the error with its body.
+enum CallError {
+    Api(String, Vec<(String, String)>),
+    Transport(String),
+}
+
+impl Client {
+    fn new(spec: CloudWatch, problems: Problems) -> Client {
+        Client {
+            spec,
+            credentials: None,
+            sequence_token: None,
+            problems,
+        }
+    }
+
+    /// Send the batch and empty it, whether that worked or not.
+    fn put(&mut self, batch: &mut Batch) {
+        if batch.events.is_empty() {
+            return;
+        }
+        if let Err(e) = self.put_with_retry(batch) {
+            self.problems.report(format!(
+                "dropped {} CloudWatch events: {e}",
+                batch.events.len()
+            ));
+        }
+        batch.clear();
+    }
+
+    fn put_with_retry(&mut self, batch: &Batch) -> Result<(), String> {
+        let mut wait = Duration::from_secs(1);
+        let mut created = false;
+        for _ in 0..4 {
+            let body = self.put_body(batch);
+            let error = match self.call("PutLogEvents", &body) {
+                Ok(fields) => {
+                    self.sequence_token = field(&fields, "nextSequenceToken");
+                    return Ok(());
+                }
+                Err(CallError::Api(kind, fields)) => match kind.as_str() {
+                    "InvalidSequenceTokenException" => {
+                        self.sequence_token = field(&fields, "expectedSequenceToken");
+                        continue;
+                    }
+                    "DataAlreadyAcceptedException" => {
+                        self.sequence_token = field(&fields, "expectedSequenceToken");
+                        return Ok(());
+                    }
+                    "ResourceNotFoundException" if !created => {
+                        created = true;
+                        self.create_stream()?;
+                        continue;
+                    }
+                    "ExpiredTokenException" | "UnrecognizedClientException" => {
+                        self.credentials = None;
+                        kind
+                    }
+                    "ThrottlingException" | "ServiceUnavailableException" => kind,
+                    _ => {
+                        return Err(format!(
+                            "{kind} {}",
+                            field(&fields, "message").unwrap_or_default()
+                        ))
+                    }
+                },
+                Err(CallError::Transport(e)) => e,
+            };
+            self.problems
+                .report(format!("CloudWatch PutLogEvents failed, retrying: {error}"));
+            thread::sleep(wait);
+            wait *= 2;
+        }
+        Err(String::from("gave up after retrying"))
+    }
+
+    fn put_body(&self, batch: &Batch) -> String {
+        let mut body = String::from("{\"logGroupName\":");
+        push_json_str(&mut body, &self.spec.group);
+        body.push_str(",\"logStreamName\":");
+        push_json_str(&mut body, &self.spec.stream);
+        if let Some(token) = &self.sequence_token {
+            body.push_str(",\"sequenceToken\":");
+            push_json_str(&mut body, token);
+        }
+        body.push_str(",\"logEvents\":[");
+        for (i, (timestamp, message)) in batch.events.iter().enumerate() {
+            if i > 0 {
+                body.push(',');
+            }
+            body.push_str(&format!("{{\"timestamp\":{timestamp},\"message\":"));
+            push_json_str(&mut body, message);
+            body.push('}');
+        }
+        body.push_str("]}");
+        body
+    }
+
+    fn create_stream(&mut self) -> Result<(), String> {
+        let mut body = String::from("{\"logGroupName\":");
+        push_json_str(&mut body, &self.spec.group);
+        body.push_str(",\"logStreamName\":");
+        push_json_str(&mut body, &self.spec.stream);
+        body.push('}');
+        match self.call("CreateLogStream", &body) {
+            Ok(_) => Ok(()),
+            Err(CallError::Api(kind, _)) if kind == "ResourceAlreadyExistsException" => Ok(()),
+            Err(CallError::Api(kind, fields)) => Err(format!(
+                "{kind} {}",
+                field(&fields, "message").unwrap_or_default()
+            )),
+            Err(CallError::Transport(e)) => Err(e),
+        }
+    }
+
+    fn credentials(&mut self) -> Result<Credentials, String> {
+        if let Some((access_key, secret_key)) = &self.spec.credentials {
+            return Ok(Credentials {
+                access_key: access_key.clone(),
+                secret_key: secret_key.clone(),
+                token: None,
+            });
+        }
+        let stale = |expires: &Option<DateTime<Utc>>| {
+            expires.is_some_and(|e| e - chrono::Duration::minutes(5) < Utc::now())
+        };
+        if self.credentials.as_ref().is_none_or(|(_, e)| stale(e)) {
+            self.credentials = Some(Credentials::default_chain()?);
+        }
+        Ok(self.credentials.as_ref().expect("just looked up").0.clone())
+    }
+
+    fn call(&mut self, action: &str, body: &str) -> Result<Vec<(String, String)>, CallError> {
+        let credentials = self.credentials().map_err(CallError::Transport)?;
+        let target = format!("Logs_20140328.{action}");
+        let content_type = "application/x-amz-json-1.1";
+        let request = Request {
+            method: "POST",
+            host: sigv4::host(&self.spec.endpoint),
+            path: "/",
+            headers: &[("content-type", content_type), ("x-amz-target", &target)],
+            body: body.as_bytes(),
+        };
+        let headers = sigv4::sign(&credentials, &self.spec.region, "logs", &request);
+        let mut request = ureq::post(&format!("{}/", self.spec.endpoint)).timeout(REQUEST_TIMEOUT);
+        for (name, value) in &headers {
+            request = request.set(name, value);
+        }
+        let fields = |response: ureq::Response| {
+            let body = response.into_string().unwrap_or_default();
+            parse_json_object(&body).unwrap_or_default()
+        };
+        match request.send_string(body) {
+            Ok(response) => Ok(fields(response)),
+            Err(ureq::Error::Status(status, response)) => {
+                let fields = fields(response);
+                let kind = field(&fields, "__type").unwrap_or_else(|| format!("HTTP {status}"));
+                // Sometimes prefixed with the namespace, `com.amazonaws...#`.
+                let kind = kind.rsplit('#').next().unwrap_or_default().to_string();
+                Err(CallError::Api(kind, fields))
+            }
+            Err(e) => Err(CallError::Transport(e.to_string())),
+        }
+    }
+}
+
+fn field(fields: &[(String, String)], key: &str) -> Option<String> {
+    fields
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_respects_put_log_events_limits() {
+        let mut batch = Batch::default();
+        assert!(batch.fits(0, "first"));
+        batch.push(0, "x".repeat(MAX_EVENT_BYTES + 10));
+        assert_eq!(batch.events[0].1.len(), MAX_EVENT_BYTES);
+        for _ in 0..2 {
+            batch.push(1, "x".repeat(MAX_EVENT_BYTES));
+        }
+        assert!(batch.fits(1, &"x".repeat(MAX_EVENT_BYTES)));
+        batch.push(1, "x".repeat(MAX_EVENT_BYTES));
+        assert!(!batch.fits(1, "one more byte"));
+        batch.clear();
+        batch.push(0, String::from("first"));
+        assert!(!batch.fits(MAX_SPAN_MS, "a day later"));
+    }
+
+    #[test]
+    fn test_put_body_carries_the_sequence_token() {
+        let mut client = Client::new(
+            CloudWatch::new("/app", "web-1", "eu-west-1"),
+            Problems::default(),
+        );
+        client.sequence_token = Some(String::from("495"));
+        let mut batch = Batch::default();
+        batch.push(1716316642000, String::from("say \"hi\""));
+        assert_eq!(
+            client.put_body(&batch),
+            r#"{"logGroupName":"/app","logStreamName":"web-1","sequenceToken":"495","logEvents":[{"timestamp":1716316642000,"message":"say \"hi\""}]}"#
+        );
+    }
+
+    #[test]
+    fn test_flush_and_drop_dont_wait_on_a_hanging_endpoint() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        // Takes the connection and never answers.
+        thread::spawn(move || {
+            let _held: Vec<_> = listener.incoming().collect();
+        });
+        let spec = CloudWatch::new("/app", "web-1", "eu-west-1")
+            .endpoint(&endpoint)
+            .credentials("AKID", "secret");
+        let mut sink = CloudWatchSink::new(spec, QueueLimit::new(1 << 20), Problems::default());
+        sink.send(Message::Event(0, String::from("stuck"))).unwrap();
+
+        let start = Instant::now();
+        sink.flush().unwrap();
+        assert!(start.elapsed() < FLUSH_WAIT * 2);
+        let start = Instant::now();
+        drop(sink);
+        assert!(start.elapsed() < DROP_WAIT * 2);
+    }
+}
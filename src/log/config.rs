@@ -0,0 +1,30 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::LoggerOpts;
+
+impl LoggerOpts {
+    /// Read options from TOML. Missing keys keep their default.
+    ///
+    /// The keys are the field names of LoggerOpts, which is also what
+    /// serializing Logger::effective_config gives back.
+    ///
+    /// ```
+    /// use little_logger::log::LoggerOpts;
+    ///
+    /// let opts = LoggerOpts::from_toml_str(r#"
+    ///     log_label = "SERVER"
+    ///     log_target = "Both"
+    ///     file_format = "Json"
+    /// "#).unwrap();
+    /// ```
+    pub fn from_toml_str(toml: &str) -> io::Result<LoggerOpts> {
+        toml::from_str(toml).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Read options from a TOML file, see from_toml_str.
+    pub fn from_toml_file<P: AsRef<Path>>(path: P) -> io::Result<LoggerOpts> {
+        LoggerOpts::from_toml_str(&fs::read_to_string(path)?)
+    }
+}
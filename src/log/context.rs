@@ -0,0 +1,48 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use super::transaction::HeldLine;
+
+/// Lines below the level kept back in case an error follows, see
+/// LoggerOpts::set_error_context.
+#[derive(Debug)]
+pub(crate) struct ErrorContext {
+    keep: usize,
+    window: Duration,
+    lines: VecDeque<HeldLine>,
+    /// Until when lines down to Debug are written as they come.
+    open_until: Option<Instant>,
+}
+
+impl ErrorContext {
+    pub(crate) fn new(keep: usize, window: Duration) -> ErrorContext {
+        ErrorContext {
+            keep,
+            window,
+            lines: VecDeque::with_capacity(keep),
+            open_until: None,
+        }
+    }
+
+    pub(crate) fn is_open(&self, now: Instant) -> bool {
+        self.open_until.is_some_and(|until| now < until)
+    }
+
+    /// Keep a line, forgetting the oldest once there are `keep`.
+    pub(crate) fn hold(&mut self, line: HeldLine) {
+        if self.keep == 0 {
+            return;
+        }
+        if self.lines.len() == self.keep {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+
+    /// An error was logged: open the window and hand back the lines that
+    /// led up to it.
+    pub(crate) fn trigger(&mut self, now: Instant) -> Vec<HeldLine> {
+        self.open_until = Some(now + self.window);
+        self.lines.drain(..).collect()
+    }
+}
@@ -0,0 +1,444 @@
+//! The last few KB of log output kept in a memory-mapped file, so they
+//! outlive a crash that loses everything still buffered, see
+//! LoggerOpts::add_crash_ring.
+//!
+//! Writing to the mapping is a memory copy, the kernel owns the pages and
+//! writes them to the file even if the process is killed (SIGKILL, the
+//! OOM killer) a moment later. Only losing the machine loses them.
+//!
+//! The file is a header and a ring of bytes:
+//!
+//! | bytes  | what                                                 |
+//! |--------|------------------------------------------------------|
+//! | 0..8   | `LLCRASH1`                                           |
+//! | 8..16  | ring capacity, u64 little endian                     |
+//! | 16..24 | bytes written since the ring was made, u64 little endian |
+//! | 24..32 | zero                                                 |
+//! | 32..   | the ring                                             |
+//!
+//! The count is updated after the bytes of a record are in, and a reader
+//! drops everything up to the first newline after the oldest byte, so
+//! a record cut by the crash or half overwritten never shows up.
+//!
+//! A memory ring, see LoggerOpts::add_memory_ring, is the same header and
+//! ring in the heap, for a core dump to carry instead of a file. The
+//! `LLOG_MEMORY_RING` symbol says where it is:
+//!
+//! | bytes  | what                                        |
+//! |--------|---------------------------------------------|
+//! | 0..8   | `LLRINGAT`                                  |
+//! | 8..16  | address of the ring's header, 0 until made  |
+//! | 16..24 | length of header and ring together          |
+//!
+//! (the last two are pointer sized, 8 bytes on 64 bit targets). From gdb:
+//!
+//! ```text
+//! (gdb) set $at = *(char **)((char *)&LLOG_MEMORY_RING + 8)
+//! (gdb) set $len = *(unsigned long *)((char *)&LLOG_MEMORY_RING + 16)
+//! (gdb) dump binary memory ring.bin $at $at + $len
+//! ```
+//!
+//! and crash::recover("ring.bin") gives the records. Without symbols, a
+//! minidump tool can search the process's memory for `LLRINGAT` instead.
+
+use std::fs;
+#[cfg(unix)]
+use std::fs::{File, OpenOptions};
+use std::io;
+#[cfg(unix)]
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{fence, AtomicPtr, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use super::format::{Layout, LogFormat, Record};
+use super::sink::Sink;
+
+/// What every crash ring starts with. The number goes up if the layout
+/// ever changes.
+const MAGIC: &[u8; 8] = b"LLCRASH1";
+const HEADER: usize = 32;
+
+/// Where a debugger finds the memory ring, see the module docs. Only its
+/// layout matters, nothing in the crate reads it.
+#[repr(C)]
+#[derive(Debug)]
+pub struct RingLocator {
+    magic: [u8; 8],
+    ring: AtomicPtr<u8>,
+    len: AtomicUsize,
+}
+
+/// The first memory ring made, while it's alive. Rings made while it lives
+/// aren't pointed at, so a short lived Logger can't take the symbol away.
+#[no_mangle]
+pub static LLOG_MEMORY_RING: RingLocator = RingLocator {
+    magic: *b"LLRINGAT",
+    ring: AtomicPtr::new(std::ptr::null_mut()),
+    len: AtomicUsize::new(0),
+};
+
+/// Held while LLOG_MEMORY_RING changes hands, so its two words always
+/// describe the same ring.
+static LOCATOR: Mutex<()> = Mutex::new(());
+
+/// A crash ring to keep, see LoggerOpts::add_crash_ring and
+/// add_memory_ring.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct CrashRingSpec {
+    /// None for a memory ring.
+    pub(crate) path: Option<PathBuf>,
+    pub(crate) bytes: usize,
+    pub(crate) format: LogFormat,
+}
+
+fn write_header(bytes: &mut [u8], capacity: usize) {
+    bytes[..8].copy_from_slice(MAGIC);
+    bytes[8..16].copy_from_slice(&(capacity as u64).to_le_bytes());
+}
+
+/// A shared, writable mapping of a whole ring file.
+#[cfg(unix)]
+#[derive(Debug)]
+struct Mapping {
+    ptr: *mut u8,
+    len: usize,
+    /// Kept open for msync's sake, the mapping outlives a closed file.
+    _file: File,
+}
+
+// SAFETY: the mapping belongs to one sink and is only touched through
+// `&mut self`, moving it to another thread is moving a Vec.
+#[cfg(unix)]
+unsafe impl Send for Mapping {}
+
+#[cfg(unix)]
+impl Mapping {
+    fn create(path: &Path, capacity: usize) -> io::Result<Mapping> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        let len = HEADER + capacity;
+        file.set_len(len as u64)?;
+        // SAFETY: a fresh shared mapping of a file just sized to `len`,
+        // nothing else in this process maps it.
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        let mut mapping = Mapping {
+            ptr: ptr.cast(),
+            len,
+            _file: file,
+        };
+        write_header(mapping.bytes(), capacity);
+        Ok(mapping)
+    }
+
+    fn bytes(&mut self) -> &mut [u8] {
+        // SAFETY: `ptr` is a live mapping of `len` bytes until drop.
+        unsafe { std::slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // SAFETY: unmapping what create mapped, once.
+        unsafe {
+            libc::munmap(self.ptr.cast(), self.len);
+        }
+    }
+}
+
+/// A memory ring, pointed at by LLOG_MEMORY_RING if it was the first.
+#[derive(Debug)]
+struct Memory {
+    bytes: Box<[u8]>,
+}
+
+impl Memory {
+    fn create(capacity: usize) -> Memory {
+        let mut bytes = vec![0u8; HEADER + capacity].into_boxed_slice();
+        write_header(&mut bytes, capacity);
+        let _locator = LOCATOR.lock().unwrap_or_else(|e| e.into_inner());
+        if LLOG_MEMORY_RING.ring.load(Ordering::Acquire).is_null() {
+            // The length first, so a dump taken in between never pairs
+            // this ring with the length of one before it.
+            LLOG_MEMORY_RING.len.store(bytes.len(), Ordering::Release);
+            LLOG_MEMORY_RING
+                .ring
+                .store(bytes.as_mut_ptr(), Ordering::Release);
+        }
+        Memory { bytes }
+    }
+}
+
+impl Drop for Memory {
+    fn drop(&mut self) {
+        // Only if this is the ring the symbol points at.
+        let _locator = LOCATOR.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = LLOG_MEMORY_RING.ring.compare_exchange(
+            self.bytes.as_mut_ptr(),
+            std::ptr::null_mut(),
+            Ordering::AcqRel,
+            Ordering::Relaxed,
+        );
+    }
+}
+
+#[derive(Debug)]
+enum Ring {
+    #[cfg(unix)]
+    Mapped(Mapping),
+    Memory(Memory),
+}
+
+impl Ring {
+    fn bytes(&mut self) -> &mut [u8] {
+        match self {
+            #[cfg(unix)]
+            Ring::Mapped(mapping) => mapping.bytes(),
+            Ring::Memory(memory) => &mut memory.bytes,
+        }
+    }
+}
+
+/// Writes every record into a crash ring.
+#[derive(Debug)]
+pub(crate) struct CrashRingSink {
+    spec: CrashRingSpec,
+    /// Made on the first record.
+    ring: Option<Ring>,
+    written: u64,
+}
+
+impl CrashRingSink {
+    pub(crate) fn new(spec: CrashRingSpec) -> CrashRingSink {
+        CrashRingSink {
+            spec,
+            ring: None,
+            written: 0,
+        }
+    }
+
+    fn create(&self) -> io::Result<Ring> {
+        match &self.spec.path {
+            #[cfg(unix)]
+            Some(path) => {
+                let mut seen = SEEN.lock().unwrap_or_else(|e| e.into_inner());
+                if !seen.contains(path) {
+                    if path.exists() {
+                        fs::rename(path, previous(path))?;
+                    }
+                    seen.push(path.clone());
+                }
+                Ok(Ring::Mapped(Mapping::create(path, self.spec.bytes)?))
+            }
+            // add_crash_ring is unix only.
+            #[cfg(not(unix))]
+            Some(_) => unreachable!(),
+            None => Ok(Ring::Memory(Memory::create(self.spec.bytes))),
+        }
+    }
+}
+
+/// The ring paths this process has made a ring at. What is found at one
+/// of them is this run's own, and stays where it is.
+#[cfg(unix)]
+static SEEN: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Where the ring left by the run before is moved, so starting again
+/// right after a crash doesn't write over what it left.
+#[cfg(unix)]
+fn previous(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".prev");
+    PathBuf::from(name)
+}
+
+impl Sink for CrashRingSink {
+    fn write_record(&mut self, record: &Record, layout: &Layout) -> io::Result<()> {
+        let capacity = self.spec.bytes;
+        let ring = match &mut self.ring {
+            Some(ring) => ring,
+            None => {
+                let ring = self.create()?;
+                self.ring.insert(ring)
+            }
+        };
+        let line = self.spec.format.encode(record, layout);
+        // A record bigger than the whole ring keeps its end.
+        let line = &line[line.len().saturating_sub(capacity)..];
+        let bytes = ring.bytes();
+        let ring = &mut bytes[HEADER..];
+        let start = (self.written % capacity as u64) as usize;
+        let first = line.len().min(capacity - start);
+        ring[start..start + first].copy_from_slice(&line[..first]);
+        ring[..line.len() - first].copy_from_slice(&line[first..]);
+        self.written += line.len() as u64;
+        // The bytes go in before the count that says they're there.
+        fence(Ordering::Release);
+        bytes[16..24].copy_from_slice(&self.written.to_le_bytes());
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        // Only for a machine going down, a killed process loses nothing
+        // without it. MS_ASYNC starts the write and doesn't wait for it.
+        #[cfg(unix)]
+        if let Some(Ring::Mapped(mapping)) = &self.ring {
+            // SAFETY: syncing the live mapping made by create.
+            if unsafe { libc::msync(mapping.ptr.cast(), mapping.len, libc::MS_ASYNC) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "crash_ring"
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// The whole records still in a crash ring file, or a memory ring dumped
+/// to a file, oldest first.
+///
+/// Read the `.prev` file next to it once the process was started again,
+/// the new run moves the old ring there before making its own.
+///
+/// ```no_run
+/// use little_logger::log::crash;
+///
+/// let last = crash::recover("/dev/shm/app.crash").unwrap();
+/// print!("{last}");
+/// ```
+pub fn recover<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    recover_bytes(&fs::read(path)?)
+}
+
+/// Like recover, for a ring already read into memory, say out of a
+/// minidump.
+pub fn recover_bytes(bytes: &[u8]) -> io::Result<String> {
+    if bytes.len() < HEADER || &bytes[..8] != MAGIC {
+        return Err(invalid("not a crash ring"));
+    }
+    let word = |at: usize| {
+        let word: [u8; 8] = bytes[at..at + 8].try_into().unwrap();
+        u64::from_le_bytes(word)
+    };
+    let capacity = word(8) as usize;
+    let written = word(16);
+    let ring = &bytes[HEADER..];
+    if capacity == 0 || ring.len() < capacity {
+        return Err(invalid("crash ring cut short"));
+    }
+    if written <= capacity as u64 {
+        return Ok(String::from_utf8_lossy(&ring[..written as usize]).into_owned());
+    }
+    let start = (written % capacity as u64) as usize;
+    let mut out = ring[start..capacity].to_vec();
+    out.extend_from_slice(&ring[..start]);
+    // The oldest record is missing its start, or was being written over.
+    let whole = match out.iter().position(|&b| b == b'\n') {
+        Some(at) => &out[at + 1..],
+        None => &[],
+    };
+    Ok(String::from_utf8_lossy(whole).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::{Logger, LoggerOpts};
+
+    #[cfg(unix)]
+    #[test]
+    fn test_recover_keeps_the_last_whole_records() {
+        let path = std::env::temp_dir().join("llog_crash_test.ring");
+        let _ = fs::remove_file(previous(&path));
+        // What the run before left.
+        let mut before = vec![0u8; HEADER + 64];
+        write_header(&mut before, 64);
+        before[HEADER..HEADER + 7].copy_from_slice(b"before\n");
+        before[16..24].copy_from_slice(&7u64.to_le_bytes());
+        fs::write(&path, before).unwrap();
+
+        let opts = LoggerOpts::new()
+            .set_log_type("null")
+            .add_crash_ring(&path, 1, LogFormat::Text)
+            .deterministic();
+        let mut logger = Logger::new(opts.clone());
+        for i in 0..100 {
+            logger.info(format!("record {i}"));
+        }
+        // No flush or drop, as if the process had been killed.
+        let last = recover(&path).unwrap();
+        assert!(last.len() <= 1024);
+        assert!(last.starts_with("[LLOG]"));
+        assert!(last.ends_with("record 99\n"));
+        assert_eq!(last.lines().count(), last.matches("[LLOG]").count());
+        drop(logger);
+
+        assert_eq!(recover(previous(&path)).unwrap(), "before\n");
+
+        // A second ring at the same path in this run leaves the first
+        // where it is, rather than taking it for the run before's.
+        let mut logger = Logger::new(opts);
+        logger.info("again");
+        assert_eq!(recover(previous(&path)).unwrap(), "before\n");
+        assert!(recover(&path).unwrap().ends_with("again\n"));
+        drop(logger);
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_file(previous(&path));
+    }
+
+    #[test]
+    fn test_memory_ring_is_found_through_the_symbol() {
+        let opts = LoggerOpts::new()
+            .set_log_type("null")
+            .add_memory_ring(4, LogFormat::Text);
+        let mut logger = Logger::new(opts);
+        logger.warn("in the dump");
+
+        let locator = &LLOG_MEMORY_RING;
+        assert_eq!(&locator.magic, b"LLRINGAT");
+        let at = locator.ring.load(Ordering::Acquire);
+        let len = locator.len.load(Ordering::Acquire);
+        assert_eq!(len, HEADER + 4096);
+        // What a debugger does with the two words.
+        let dumped = unsafe { std::slice::from_raw_parts(at, len) }.to_vec();
+        assert!(recover_bytes(&dumped).unwrap().ends_with("in the dump\n"));
+
+        // A Logger that comes and goes leaves the symbol alone.
+        let opts = LoggerOpts::new()
+            .set_log_type("null")
+            .add_memory_ring(8, LogFormat::Text);
+        let mut brief = Logger::new(opts);
+        brief.warn("not in the dump");
+        assert_eq!(locator.ring.load(Ordering::Acquire), at);
+        assert_eq!(locator.len.load(Ordering::Acquire), len);
+        drop(brief);
+        assert_eq!(locator.ring.load(Ordering::Acquire), at);
+
+        drop(logger);
+        assert!(locator.ring.load(Ordering::Acquire).is_null());
+    }
+}
@@ -0,0 +1,77 @@
+//! Long error details kept out of the main log, see
+//! LoggerOpts::set_error_detail_file.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use super::ulid::UlidGenerator;
+
+/// Errors longer than this, or over more than one line, go to the detail
+/// file.
+const LONG: usize = 160;
+
+#[derive(Debug)]
+pub(crate) struct ErrorDetail {
+    path: PathBuf,
+    /// Opened on the first long error, so runs without one leave no file.
+    file: Option<File>,
+    ids: UlidGenerator,
+}
+
+impl ErrorDetail {
+    pub(crate) fn new(path: PathBuf) -> ErrorDetail {
+        ErrorDetail {
+            path,
+            file: None,
+            ids: UlidGenerator::default(),
+        }
+    }
+
+    pub(crate) fn is_long(err: &str) -> bool {
+        err.len() > LONG || err.trim_end().contains('\n')
+    }
+
+    /// The first line of `err`, cut down to fit on the main log line.
+    pub(crate) fn summary(err: &str) -> &str {
+        let first = err.lines().next().unwrap_or_default();
+        match first.char_indices().nth(LONG) {
+            Some((cut, _)) => &first[..cut],
+            None => first,
+        }
+    }
+
+    /// Write the whole of `err` under a fresh id, which the main log line
+    /// carries as its `detail` field.
+    pub(crate) fn write(&mut self, heading: &str, err: &str) -> io::Result<String> {
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)?;
+                self.file.insert(file)
+            }
+        };
+        let id = self.ids.next();
+        // One write, so entries from two processes sharing the file don't
+        // interleave.
+        file.write_all(format!("--- {id} {heading}\n{}\n\n", err.trim_end()).as_bytes())?;
+        Ok(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_is_the_first_line() {
+        let trace = "connection reset\n   0: app::db::query\n   1: app::main";
+        assert!(ErrorDetail::is_long(trace));
+        assert_eq!(ErrorDetail::summary(trace), "connection reset");
+        assert!(!ErrorDetail::is_long("connection reset\n"));
+        assert_eq!(ErrorDetail::summary(&"é".repeat(200)).chars().count(), LONG);
+    }
+}
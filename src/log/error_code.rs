@@ -0,0 +1,106 @@
+use std::error::Error;
+use std::fmt;
+
+/// The error codes an application logs with Logger::error_code and what
+/// each one means, so a dashboard keyed by code never counts two
+/// different failures as one.
+///
+/// ```
+/// use little_logger::log::ErrorCodes;
+///
+/// const CODES: &[(&str, &str)] = &[
+///     ("E1042", "database timed out"),
+///     ("E1043", "database refused the login"),
+/// ];
+///
+/// let codes = ErrorCodes::from_table(CODES).expect("error codes are unique");
+/// assert_eq!(codes.description("E1042"), Some("database timed out"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ErrorCodes {
+    codes: Vec<(String, String)>,
+}
+
+impl ErrorCodes {
+    pub fn new() -> ErrorCodes {
+        ErrorCodes::default()
+    }
+
+    /// A registry of every `(code, description)` in `table`, or the first
+    /// code that is in it twice.
+    pub fn from_table(table: &[(&str, &str)]) -> Result<ErrorCodes, DuplicateCode> {
+        let mut codes = ErrorCodes::new();
+        for (code, description) in table {
+            codes.register(code, description)?;
+        }
+        Ok(codes)
+    }
+
+    /// Add `code`, failing if it is already taken.
+    pub fn register(&mut self, code: &str, description: &str) -> Result<(), DuplicateCode> {
+        if self.contains(code) {
+            return Err(DuplicateCode {
+                code: code.to_string(),
+            });
+        }
+        self.codes.push((code.to_string(), description.to_string()));
+        Ok(())
+    }
+
+    pub fn contains(&self, code: &str) -> bool {
+        self.description(code).is_some()
+    }
+
+    pub fn description(&self, code: &str) -> Option<&str> {
+        self.codes
+            .iter()
+            .find(|(c, _)| c == code)
+            .map(|(_, description)| description.as_str())
+    }
+
+    /// Every code and its description, in the order they were registered.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.codes.iter().map(|(c, d)| (c.as_str(), d.as_str()))
+    }
+}
+
+/// A code given to ErrorCodes a second time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateCode {
+    code: String,
+}
+
+impl DuplicateCode {
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+}
+
+impl fmt::Display for DuplicateCode {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "error code {:?} is registered twice", self.code)
+    }
+}
+
+impl Error for DuplicateCode {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duplicate_code_is_rejected() {
+        let table = [
+            ("E1042", "database timed out"),
+            ("E1043", "login refused"),
+            ("E1042", "query timed out"),
+        ];
+        let err = ErrorCodes::from_table(&table).unwrap_err();
+        assert_eq!(err.code(), "E1042");
+        assert_eq!(err.to_string(), "error code \"E1042\" is registered twice");
+
+        let codes = ErrorCodes::from_table(&table[..2]).unwrap();
+        assert_eq!(codes.iter().count(), 2);
+        assert!(!codes.contains("E9999"));
+    }
+}
@@ -0,0 +1,127 @@
+//! Convert finished logs into formats other tools can query.
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use arrow_array::builder::StringBuilder;
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Schema};
+use parquet::arrow::ArrowWriter;
+
+use super::reader::{self, Entry};
+
+/// How many records go into one Parquet row group.
+const ROW_GROUP_SIZE: usize = 8192;
+
+/// The columns every record is split into, and whether they can be missing.
+const COLUMNS: [(&str, bool); 9] = [
+    ("label", false),
+    ("time", false),
+    ("level", false),
+    ("target", true),
+    ("message", false),
+    ("error", true),
+    ("error.kind", true),
+    ("error.code", true),
+    ("template", true),
+];
+
+/// Convert a LogFormat::Json, MsgPack or Cbor log into a Parquet file.
+///
+/// The source format is found the same way reader::open finds it. Records are written
+/// in row groups of 8192 with one column per field, so the result can be
+/// queried with DuckDB, Spark and friends. Returns the number of records.
+///
+/// Only available with the `arrow` feature.
+///
+/// ```no_run
+/// use little_logger::log::export::convert_to_parquet;
+///
+/// let rows = convert_to_parquet("llog.txt", "llog.parquet").unwrap();
+/// ```
+pub fn convert_to_parquet<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q) -> io::Result<usize> {
+    let entries = reader::open(src)?;
+
+    let schema = Arc::new(Schema::new(
+        COLUMNS
+            .iter()
+            .map(|(name, nullable)| Field::new(*name, DataType::Utf8, *nullable))
+            .collect::<Vec<_>>(),
+    ));
+    let mut writer =
+        ArrowWriter::try_new(File::create(dst)?, schema.clone(), None).map_err(io::Error::other)?;
+
+    let mut rows = 0;
+    let mut batch: Vec<Entry> = Vec::with_capacity(ROW_GROUP_SIZE);
+    for entry in entries {
+        batch.push(entry?);
+        if batch.len() == ROW_GROUP_SIZE {
+            rows += write_batch(&mut writer, &schema, &mut batch)?;
+        }
+    }
+    rows += write_batch(&mut writer, &schema, &mut batch)?;
+    writer.close().map_err(io::Error::other)?;
+    Ok(rows)
+}
+
+fn write_batch(
+    writer: &mut ArrowWriter<File>,
+    schema: &Arc<Schema>,
+    batch: &mut Vec<Entry>,
+) -> io::Result<usize> {
+    if batch.is_empty() {
+        return Ok(0);
+    }
+    let columns: Vec<ArrayRef> = COLUMNS
+        .iter()
+        .map(|(name, nullable)| {
+            let mut column = StringBuilder::new();
+            for entry in batch.iter() {
+                match entry.get(name) {
+                    Some(value) => column.append_value(value),
+                    None if *nullable => column.append_null(),
+                    None => column.append_value(""),
+                }
+            }
+            Arc::new(column.finish()) as ArrayRef
+        })
+        .collect();
+    let record_batch = RecordBatch::try_new(schema.clone(), columns).map_err(io::Error::other)?;
+    writer.write(&record_batch).map_err(io::Error::other)?;
+    writer.flush().map_err(io::Error::other)?;
+    let rows = batch.len();
+    batch.clear();
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    #[test]
+    fn test_json_log_converts_to_parquet() {
+        let src = std::env::temp_dir().join("llog_export_test.txt");
+        let dst = std::env::temp_dir().join("llog_export_test.parquet");
+        std::fs::write(
+            &src,
+            "{\"label\":\"LLOG\",\"time\":\"t\",\"level\":\"INFO\",\"message\":\"a\"}\n\
+             {\"label\":\"LLOG\",\"time\":\"t\",\"level\":\"ERROR\",\"message\":\"b\",\"error\":\"e\"}\n",
+        )
+        .unwrap();
+
+        assert_eq!(convert_to_parquet(&src, &dst).unwrap(), 2);
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&dst).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let batch = reader.map(Result::unwrap).next().unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(batch.column(5).null_count(), 1);
+        assert_eq!(batch.column(6).null_count(), 2);
+        let _ = std::fs::remove_file(&src);
+        let _ = std::fs::remove_file(&dst);
+    }
+}
@@ -0,0 +1,52 @@
+use std::fmt;
+
+use super::{Level, Logger};
+
+/// Log the error side of a Result right where it is handled.
+///
+/// ```no_run
+/// use little_logger::log::{LogResultExt, Logger};
+///
+/// let mut logger = Logger::default();
+/// let port: u16 = "80a"
+///     .parse()
+///     .log_warn(&mut logger, "bad port, using the default")
+///     .unwrap_or(8080);
+/// let retries: u8 = "x".parse().log_error_and_default(&mut logger, "reading retries");
+/// ```
+pub trait LogResultExt<T, E> {
+    /// Log the error at `level` and give the Result back.
+    fn log_at(self, logger: &mut Logger, level: Level, context: &str) -> Result<T, E>;
+
+    /// Log the error at Level::Warn and give the Result back.
+    fn log_warn(self, logger: &mut Logger, context: &str) -> Result<T, E>;
+
+    /// Log the error at Level::Error and give the Result back.
+    fn log_error(self, logger: &mut Logger, context: &str) -> Result<T, E>;
+
+    /// Log the error at Level::Error and fall back to `T::default()`.
+    fn log_error_and_default(self, logger: &mut Logger, context: &str) -> T
+    where
+        T: Default;
+}
+
+impl<T, E: fmt::Display> LogResultExt<T, E> for Result<T, E> {
+    fn log_at(self, logger: &mut Logger, level: Level, context: &str) -> Result<T, E> {
+        logger.log_err(self, level, context)
+    }
+
+    fn log_warn(self, logger: &mut Logger, context: &str) -> Result<T, E> {
+        self.log_at(logger, Level::Warn, context)
+    }
+
+    fn log_error(self, logger: &mut Logger, context: &str) -> Result<T, E> {
+        self.log_at(logger, Level::Error, context)
+    }
+
+    fn log_error_and_default(self, logger: &mut Logger, context: &str) -> T
+    where
+        T: Default,
+    {
+        self.log_error(logger, context).unwrap_or_default()
+    }
+}
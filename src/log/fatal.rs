@@ -0,0 +1,184 @@
+//! Logging an unrecoverable error and aborting, without going through a
+//! Logger whose state may be what broke, see llog_fatal!.
+//!
+//! The line is formatted into a buffer set aside when the program starts
+//! and written with plain `write` calls: to stderr, and to the log file
+//! the last Logger opened, through a descriptor duplicated when it opened
+//! it. No lock is taken and nothing is allocated by the crate, though a
+//! Display impl in the message may still allocate.
+
+use std::cell::UnsafeCell;
+use std::fmt::{self, Write as _};
+#[cfg(unix)]
+use std::sync::atomic::AtomicI32;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Longer lines are cut short.
+const LEN: usize = 4096;
+
+struct Buf(UnsafeCell<[u8; LEN]>);
+
+// SAFETY: only the thread that set TAKEN touches the buffer.
+unsafe impl Sync for Buf {}
+
+static BUF: Buf = Buf(UnsafeCell::new([0; LEN]));
+/// Set by the first thread to call fatal, which has BUF to itself.
+static TAKEN: AtomicBool = AtomicBool::new(false);
+/// A duplicate of the descriptor of the log file opened last, or -1.
+#[cfg(unix)]
+static LOG_FD: AtomicI32 = AtomicI32::new(-1);
+
+/// Make `file` the log file a fatal error is written to.
+///
+/// The descriptor is duplicated, so it stays good after the Logger closes
+/// or rotates the file, and the duplicate of the file before is closed.
+#[cfg(unix)]
+pub(crate) fn set_log_file(file: &std::fs::File) {
+    use std::os::fd::AsRawFd;
+
+    // SAFETY: dup and close only touch the descriptor table.
+    unsafe {
+        let fd = libc::dup(file.as_raw_fd());
+        if fd < 0 {
+            return;
+        }
+        let old = LOG_FD.swap(fd, Ordering::AcqRel);
+        if old >= 0 {
+            libc::close(old);
+        }
+    }
+}
+
+/// Fills a byte buffer, dropping whatever doesn't fit.
+struct Cursor<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl fmt::Write for Cursor<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let n = s.len().min(self.buf.len() - self.len);
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        Ok(())
+    }
+}
+
+/// The fatal line in `buf`, newline included. Returns its length.
+fn render(buf: &mut [u8], target: &str, file: &str, line: u32, args: fmt::Arguments<'_>) -> usize {
+    let end = buf.len() - 1;
+    let mut out = Cursor {
+        buf: &mut buf[..end],
+        len: 0,
+    };
+    let _ = write!(out, "[LLOG] [FATAL] [{target}]: {args} ({file}:{line})");
+    let len = out.len;
+    buf[len] = b'\n';
+    len + 1
+}
+
+#[cfg(unix)]
+fn write_all(fd: libc::c_int, mut bytes: &[u8]) {
+    while !bytes.is_empty() {
+        // SAFETY: writing from a live slice.
+        let n = unsafe { libc::write(fd, bytes.as_ptr().cast(), bytes.len()) };
+        if n < 0 {
+            if std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted {
+                continue;
+            }
+            return;
+        }
+        bytes = &bytes[n as usize..];
+    }
+}
+
+/// What llog_fatal! expands to: write the line to stderr and the log
+/// file, sync the file and abort.
+///
+/// Records the Logger still has buffered are lost, the fatal line is the
+/// last thing the file is sure to hold.
+#[cold]
+pub fn fatal(target: &str, file: &str, line: u32, args: fmt::Arguments<'_>) -> ! {
+    if TAKEN.swap(true, Ordering::AcqRel) {
+        // Another thread is writing its own fatal line and will abort
+        // when done, or this one failed while formatting its message.
+        if is_owner() {
+            std::process::abort();
+        }
+        loop {
+            std::thread::park();
+        }
+    }
+    set_owner();
+    // SAFETY: TAKEN makes this the only thread to get here.
+    let buf = unsafe { &mut *BUF.0.get() };
+    let len = render(buf, target, file, line, args);
+    let bytes = &buf[..len];
+    #[cfg(unix)]
+    {
+        write_all(libc::STDERR_FILENO, bytes);
+        let fd = LOG_FD.load(Ordering::Acquire);
+        if fd >= 0 {
+            write_all(fd, bytes);
+            // SAFETY: fsync on a descriptor this module owns.
+            unsafe {
+                libc::fsync(fd);
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        use std::io::Write;
+        let _ = std::io::stderr().write_all(bytes);
+    }
+    std::process::abort()
+}
+
+/// The thread that set TAKEN.
+#[cfg(unix)]
+static OWNER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+#[cfg(unix)]
+fn set_owner() {
+    // SAFETY: pthread_self can't fail.
+    OWNER.store(unsafe { libc::pthread_self() } as usize, Ordering::Release);
+}
+
+#[cfg(unix)]
+fn is_owner() -> bool {
+    // SAFETY: pthread_self can't fail.
+    OWNER.load(Ordering::Acquire) == unsafe { libc::pthread_self() } as usize
+}
+
+#[cfg(not(unix))]
+fn set_owner() {}
+
+#[cfg(not(unix))]
+fn is_owner() -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_cuts_long_lines() {
+        let mut buf = [0u8; 64];
+        let len = render(
+            &mut buf,
+            "app::db",
+            "src/db.rs",
+            12,
+            format_args!("{} rows", 3),
+        );
+        assert_eq!(
+            &buf[..len],
+            b"[LLOG] [FATAL] [app::db]: 3 rows (src/db.rs:12)\n"
+        );
+
+        let mut buf = [0u8; 16];
+        let len = render(&mut buf, "app", "src/main.rs", 1, format_args!("oops"));
+        assert_eq!(&buf[..len], b"[LLOG] [FATAL] \n");
+    }
+}
@@ -0,0 +1,111 @@
+use super::Level;
+
+/// Decides which lines are written, per target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub(crate) struct Filter {
+    /// The level used when no target override matches.
+    pub(crate) default: Level,
+    /// Target prefixes and their levels.
+    pub(crate) targets: Vec<(String, Level)>,
+    /// When set, only these labels get through.
+    pub(crate) label_allow: Option<Vec<String>>,
+    /// These labels never get through.
+    pub(crate) label_deny: Vec<String>,
+}
+
+impl Default for Filter {
+    fn default() -> Filter {
+        Filter {
+            default: Level::Trace,
+            targets: Vec::new(),
+            label_allow: None,
+            label_deny: Vec::new(),
+        }
+    }
+}
+
+impl Filter {
+    pub(crate) fn set(&mut self, target: &str, level: Level) {
+        match self.targets.iter_mut().find(|(t, _)| t == target) {
+            Some(entry) => entry.1 = level,
+            None => self.targets.push((target.to_string(), level)),
+        }
+    }
+
+    pub(crate) fn enabled(&self, level: Level, target: Option<&str>) -> bool {
+        level >= self.level_for(target)
+    }
+
+    /// Whether a line that isn't enabled is off only for being under the
+    /// default level, rather than by a target's own level.
+    pub(crate) fn below_default(&self, level: Level, target: Option<&str>) -> bool {
+        let targeted = target.is_some_and(|target| {
+            self.targets
+                .iter()
+                .any(|(prefix, _)| covers(prefix, target))
+        });
+        level < self.default && !targeted
+    }
+
+    /// Checked before anything is formatted.
+    pub(crate) fn label_enabled(&self, label: &str) -> bool {
+        let allowed = match &self.label_allow {
+            Some(allow) => allow.iter().any(|l| l == label),
+            None => true,
+        };
+        allowed && !self.label_deny.iter().any(|l| l == label)
+    }
+
+    fn level_for(&self, target: Option<&str>) -> Level {
+        let Some(target) = target else {
+            return self.default;
+        };
+        self.targets
+            .iter()
+            .filter(|(prefix, _)| covers(prefix, target))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(self.default, |(_, level)| *level)
+    }
+}
+
+/// `my_app::db` covers `my_app::db` and `my_app::db::pool`, not `my_app::dbx`.
+fn covers(prefix: &str, target: &str) -> bool {
+    match target.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with("::"),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_longest_target_wins() {
+        let mut filter = Filter {
+            default: Level::Warn,
+            ..Filter::default()
+        };
+        filter.set("my_app", Level::Info);
+        filter.set("my_app::db", Level::Trace);
+
+        assert!(filter.enabled(Level::Trace, Some("my_app::db::pool")));
+        assert!(!filter.enabled(Level::Debug, Some("my_app::net")));
+        assert!(!filter.enabled(Level::Info, Some("my_app_two")));
+        assert!(!filter.enabled(Level::Info, None));
+    }
+
+    #[test]
+    fn test_deny_beats_allow() {
+        let filter = Filter {
+            label_allow: Some(vec![String::from("AUDIT"), String::from("BILLING")]),
+            label_deny: vec![String::from("BILLING")],
+            ..Filter::default()
+        };
+        assert!(filter.label_enabled("AUDIT"));
+        assert!(!filter.label_enabled("BILLING"));
+        assert!(!filter.label_enabled("LLOG"));
+    }
+}
@@ -0,0 +1,61 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::Duration;
+
+/// A log file that a flush thread can reach as well as the Logger.
+pub(crate) type SharedFile = Arc<Mutex<BufWriter<File>>>;
+
+/// The file a LogFile is writing, shared with the LocalHandles writing into
+/// it so a rotation moves them all on to the next one. None until the first
+/// line, and again right after a rotation.
+pub(crate) type LiveFile = Arc<Mutex<Option<SharedFile>>>;
+
+/// Wrap `file` in a buffer of `capacity` bytes.
+///
+/// A capacity of zero writes straight through. Otherwise, with an
+/// `interval`, a thread flushes the buffer that often until the Logger
+/// that owns the file is gone.
+pub(crate) fn share(file: File, capacity: usize, interval: Option<Duration>) -> SharedFile {
+    let shared = Arc::new(Mutex::new(BufWriter::with_capacity(capacity, file)));
+    if let (true, Some(interval)) = (capacity > 0, interval) {
+        spawn_flusher(Arc::downgrade(&shared), interval);
+    }
+    #[cfg(all(unix, feature = "signals"))]
+    super::signals::track(&shared);
+    shared
+}
+
+fn spawn_flusher(file: Weak<Mutex<BufWriter<File>>>, interval: Duration) {
+    let spawned = thread::Builder::new()
+        .name(String::from("little_logger-flush"))
+        .spawn(move || loop {
+            thread::sleep(interval);
+            let Some(shared) = file.upgrade() else {
+                return;
+            };
+            let _ = shared.lock().map(|mut out| out.flush());
+        });
+    // Without the thread lines are still flushed when the buffer fills
+    // up and when the Logger is dropped.
+    drop(spawned);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flush_thread_empties_buffer() {
+        let path = std::env::temp_dir().join("llog_flush_test.txt");
+        let file = File::create(&path).unwrap();
+        let shared = share(file, 4096, Some(Duration::from_millis(10)));
+
+        shared.lock().unwrap().write_all(b"buffered\n").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "buffered\n");
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,692 @@
+use std::fmt::Write;
+
+use super::{cbor, msgpack, schema, Level, SeverityMap};
+
+/// How a sink lays out each log line.
+///
+/// Every sink carries its own format, so the console can stay easy on the
+/// eyes while the file gets something a machine can parse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum LogFormat {
+    /// The classic layout: `[LLOG]::[2024-05-21 18:37:22]::[INFO] -> message`
+    Text,
+    /// The classic layout with ANSI colors. Meant for a terminal.
+    Colored,
+    /// One JSON object per line.
+    Json,
+    /// Indented JSON objects spread over several lines. Meant for development.
+    JsonPretty,
+    /// Length-prefixed MessagePack maps. Small and quick to parse, read it
+    /// back with `reader::MsgPackReader`.
+    MsgPack,
+    /// CBOR maps tagged as self-describing. Read it back with
+    /// `reader::CborReader`.
+    Cbor,
+    /// One JSON object per line with the `severity`, `time` and `message`
+    /// keys Google Cloud Logging picks up from the stdout of GKE and Cloud
+    /// Run. Set a RFC 3339 dt_format so it parses the time too.
+    ///
+    /// Static fields become its labels, the macros fill in the source
+    /// location and a `trace` field is taken as the Cloud Trace id,
+    /// `projects/<project>/traces/<id>`.
+    Gcp,
+    /// One JSON object per line the Datadog agent parses without a custom
+    /// pipeline: `status`, `service` from the label, `timestamp`,
+    /// `logger.name` and `error.message`. `dd.trace_id` and `dd.span_id`
+    /// fields are passed along to connect the line to its trace.
+    Datadog,
+}
+
+/// Everything a format needs to know about a single log line.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Record<'r> {
+    pub(crate) level: Level,
+    /// The level as it should be displayed, already cased and padded.
+    pub(crate) level_name: &'r str,
+    /// The module the line was logged from, if it was given.
+    pub(crate) target: Option<&'r str>,
+    pub(crate) label: &'r str,
+    pub(crate) date_time: &'r str,
+    pub(crate) msg: &'r str,
+    pub(crate) err: Option<&'r str>,
+    /// The type of the error, when it came from a std::error::Error.
+    pub(crate) err_kind: Option<&'r str>,
+    /// The stable code the error was logged with, see Logger::error_code.
+    pub(crate) err_code: Option<&'r str>,
+    /// The message before its fields were filled in, see Logger::log_t.
+    pub(crate) template: Option<&'r str>,
+    /// Fields attached to this record only.
+    pub(crate) kv: &'r [(&'r str, &'r str)],
+    /// The kv keys holding numbers or bools, which JSON writes unquoted.
+    pub(crate) typed: &'r [&'r str],
+    /// Fields set once on the LoggerOpts and added to every record.
+    pub(crate) static_fields: &'r [(String, String)],
+    /// The innermost span entered when the line was logged.
+    pub(crate) span_id: Option<&'r str>,
+    /// How many spans deep the line is. Text formats indent by this much.
+    pub(crate) span_depth: usize,
+    /// A ULID shared by every sink the record goes to.
+    pub(crate) event_id: Option<&'r str>,
+    /// The same for every record from this run of the process.
+    pub(crate) session_id: Option<&'r str>,
+    /// Text formats write just the message, see Logger::log_raw.
+    pub(crate) raw: bool,
+    /// The old and new value of the setting named by the message, see
+    /// Logger::log_change.
+    pub(crate) change: Option<(&'r str, &'r str)>,
+    /// The file, line and function of the macro call that logged it.
+    pub(crate) source: Option<(&'r str, u32, &'r str)>,
+}
+
+impl<'r> Record<'r> {
+    /// The named fields the structured formats write, in order. Any new
+    /// one has to be added to the schema module.
+    pub(crate) fn fields(&self) -> Vec<(&'r str, &'r str)> {
+        let mut fields = vec![("schema", schema::VERSION)];
+        if let Some(event_id) = self.event_id {
+            fields.push(("event_id", event_id));
+        }
+        if let Some(session_id) = self.session_id {
+            fields.push(("session_id", session_id));
+        }
+        fields.extend([
+            ("label", self.label),
+            ("time", self.date_time),
+            ("level", self.level_name.trim_end()),
+        ]);
+        if let Some(target) = self.target {
+            fields.push(("target", target));
+        }
+        if let Some(span_id) = self.span_id {
+            fields.push(("span_id", span_id));
+        }
+        fields.push(("message", self.msg));
+        if let Some(template) = self.template {
+            fields.push(("template", template));
+        }
+        if let Some((old, new)) = self.change {
+            fields.extend([("old", old), ("new", new)]);
+        }
+        if let Some(err) = self.err {
+            fields.push(("error", err));
+        }
+        if let Some(kind) = self.err_kind {
+            fields.push(("error.kind", kind));
+        }
+        if let Some(code) = self.err_code {
+            fields.push(("error.code", code));
+        }
+        fields.extend_from_slice(self.kv);
+        for (key, value) in self.static_fields {
+            fields.push((key, value));
+        }
+        fields
+    }
+}
+
+/// The separators the text formats put between fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Layout {
+    /// Goes between the bracketed label, date/time and level.
+    pub(crate) label_sep: String,
+    /// Goes between the bracketed fields and the message.
+    pub(crate) msg_sep: String,
+    /// Replaces the level names of the formats for other systems.
+    pub(crate) severity: Option<SeverityMap>,
+}
+
+impl Default for Layout {
+    fn default() -> Layout {
+        Layout {
+            label_sep: String::from("::"),
+            msg_sep: String::from(" -> "),
+            severity: None,
+        }
+    }
+}
+
+const RESET: &str = "\x1b[0m";
+const BOLD_CYAN: &str = "\x1b[1;36m";
+const DIM: &str = "\x1b[2m";
+const RED: &str = "\x1b[31m";
+const YELLOW: &str = "\x1b[33m";
+const GREEN: &str = "\x1b[32m";
+const BLUE: &str = "\x1b[34m";
+
+fn level_color(level: Level) -> &'static str {
+    match level {
+        Level::Trace => DIM,
+        Level::Debug => BLUE,
+        Level::Info => GREEN,
+        Level::Warn => YELLOW,
+        Level::Error => RED,
+    }
+}
+
+impl LogFormat {
+    /// The name used for the format in file headers.
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            LogFormat::Text => "text",
+            LogFormat::Colored => "colored",
+            LogFormat::Json => "json",
+            LogFormat::JsonPretty => "json_pretty",
+            LogFormat::MsgPack => "msgpack",
+            LogFormat::Cbor => "cbor",
+            LogFormat::Gcp => "gcp",
+            LogFormat::Datadog => "datadog",
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<LogFormat> {
+        let format = match name {
+            "text" => LogFormat::Text,
+            "colored" => LogFormat::Colored,
+            "json" => LogFormat::Json,
+            "json_pretty" => LogFormat::JsonPretty,
+            "msgpack" => LogFormat::MsgPack,
+            "cbor" => LogFormat::Cbor,
+            "gcp" => LogFormat::Gcp,
+            "datadog" => LogFormat::Datadog,
+            _ => return None,
+        };
+        Some(format)
+    }
+
+    /// Whether lines in this format can be treated as text.
+    pub(crate) fn is_binary(&self) -> bool {
+        matches!(self, LogFormat::MsgPack | LogFormat::Cbor)
+    }
+
+    /// The bytes a sink writes for `record`.
+    pub(crate) fn encode(&self, record: &Record, layout: &Layout) -> Vec<u8> {
+        match self {
+            LogFormat::MsgPack => msgpack::encode(record),
+            LogFormat::Cbor => cbor::encode(record),
+            _ => self.render(record, layout).into_bytes(),
+        }
+    }
+
+    pub(crate) fn render(&self, record: &Record, layout: &Layout) -> String {
+        if record.raw && matches!(self, LogFormat::Text | LogFormat::Colored) {
+            return format!("{}\n", record.msg);
+        }
+        match self {
+            LogFormat::Text => render_text(record, layout),
+            LogFormat::Colored => render_colored(record, layout),
+            LogFormat::Json => render_json(record, false),
+            LogFormat::JsonPretty => render_json(record, true),
+            LogFormat::Gcp => render_gcp(record, layout),
+            LogFormat::Datadog => render_datadog(record, layout),
+            // Binary formats have no text form, JSON carries the same fields.
+            LogFormat::MsgPack | LogFormat::Cbor => render_json(record, false),
+        }
+    }
+}
+
+fn render_text(record: &Record, layout: &Layout) -> String {
+    let Layout {
+        label_sep, msg_sep, ..
+    } = layout;
+    let mut line = format!(
+        "[{}]{label_sep}[{}]{label_sep}[{}]",
+        record.label, record.date_time, record.level_name
+    );
+    if let Some(target) = record.target {
+        let _ = write!(line, "{label_sep}[{target}]");
+    }
+    let _ = write!(line, "{msg_sep}{}{}", indent(record), record.msg);
+    if let Some((old, new)) = record.change {
+        let _ = write!(line, ": {old} -> {new}");
+    }
+    if let Some(code) = record.err_code {
+        push_kv(&mut line, &[("code", code)]);
+    }
+    // A template's fields are in the message already.
+    if record.template.is_none() {
+        push_kv(&mut line, record.kv);
+    }
+    if let Some(event_id) = record.event_id {
+        push_kv(&mut line, &[("event_id", event_id)]);
+    }
+    if let Some(session_id) = record.session_id {
+        push_kv(&mut line, &[("session", session_id)]);
+    }
+    line.push('\n');
+    if let Some(err) = record.err {
+        line.push_str(err);
+        line.push('\n');
+    }
+    line
+}
+
+fn render_colored(record: &Record, layout: &Layout) -> String {
+    let Layout {
+        label_sep, msg_sep, ..
+    } = layout;
+    let mut line = format!(
+        "{BOLD_CYAN}[{}]{RESET}{label_sep}{DIM}[{}]{RESET}{label_sep}{}[{}]{RESET}",
+        record.label,
+        record.date_time,
+        level_color(record.level),
+        record.level_name,
+    );
+    if let Some(target) = record.target {
+        let _ = write!(line, "{label_sep}{DIM}[{target}]{RESET}");
+    }
+    let _ = write!(line, "{msg_sep}{}{}", indent(record), record.msg);
+    if let Some((old, new)) = record.change {
+        let _ = write!(line, ": {RED}{old}{RESET} -> {GREEN}{new}{RESET}");
+    }
+    if let Some(code) = record.err_code {
+        push_kv(&mut line, &[("code", code)]);
+    }
+    if record.template.is_none() {
+        push_kv(&mut line, record.kv);
+    }
+    if let Some(event_id) = record.event_id {
+        push_kv(&mut line, &[("event_id", event_id)]);
+    }
+    if let Some(session_id) = record.session_id {
+        push_kv(&mut line, &[("session", session_id)]);
+    }
+    line.push('\n');
+    if let Some(err) = record.err {
+        let _ = writeln!(line, "{RED}{err}{RESET}");
+    }
+    line
+}
+
+/// Two spaces for every span the record is nested in.
+fn indent(record: &Record) -> String {
+    "  ".repeat(record.span_depth)
+}
+
+/// Replace each `{key}` in the template with the value of that field.
+/// `{{` and `}}` stand for plain braces, unknown keys are left as they are.
+pub(crate) fn fill_template(template: &str, kv: &[(&str, &str)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(at) = rest.find(['{', '}']) {
+        out.push_str(&rest[..at]);
+        rest = &rest[at..];
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            out.push_str(&rest[..1]);
+            rest = &rest[2..];
+            continue;
+        }
+        let value = rest[1..].find('}').and_then(|end| {
+            let key = &rest[1..end + 1];
+            let (_, value) = kv.iter().find(|(k, _)| *k == key)?;
+            Some((value, end + 2))
+        });
+        match value {
+            Some((value, len)) => {
+                out.push_str(value);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push_str(&rest[..1]);
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Append ` key=value` for each field, quoting values that need it.
+pub(crate) fn push_kv(out: &mut String, kv: &[(&str, &str)]) {
+    for (key, value) in kv {
+        let _ = write!(out, " {key}=");
+        let plain = !value.is_empty()
+            && !value
+                .chars()
+                .any(|c| c.is_whitespace() || c.is_control() || c == '"' || c == '=');
+        if plain {
+            out.push_str(value);
+        } else {
+            push_json_str(out, value);
+        }
+    }
+}
+
+fn render_json(record: &Record, pretty: bool) -> String {
+    let mut obj = JsonObject::new(pretty);
+    for (key, value) in record.fields() {
+        obj.value(record, key, value);
+    }
+    obj.finish()
+}
+
+/// The JSON fields renamed to the ones Cloud Logging knows, `level` going
+/// into `severity`.
+fn render_gcp(record: &Record, layout: &Layout) -> String {
+    const GCP: &str = "logging.googleapis.com/";
+    let mut obj = JsonObject::new(false);
+    let severity = layout.severity.clone().unwrap_or_else(SeverityMap::gcp);
+    obj.field("severity", severity.get(record.level));
+    obj.field("time", record.date_time);
+    obj.field("message", record.msg);
+    if !record.static_fields.is_empty() {
+        let mut labels = JsonObject::new(false);
+        for (key, value) in record.static_fields {
+            labels.field(key, value);
+        }
+        obj.object(&format!("{GCP}labels"), labels);
+    }
+    if let Some((file, line, function)) = record.source {
+        let mut location = JsonObject::new(false);
+        location.field("file", file);
+        location.field("line", &line.to_string());
+        location.field("function", function);
+        obj.object(&format!("{GCP}sourceLocation"), location);
+    }
+    let statics = record.static_fields.len();
+    let fields = record.fields();
+    for (key, value) in &fields[..fields.len() - statics] {
+        match *key {
+            "level" | "time" | "message" => {}
+            "trace" => obj.field(&format!("{GCP}trace"), value),
+            _ => obj.value(record, key, value),
+        }
+    }
+    obj.finish()
+}
+
+/// The JSON fields renamed to Datadog's reserved and standard attributes.
+fn render_datadog(record: &Record, layout: &Layout) -> String {
+    let mut obj = JsonObject::new(false);
+    let status = layout.severity.clone().unwrap_or_else(SeverityMap::datadog);
+    for (key, value) in record.fields() {
+        let key = match key {
+            "level" => {
+                obj.field("status", status.get(record.level));
+                continue;
+            }
+            "label" => "service",
+            "time" => "timestamp",
+            "target" => "logger.name",
+            "error" => "error.message",
+            key => {
+                obj.value(record, key, value);
+                continue;
+            }
+        };
+        obj.field(key, value);
+    }
+    obj.finish()
+}
+
+/// Builds one JSON object, either on a single line or indented.
+struct JsonObject {
+    out: String,
+    pretty: bool,
+    empty: bool,
+}
+
+impl JsonObject {
+    fn new(pretty: bool) -> JsonObject {
+        JsonObject {
+            out: String::from("{"),
+            pretty,
+            empty: true,
+        }
+    }
+
+    fn field(&mut self, key: &str, value: &str) {
+        self.key(key);
+        push_json_str(&mut self.out, value);
+    }
+
+    /// A field of the record, unquoted when it was logged as a number or
+    /// bool.
+    fn value(&mut self, record: &Record, key: &str, value: &str) {
+        if record.typed.contains(&key) {
+            self.key(key);
+            self.out.push_str(value);
+        } else {
+            self.field(key, value);
+        }
+    }
+
+    /// Nest `inner` under `key`. Only single line objects nest.
+    fn object(&mut self, key: &str, inner: JsonObject) {
+        self.key(key);
+        self.out.push_str(inner.finish().trim_end());
+    }
+
+    fn key(&mut self, key: &str) {
+        if !self.empty {
+            self.out.push(',');
+        }
+        if self.pretty {
+            self.out.push_str("\n  ");
+        }
+        push_json_str(&mut self.out, key);
+        self.out.push_str(if self.pretty { ": " } else { ":" });
+        self.empty = false;
+    }
+
+    fn finish(mut self) -> String {
+        if self.pretty && !self.empty {
+            self.out.push('\n');
+        }
+        self.out.push_str("}\n");
+        self.out
+    }
+}
+
+/// Write `value` as a quoted JSON string.
+pub(crate) fn push_json_str(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An INFO record with nothing else set, for the tests to fill in.
+    fn record() -> Record<'static> {
+        Record {
+            level: Level::Info,
+            level_name: "INFO",
+            target: None,
+            label: "LLOG",
+            date_time: "18:37:22",
+            msg: "",
+            err: None,
+            err_kind: None,
+            err_code: None,
+            template: None,
+            kv: &[],
+            typed: &[],
+            static_fields: &[],
+            span_id: None,
+            span_depth: 0,
+            event_id: None,
+            session_id: None,
+            raw: false,
+            change: None,
+            source: None,
+        }
+    }
+
+    #[test]
+    fn test_json_escapes_message() {
+        let record = Record {
+            level_name: "INFO ",
+            date_time: "2024-05-21 18:37:22",
+            msg: "say \"hi\"\n",
+            ..record()
+        };
+        assert_eq!(
+            LogFormat::Json.render(&record, &Layout::default()),
+            "{\"schema\":\"1\",\"label\":\"LLOG\",\"time\":\"2024-05-21 18:37:22\",\"level\":\"INFO\",\"message\":\"say \\\"hi\\\"\\n\"}\n"
+        );
+    }
+
+    #[test]
+    fn test_json_pretty_indents_fields() {
+        let record = Record {
+            level: Level::Error,
+            level_name: "ERROR",
+            msg: "boom",
+            ..record()
+        };
+        assert_eq!(
+            LogFormat::JsonPretty.render(&record, &Layout::default()),
+            "{\n  \"schema\": \"1\",\n  \"label\": \"LLOG\",\n  \"time\": \"18:37:22\",\n  \"level\": \"ERROR\",\n  \"message\": \"boom\"\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_json_appends_static_fields() {
+        let static_fields = [(String::from("service"), String::from("payments"))];
+        let record = Record {
+            msg: "paid",
+            static_fields: &static_fields,
+            ..record()
+        };
+        assert!(LogFormat::Json
+            .render(&record, &Layout::default())
+            .ends_with("\"message\":\"paid\",\"service\":\"payments\"}\n"));
+        assert!(!LogFormat::Text
+            .render(&record, &Layout::default())
+            .contains("payments"));
+    }
+
+    #[test]
+    fn test_gcp_maps_level_to_severity() {
+        let static_fields = [(String::from("service"), String::from("api"))];
+        let record = Record {
+            level: Level::Warn,
+            level_name: "WARN",
+            date_time: "2024-05-21T18:37:22+00:00",
+            msg: "slow",
+            kv: &[("ms", "950"), ("trace", "projects/p/traces/abc")],
+            static_fields: &static_fields,
+            source: Some(("src/main.rs", 7, "app::main")),
+            ..record()
+        };
+        assert_eq!(
+            LogFormat::Gcp.render(&record, &Layout::default()),
+            concat!(
+                r#"{"severity":"WARNING","time":"2024-05-21T18:37:22+00:00","message":"slow","#,
+                r#""logging.googleapis.com/labels":{"service":"api"},"#,
+                r#""logging.googleapis.com/sourceLocation":"#,
+                r#"{"file":"src/main.rs","line":"7","function":"app::main"},"#,
+                r#""schema":"1","label":"LLOG","ms":"950","#,
+                r#""logging.googleapis.com/trace":"projects/p/traces/abc"}"#,
+                "\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_datadog_renames_reserved_fields() {
+        let record = Record {
+            level: Level::Error,
+            level_name: "ERROR",
+            target: Some("api::pay"),
+            label: "billing",
+            date_time: "2024-05-21T18:37:22Z",
+            msg: "charge failed",
+            err: Some("card declined"),
+            kv: &[("dd.trace_id", "4711"), ("dd.span_id", "42")],
+            ..record()
+        };
+        assert_eq!(
+            LogFormat::Datadog.render(&record, &Layout::default()),
+            concat!(
+                r#"{"schema":"1","service":"billing","timestamp":"2024-05-21T18:37:22Z","#,
+                r#""status":"error","logger.name":"api::pay","message":"charge failed","#,
+                r#""error.message":"card declined","dd.trace_id":"4711","dd.span_id":"42"}"#,
+                "\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_text_appends_quoted_fields() {
+        let record = Record {
+            msg: "login",
+            kv: &[("user", "bob"), ("agent", "curl \"8\""), ("empty", "")],
+            ..record()
+        };
+        assert_eq!(
+            LogFormat::Text.render(&record, &Layout::default()),
+            "[LLOG]::[18:37:22]::[INFO] -> login user=bob agent=\"curl \\\"8\\\"\" empty=\"\"\n"
+        );
+    }
+
+    #[test]
+    fn test_fill_template() {
+        let kv = [("user", "bob"), ("item", "sku-7")];
+        assert_eq!(
+            fill_template("{user} bought {item}", &kv),
+            "bob bought sku-7"
+        );
+        assert_eq!(fill_template("{{user}} {who} {", &kv), "{user} {who} {");
+    }
+
+    #[test]
+    fn test_json_keeps_the_template() {
+        let record = Record {
+            msg: "bob bought sku-7",
+            template: Some("{user} bought {item}"),
+            kv: &[("user", "bob"), ("item", "sku-7")],
+            ..record()
+        };
+        assert_eq!(
+            LogFormat::Json.render(&record, &Layout::default()),
+            concat!(
+                r#"{"schema":"1","label":"LLOG","time":"18:37:22","level":"INFO","#,
+                r#""message":"bob bought sku-7","template":"{user} bought {item}","#,
+                r#""user":"bob","item":"sku-7"}"#,
+                "\n"
+            )
+        );
+        assert_eq!(
+            LogFormat::Text.render(&record, &Layout::default()),
+            "[LLOG]::[18:37:22]::[INFO] -> bob bought sku-7\n"
+        );
+    }
+
+    #[test]
+    fn test_text_uses_custom_separators() {
+        let record = Record {
+            level: Level::Warn,
+            level_name: "WARN",
+            label: "APP",
+            msg: "low disk",
+            ..record()
+        };
+        let layout = Layout {
+            label_sep: String::from(" "),
+            msg_sep: String::from(": "),
+            severity: None,
+        };
+        assert_eq!(
+            LogFormat::Text.render(&record, &layout),
+            "[APP] [18:37:22] [WARN]: low disk\n"
+        );
+    }
+}
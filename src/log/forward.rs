@@ -0,0 +1,78 @@
+//! Logging the lines of other programs and streams.
+
+use std::io::{self, BufRead, BufReader, Read};
+use std::thread::{self, JoinHandle};
+
+use super::{Level, LocalHandle, Logger};
+
+/// Log every line from `src` until it ends, with `kv` on each.
+///
+/// Bytes that aren't UTF-8 are shown as set_invalid_utf8 says.
+pub(crate) fn log_lines<R: BufRead>(
+    mut src: R,
+    logger: &mut Logger,
+    level: Level,
+    kv: &[(&str, &str)],
+) -> io::Result<usize> {
+    let mut line = Vec::new();
+    let mut count = 0;
+    loop {
+        line.clear();
+        if src.read_until(b'\n', &mut line)? == 0 {
+            return Ok(count);
+        }
+        let text = logger.opts.invalid_utf8.decode(&line);
+        logger.log_kv(level, text.trim_end_matches(['\r', '\n']), kv);
+        count += 1;
+    }
+}
+
+/// Log the lines of a child's pipe from a thread of its own.
+pub(crate) fn spawn_reader<R: Read + Send + 'static>(
+    mut handle: LocalHandle,
+    pipe: R,
+    level: Level,
+    stream: &'static str,
+    pid: String,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let kv = [("stream", stream), ("pid", pid.as_str())];
+        let _ = log_lines(BufReader::new(pipe), &mut handle, level, &kv);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::testing::Capture;
+    use crate::log::LoggerOpts;
+    use std::process::{Command, Stdio};
+
+    #[cfg(unix)]
+    #[test]
+    fn test_forward_child_tags_streams() {
+        let capture = Capture::new();
+        let opts = LoggerOpts::new().set_log_type("null").set_capture(&capture);
+        let mut logger = Logger::new(opts);
+        let mut child = Command::new("sh")
+            .args(["-c", "echo out; echo err >&2"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let readers = logger.forward_child(&mut child, Level::Info, Level::Warn);
+        child.wait().unwrap();
+        for reader in readers {
+            reader.join().unwrap();
+        }
+
+        assert!(capture.contains(Level::Info, "out"));
+        let records = capture.records();
+        let err = records.iter().find(|r| r.message == "err").unwrap();
+        assert_eq!(err.level, Level::Warn);
+        assert_eq!(
+            err.fields[0],
+            (String::from("stream"), String::from("stderr"))
+        );
+    }
+}
@@ -0,0 +1,96 @@
+//! The first line of a new log file, saying how the rest was written.
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+use super::format::push_json_str;
+use super::LogFormat;
+
+/// Every header line starts with this.
+pub(crate) const PREFIX: &str = "#little_logger";
+
+/// What a reader needs to know to parse a log file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Header {
+    pub(crate) version: String,
+    pub(crate) format: LogFormat,
+    pub(crate) dt_format: String,
+}
+
+impl Header {
+    pub(crate) fn new(format: LogFormat, dt_format: &str) -> Header {
+        Header {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            format,
+            dt_format: dt_format.to_string(),
+        }
+    }
+
+    /// `#little_logger version=0.1.0 format=json dt_format="%H:%M:%S"`
+    pub(crate) fn render(&self) -> String {
+        let mut line = format!(
+            "{PREFIX} version={} format={} dt_format=",
+            self.version,
+            self.format.name()
+        );
+        push_json_str(&mut line, &self.dt_format);
+        line.push('\n');
+        line
+    }
+
+    /// Parse a line written by `render`, None if it isn't a header.
+    pub(crate) fn parse(line: &str) -> Option<Header> {
+        let mut chars = line.strip_prefix(PREFIX)?.trim().chars().peekable();
+        let (mut version, mut format, mut dt_format) = (None, None, None);
+        while chars.peek().is_some() {
+            let key: String = chars.by_ref().take_while(|c| *c != '=').collect();
+            let value = parse_value(&mut chars)?;
+            match key.trim() {
+                "version" => version = Some(value),
+                "format" => format = LogFormat::from_name(&value),
+                "dt_format" => dt_format = Some(value),
+                _ => {}
+            }
+        }
+        Some(Header {
+            version: version?,
+            format: format?,
+            dt_format: dt_format.unwrap_or_default(),
+        })
+    }
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Option<String> {
+    if chars.next_if_eq(&'"').is_none() {
+        return Some(chars.by_ref().take_while(|c| *c != ' ').collect());
+    }
+    let mut value = String::new();
+    loop {
+        match chars.next()? {
+            '"' => break,
+            '\\' => match chars.next()? {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                'r' => value.push('\r'),
+                c => value.push(c),
+            },
+            c => value.push(c),
+        }
+    }
+    while chars.next_if_eq(&' ').is_some() {}
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_round_trips() {
+        let header = Header::new(LogFormat::JsonPretty, "%Y-%m-%d \"%H\"");
+        let line = header.render();
+        assert!(line.starts_with("#little_logger version="));
+        assert_eq!(Header::parse(line.trim_end()), Some(header));
+        assert_eq!(Header::parse("[LLOG]::[t]::[INFO] -> hi"), None);
+    }
+}
@@ -0,0 +1,81 @@
+//! Labels, targets and function names kept once per Logger and the
+//! handles made from it, so the many records holding on to the same one
+//! share a single allocation.
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// Longer strings are rarely repeated and aren't kept.
+const LONGEST: usize = 256;
+/// Past this many, new strings aren't kept, so targets made up on the fly
+/// can't grow the set without end.
+const MOST: usize = 4096;
+
+#[derive(Debug, Default)]
+pub(crate) struct Interner {
+    strings: HashSet<Arc<str>>,
+}
+
+impl Interner {
+    /// The kept copy of `s`, cloning which only bumps a count.
+    pub(crate) fn intern(&mut self, s: &str) -> Arc<str> {
+        if let Some(kept) = self.strings.get(s) {
+            return Arc::clone(kept);
+        }
+        let new: Arc<str> = Arc::from(s);
+        if s.len() <= LONGEST && self.strings.len() < MOST {
+            self.strings.insert(Arc::clone(&new));
+        }
+        new
+    }
+}
+
+/// One Interner for a Logger and its children, cloning which only bumps
+/// a count.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Shared(Arc<Mutex<Interner>>);
+
+impl Shared {
+    pub(crate) fn intern(&self, s: &str) -> Arc<str> {
+        self.lock().intern(s)
+    }
+
+    pub(crate) fn lock(&self) -> MutexGuard<'_, Interner> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repeats_share_one_allocation() {
+        let mut strings = Interner::default();
+        let first = strings.intern("my_app::cache");
+        assert!(Arc::ptr_eq(&first, &strings.intern("my_app::cache")));
+
+        let long = "x".repeat(LONGEST + 1);
+        assert!(!Arc::ptr_eq(&strings.intern(&long), &strings.intern(&long)));
+        for i in 0..MOST {
+            strings.intern(&i.to_string());
+        }
+        assert_eq!(strings.strings.len(), MOST);
+        assert!(Arc::ptr_eq(&first, &strings.intern("my_app::cache")));
+    }
+
+    #[test]
+    fn test_children_share_the_label() {
+        use crate::log::{Logger, LoggerOpts};
+
+        let mut logger = Logger::new(LoggerOpts::new().set_log_type("null").set_log_label("API"));
+        let handle = logger.thread_local_handle();
+        assert!(Arc::ptr_eq(&logger.log_label, &handle.log_label));
+        assert!(Arc::ptr_eq(
+            &logger.strings.intern("my_app::db"),
+            &handle.strings.intern("my_app::db")
+        ));
+        let labeled = logger.with_label("API");
+        assert!(Arc::ptr_eq(&labeled.label, &labeled.logger.log_label));
+    }
+}
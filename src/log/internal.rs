@@ -0,0 +1,51 @@
+//! The logger's reports about its own problems, see
+//! LoggerOpts::set_internal_log.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Where the logger reports records it dropped, files it failed to rotate
+/// and sinks that failed or recovered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InternalLog {
+    /// A `little_logger:` line on stderr.
+    #[default]
+    Stderr,
+    /// A Warn record labelled LLOG-INTERNAL in the log itself. Problems
+    /// writing those go to stderr.
+    Stream,
+}
+
+impl InternalLog {
+    /// The label of the records the logger writes about itself.
+    pub const LABEL: &'static str = "LLOG-INTERNAL";
+}
+
+/// Called with each problem, see LoggerOpts::on_internal_problem.
+#[derive(Clone)]
+pub(crate) struct ProblemFn(pub(crate) Arc<dyn Fn(&str) + Send + Sync>);
+
+impl fmt::Debug for ProblemFn {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("ProblemFn")
+    }
+}
+
+/// Problems noticed while writing, picked up by the Logger once the
+/// record is done. Clones share the same list.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Problems(Arc<Mutex<Vec<String>>>);
+
+impl Problems {
+    pub(crate) fn report<S: Into<String>>(&self, problem: S) {
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(problem.into());
+    }
+
+    pub(crate) fn take(&self) -> Vec<String> {
+        std::mem::take(&mut *self.0.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
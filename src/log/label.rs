@@ -0,0 +1,26 @@
+use std::fmt;
+use std::sync::Arc;
+
+use super::Level;
+
+/// What a label function gets to look at, see LoggerOpts::set_label_fn,
+/// and what Logger::mute_for picks records by.
+#[derive(Debug, Clone, Copy)]
+pub struct RecordMeta<'r> {
+    pub level: Level,
+    /// The label the record is written with. A label function sees the
+    /// one it would get without it.
+    pub label: &'r str,
+    pub target: Option<&'r str>,
+    pub message: &'r str,
+}
+
+/// Works out the label of each record.
+#[derive(Clone)]
+pub(crate) struct LabelFn(pub(crate) Arc<dyn Fn(&RecordMeta) -> String + Send + Sync>);
+
+impl fmt::Debug for LabelFn {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("LabelFn")
+    }
+}
@@ -0,0 +1,160 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use super::Localization;
+
+/// How important a log line is.
+///
+/// Ordered from least to most severe, so `Level::Warn > Level::Info`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    /// The upper case name of the level, e.g. `WARN`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Level::Trace => "TRACE",
+            Level::Debug => "DEBUG",
+            Level::Info => "INFO",
+            Level::Warn => "WARN",
+            Level::Error => "ERROR",
+        }
+    }
+
+    fn short(&self) -> &'static str {
+        match self {
+            Level::Trace => "T",
+            Level::Debug => "D",
+            Level::Info => "I",
+            Level::Warn => "W",
+            Level::Error => "E",
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(self.as_str())
+    }
+}
+
+/// Returned when a string doesn't name a level.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseLevelError {
+    input: String,
+}
+
+impl fmt::Display for ParseLevelError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "unknown log level: {:?}", self.input)
+    }
+}
+
+impl Error for ParseLevelError {}
+
+impl FromStr for Level {
+    type Err = ParseLevelError;
+
+    /// Parse the names other tools use for levels, in any case.
+    ///
+    /// Besides `trace` through `error` this takes `warning`, `err`,
+    /// `critical` and friends, the one letter names, and syslog severity
+    /// numbers `0` to `7`. Syslog has nothing below debug so Trace is `8`.
+    fn from_str(input: &str) -> Result<Level, ParseLevelError> {
+        let level = match input.trim().to_lowercase().as_str() {
+            "trace" | "t" | "8" => Level::Trace,
+            "debug" | "d" | "7" => Level::Debug,
+            "info" | "information" | "notice" | "i" | "6" | "5" => Level::Info,
+            "warn" | "warning" | "w" | "4" => Level::Warn,
+            "error" | "err" | "e" | "critical" | "crit" | "fatal" | "alert" | "emerg"
+            | "emergency" | "3" | "2" | "1" | "0" => Level::Error,
+            _ => {
+                return Err(ParseLevelError {
+                    input: input.to_string(),
+                })
+            }
+        };
+        Ok(level)
+    }
+}
+
+/// Which spelling of the level name ends up in the log line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LevelCase {
+    /// `INFO`, `WARN`, `ERROR`
+    Upper,
+    /// `info`, `warn`, `error`
+    Lower,
+    /// `I`, `W`, `E`
+    Short,
+}
+
+/// The width of the longest level name.
+const LEVEL_WIDTH: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct LevelStyle {
+    pub(crate) case: LevelCase,
+    pub(crate) pad: bool,
+}
+
+impl LevelStyle {
+    pub(crate) fn name(&self, level: Level, names: &Localization) -> String {
+        let name = match (names.level_name(level), self.case) {
+            (Some(name), LevelCase::Upper) => name.to_string(),
+            (Some(name), LevelCase::Lower) => name.to_lowercase(),
+            (Some(name), LevelCase::Short) => name.chars().take(1).collect(),
+            (None, LevelCase::Upper) => level.as_str().to_string(),
+            (None, LevelCase::Lower) => level.as_str().to_lowercase(),
+            (None, LevelCase::Short) => level.short().to_string(),
+        };
+        // Short names are always one character wide already.
+        if self.pad && self.case != LevelCase::Short {
+            let width = LEVEL_WIDTH.max(names.level_width());
+            format!("{:<width$}", name, width = width)
+        } else {
+            name
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_level_names_line_up() {
+        let style = LevelStyle {
+            case: LevelCase::Upper,
+            pad: true,
+        };
+        assert_eq!(style.name(Level::Info, &Localization::default()), "INFO ");
+        assert_eq!(style.name(Level::Error, &Localization::default()), "ERROR");
+
+        let style = LevelStyle {
+            case: LevelCase::Short,
+            pad: false,
+        };
+        assert_eq!(style.name(Level::Warn, &Localization::default()), "W");
+    }
+
+    #[test]
+    fn test_level_from_common_strings() {
+        assert_eq!("WARNING".parse(), Ok(Level::Warn));
+        assert_eq!(" info ".parse(), Ok(Level::Info));
+        assert_eq!("3".parse(), Ok(Level::Error));
+        assert_eq!("7".parse(), Ok(Level::Debug));
+        assert!("loud".parse::<Level>().is_err());
+    }
+}
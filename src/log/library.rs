@@ -0,0 +1,119 @@
+//! Logging from a library without owning a Logger.
+//!
+//! A library that builds its own Logger opens its own `llog.txt`, which
+//! the application never asked for. Libraries should log through `with`
+//! instead, which goes nowhere (a NopLogger) until the binary hands its
+//! configured Logger over with `inject`.
+//!
+//! ```no_run
+//! // In the library:
+//! use little_logger::log::library;
+//!
+//! pub fn connect(host: &str) {
+//!     library::with(|log| little_logger::info!(log, "connecting to {host}"));
+//! }
+//!
+//! // In the binary:
+//! use little_logger::log::{Logger, LoggerOpts};
+//!
+//! library::inject(Logger::new(LoggerOpts::new().set_log_type("console")));
+//! connect("db.internal");
+//! ```
+
+use std::fmt;
+use std::sync::Mutex;
+
+use super::{Level, Logger};
+
+/// Anything a library can log through. Logger and NopLogger are the two
+/// the crate ships, an application can inject its own.
+///
+/// The level macros take a `&mut dyn LlogHandle` as well as a Logger.
+pub trait LlogHandle: Send {
+    /// Log a message on behalf of a target from a function, like
+    /// `Logger::log_from`.
+    fn log_from(&mut self, target: &str, function: &str, level: Level, msg: String);
+
+    /// Whether a record at `level` for `target` would be written, to skip
+    /// building messages that are costly.
+    fn enabled(&self, level: Level, target: &str) -> bool;
+
+    /// Write out anything still buffered.
+    fn flush(&mut self) {}
+}
+
+impl LlogHandle for Logger {
+    #[track_caller]
+    fn log_from(&mut self, target: &str, function: &str, level: Level, msg: String) {
+        Logger::log_from(self, target, function, level, msg);
+    }
+
+    fn enabled(&self, level: Level, target: &str) -> bool {
+        self.opts.filter.enabled(level, Some(target))
+    }
+
+    fn flush(&mut self) {
+        if let Err(e) = Logger::flush(self) {
+            self.problems.report(format!("flushing failed: {e}"));
+        }
+    }
+}
+
+/// Drops everything, what a library logs through until a Logger is
+/// injected.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NopLogger;
+
+impl LlogHandle for NopLogger {
+    fn log_from(&mut self, _target: &str, _function: &str, _level: Level, _msg: String) {}
+
+    fn enabled(&self, _level: Level, _target: &str) -> bool {
+        false
+    }
+}
+
+static INJECTED: Mutex<Option<Box<dyn LlogHandle>>> = Mutex::new(None);
+
+/// Hand a Logger to every library logging through `with`, giving back
+/// the one injected before, if any.
+pub fn inject<H: LlogHandle + 'static>(handle: H) -> Option<Box<dyn LlogHandle>> {
+    lock().replace(Box::new(handle))
+}
+
+/// Take the injected Logger back, say to flush it on exit. Libraries go
+/// back to a NopLogger.
+pub fn take() -> Option<Box<dyn LlogHandle>> {
+    lock().take()
+}
+
+/// Whether a Logger was injected.
+pub fn is_injected() -> bool {
+    lock().is_some()
+}
+
+/// Run `f` with the injected Logger, or a NopLogger if there isn't one.
+///
+/// The Logger is locked for the call, so don't call `with` from inside
+/// `f`.
+pub fn with<R, F>(f: F) -> R
+where
+    F: FnOnce(&mut dyn LlogHandle) -> R,
+{
+    match lock().as_deref_mut() {
+        Some(handle) => f(handle),
+        None => f(&mut NopLogger),
+    }
+}
+
+fn lock() -> std::sync::MutexGuard<'static, Option<Box<dyn LlogHandle>>> {
+    // A library that panicked mid-log shouldn't stop everyone else logging.
+    INJECTED
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl fmt::Debug for dyn LlogHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("LlogHandle")
+    }
+}
@@ -0,0 +1,70 @@
+use std::ops::{Deref, DerefMut};
+use std::time::{Duration, Instant};
+
+use super::Logger;
+
+/// How much a LocalHandle keeps before handing it to the shared file.
+const HANDOFF_BYTES: usize = 64 * 1024;
+const HANDOFF_EVERY: Duration = Duration::from_millis(200);
+
+/// When a LocalHandle's buffer is due to go to the shared file.
+#[derive(Debug)]
+pub(crate) struct Handoff {
+    last: Instant,
+}
+
+impl Handoff {
+    pub(crate) fn new() -> Handoff {
+        Handoff {
+            last: Instant::now(),
+        }
+    }
+
+    /// Whether `buffered` bytes should be handed off now, restarting the
+    /// clock if so.
+    pub(crate) fn due(&mut self, buffered: usize) -> bool {
+        let due = buffered >= HANDOFF_BYTES || self.last.elapsed() >= HANDOFF_EVERY;
+        if due {
+            self.last = Instant::now();
+        }
+        due
+    }
+}
+
+/// A Logger for one thread that writes into the same log file as the one
+/// it came from, see `Logger::thread_local_handle`.
+///
+/// Lines are formatted and buffered without touching the shared file, then
+/// handed over in batches of up to 64 KiB or every 200ms, whichever comes
+/// first. Whatever is left goes over when the handle is flushed or dropped.
+#[derive(Debug)]
+pub struct LocalHandle {
+    logger: Logger,
+}
+
+impl LocalHandle {
+    pub(crate) fn new(logger: Logger) -> LocalHandle {
+        LocalHandle { logger }
+    }
+}
+
+impl Deref for LocalHandle {
+    type Target = Logger;
+
+    fn deref(&self) -> &Logger {
+        &self.logger
+    }
+}
+
+impl DerefMut for LocalHandle {
+    fn deref_mut(&mut self) -> &mut Logger {
+        &mut self.logger
+    }
+}
+
+impl Drop for LocalHandle {
+    /// Hand over whatever is still buffered.
+    fn drop(&mut self) {
+        let _ = self.logger.flush();
+    }
+}
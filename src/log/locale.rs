@@ -0,0 +1,68 @@
+use super::Level;
+
+/// Level names and built-in words in another language, see
+/// LoggerOpts::set_localization.
+///
+/// The built-in words are `starting` from Logger::log_banner and `enter`
+/// and `exit` from spans. Anything left out stays in English.
+///
+/// ```no_run
+/// use little_logger::log::{Level, Localization, LoggerOpts};
+///
+/// let french = Localization::new()
+///     .level(Level::Warn, "ATTENTION")
+///     .level(Level::Error, "ERREUR")
+///     .text("starting", "démarrage");
+/// let opts = LoggerOpts::new().set_localization(french);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Localization {
+    levels: Vec<(Level, String)>,
+    texts: Vec<(String, String)>,
+}
+
+impl Localization {
+    pub fn new() -> Localization {
+        Localization::default()
+    }
+
+    /// Use `name` for the level, the level case and padding still apply.
+    pub fn level(mut self, level: Level, name: &str) -> Self {
+        self.levels.retain(|(l, _)| *l != level);
+        self.levels.push((level, name.to_string()));
+        self
+    }
+
+    /// Use `translated` in place of the built-in word `key`.
+    pub fn text(mut self, key: &str, translated: &str) -> Self {
+        self.texts.retain(|(k, _)| k != key);
+        self.texts.push((key.to_string(), translated.to_string()));
+        self
+    }
+
+    pub(crate) fn level_name(&self, level: Level) -> Option<&str> {
+        self.levels
+            .iter()
+            .find(|(l, _)| *l == level)
+            .map(|(_, name)| name.as_str())
+    }
+
+    /// The translation of `key`, or `key` itself.
+    pub(crate) fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.texts
+            .iter()
+            .find(|(k, _)| k == key)
+            .map_or(key, |(_, translated)| translated.as_str())
+    }
+
+    /// The width of the longest translated level name.
+    pub(crate) fn level_width(&self) -> usize {
+        self.levels
+            .iter()
+            .map(|(_, name)| name.chars().count())
+            .max()
+            .unwrap_or(0)
+    }
+}
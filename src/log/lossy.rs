@@ -0,0 +1,60 @@
+use std::borrow::Cow;
+use std::fmt::Write;
+
+/// What to write in place of bytes that aren't UTF-8, see
+/// LoggerOpts::set_invalid_utf8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InvalidUtf8 {
+    /// U+FFFD for each bad sequence, like String::from_utf8_lossy.
+    #[default]
+    Replace,
+    /// `\xNN` for each bad byte, so the original bytes can be recovered.
+    Escape,
+}
+
+impl InvalidUtf8 {
+    /// `bytes` as text, borrowed when they are valid UTF-8.
+    pub(crate) fn decode(self, bytes: &[u8]) -> Cow<'_, str> {
+        match self {
+            InvalidUtf8::Replace => String::from_utf8_lossy(bytes),
+            InvalidUtf8::Escape => escape(bytes),
+        }
+    }
+}
+
+fn escape(bytes: &[u8]) -> Cow<'_, str> {
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return Cow::Borrowed(text);
+    }
+    let mut text = String::with_capacity(bytes.len() + 8);
+    for chunk in bytes.utf8_chunks() {
+        text.push_str(chunk.valid());
+        for byte in chunk.invalid() {
+            let _ = write!(text, "\\x{byte:02x}");
+        }
+    }
+    Cow::Owned(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalid_bytes_replaced_or_escaped() {
+        let bytes = b"GET /caf\xc3\xa9\xff\xfe.txt";
+        assert_eq!(
+            InvalidUtf8::Replace.decode(bytes),
+            "GET /caf\u{e9}\u{fffd}\u{fffd}.txt"
+        );
+        assert_eq!(
+            InvalidUtf8::Escape.decode(bytes),
+            "GET /caf\u{e9}\\xff\\xfe.txt"
+        );
+        assert!(matches!(
+            InvalidUtf8::Escape.decode(b"fine"),
+            Cow::Borrowed("fine")
+        ));
+    }
+}
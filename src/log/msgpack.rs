@@ -0,0 +1,104 @@
+//! Just enough MessagePack for flat maps of strings.
+
+use std::io;
+
+use super::format::Record;
+
+/// Encode `record` as a length-prefixed MessagePack map.
+///
+/// The prefix is the size of the map in bytes as a big endian u32.
+pub(crate) fn encode(record: &Record) -> Vec<u8> {
+    let fields = record.fields();
+
+    let mut body = Vec::new();
+    push_map_len(&mut body, fields.len());
+    for (key, value) in fields {
+        push_str(&mut body, key);
+        push_str(&mut body, value);
+    }
+    let mut out = (body.len() as u32).to_be_bytes().to_vec();
+    out.extend_from_slice(&body);
+    out
+}
+
+fn push_map_len(out: &mut Vec<u8>, len: usize) {
+    match len {
+        0..=15 => out.push(0x80 | len as u8),
+        16..=0xffff => {
+            out.push(0xde);
+            out.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        _ => {
+            out.push(0xdf);
+            out.extend_from_slice(&(len as u32).to_be_bytes());
+        }
+    }
+}
+
+fn push_str(out: &mut Vec<u8>, value: &str) {
+    let len = value.len();
+    match len {
+        0..=31 => out.push(0xa0 | len as u8),
+        32..=0xff => out.extend_from_slice(&[0xd9, len as u8]),
+        0x100..=0xffff => {
+            out.push(0xda);
+            out.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        _ => {
+            out.push(0xdb);
+            out.extend_from_slice(&(len as u32).to_be_bytes());
+        }
+    }
+    out.extend_from_slice(value.as_bytes());
+}
+
+/// Decode one map written by `encode`, without the length prefix.
+pub(crate) fn decode(mut buf: &[u8]) -> io::Result<Vec<(String, String)>> {
+    let len = read_map_len(&mut buf)?;
+    let mut fields = Vec::with_capacity(len);
+    for _ in 0..len {
+        let key = read_str(&mut buf)?;
+        let value = read_str(&mut buf)?;
+        fields.push((key, value));
+    }
+    Ok(fields)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn take<'b>(buf: &mut &'b [u8], n: usize) -> io::Result<&'b [u8]> {
+    if buf.len() < n {
+        return Err(invalid("truncated MessagePack record"));
+    }
+    let (head, rest) = buf.split_at(n);
+    *buf = rest;
+    Ok(head)
+}
+
+fn read_uint(buf: &mut &[u8], n: usize) -> io::Result<usize> {
+    Ok(take(buf, n)?
+        .iter()
+        .fold(0, |acc, byte| (acc << 8) | *byte as usize))
+}
+
+fn read_map_len(buf: &mut &[u8]) -> io::Result<usize> {
+    match take(buf, 1)?[0] {
+        tag @ 0x80..=0x8f => Ok((tag & 0x0f) as usize),
+        0xde => read_uint(buf, 2),
+        0xdf => read_uint(buf, 4),
+        _ => Err(invalid("expected a MessagePack map")),
+    }
+}
+
+fn read_str(buf: &mut &[u8]) -> io::Result<String> {
+    let len = match take(buf, 1)?[0] {
+        tag @ 0xa0..=0xbf => (tag & 0x1f) as usize,
+        0xd9 => read_uint(buf, 1)?,
+        0xda => read_uint(buf, 2)?,
+        0xdb => read_uint(buf, 4)?,
+        _ => return Err(invalid("expected a MessagePack string")),
+    };
+    String::from_utf8(take(buf, len)?.to_vec()).map_err(|_| invalid("string is not UTF-8"))
+}
@@ -0,0 +1,71 @@
+use std::fmt;
+use std::time::Instant;
+
+use super::internal::Problems;
+use super::RecordMeta;
+
+/// Drops the records `matches` picks until `until`, see Logger::mute_for.
+pub(crate) struct Mute {
+    until: Instant,
+    matches: Box<dyn Fn(&RecordMeta) -> bool + Send>,
+    dropped: u64,
+}
+
+impl fmt::Debug for Mute {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter
+            .debug_struct("Mute")
+            .field("until", &self.until)
+            .field("dropped", &self.dropped)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Mute {
+    pub(crate) fn new<F>(until: Instant, matches: F) -> Mute
+    where
+        F: Fn(&RecordMeta) -> bool + Send + 'static,
+    {
+        Mute {
+            until,
+            matches: Box::new(matches),
+            dropped: 0,
+        }
+    }
+
+    /// Say how much the mute dropped, so the gap in the log is explained.
+    pub(crate) fn end(self, problems: &Problems) {
+        if self.dropped > 0 {
+            problems.report(format!(
+                "a mute ended after dropping {} records",
+                self.dropped
+            ));
+        }
+    }
+}
+
+/// Whether a mute still running at `now` drops `record`. Mutes that ran
+/// out are ended on the way.
+pub(crate) fn is_muted(
+    mutes: &mut Vec<Mute>,
+    record: &RecordMeta,
+    now: Instant,
+    problems: &Problems,
+) -> bool {
+    if mutes.iter().any(|mute| mute.until <= now) {
+        let (ended, running) = std::mem::take(mutes)
+            .into_iter()
+            .partition(|mute| mute.until <= now);
+        *mutes = running;
+        for mute in ended {
+            mute.end(problems);
+        }
+    }
+    match mutes.iter_mut().find(|mute| (mute.matches)(record)) {
+        Some(mute) => {
+            mute.dropped += 1;
+            true
+        }
+        None => false,
+    }
+}
@@ -0,0 +1,43 @@
+//! Noticing at startup that stdout or stderr lead nowhere, as they do in
+//! a daemon that closed them or pointed them at `/dev/null`.
+
+/// Why output written to `fd` would be lost, or None if it goes somewhere.
+///
+/// A Rust program's std points a closed stdout or stderr at `/dev/null`
+/// before main, so "closed" only turns up when something closes it later.
+#[cfg(unix)]
+pub(crate) fn nowhere(fd: i32) -> Option<&'static str> {
+    // SAFETY: fstat and stat only write to the zeroed structs they get.
+    unsafe {
+        let mut out: libc::stat = std::mem::zeroed();
+        if libc::fstat(fd, &mut out) != 0 {
+            return Some("closed");
+        }
+        let mut null: libc::stat = std::mem::zeroed();
+        if libc::stat(c"/dev/null".as_ptr(), &mut null) != 0 {
+            return None;
+        }
+        let is_char = out.st_mode & libc::S_IFMT == libc::S_IFCHR;
+        (is_char && out.st_rdev == null.st_rdev).then_some("/dev/null")
+    }
+}
+
+#[cfg(not(unix))]
+pub(crate) fn nowhere(_fd: i32) -> Option<&'static str> {
+    None
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::fd::AsRawFd;
+
+    #[test]
+    fn test_dev_null_is_nowhere() {
+        let null = std::fs::File::open("/dev/null").unwrap();
+        assert_eq!(nowhere(null.as_raw_fd()), Some("/dev/null"));
+        let file = std::fs::File::open("Cargo.toml").unwrap();
+        assert_eq!(nowhere(file.as_raw_fd()), None);
+        assert_eq!(nowhere(-1), Some("closed"));
+    }
+}
@@ -0,0 +1,128 @@
+use std::io::{self, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+use super::format::{Layout, Record};
+use super::sink::Sink;
+use super::LogFormat;
+
+/// A command whose stdin receives every record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct PipeSpec {
+    /// The program followed by its arguments.
+    pub(crate) command: Vec<String>,
+    pub(crate) format: LogFormat,
+}
+
+/// Streams records into a child process, starting it again if it exits.
+#[derive(Debug)]
+pub(crate) struct PipeSink {
+    spec: PipeSpec,
+    /// Write each record on exactly one line, see
+    /// LoggerOpts::set_pipe_one_line.
+    one_line: bool,
+    /// Started on the first write, like the log file.
+    child: Option<(Child, ChildStdin)>,
+}
+
+impl PipeSink {
+    pub(crate) fn new(spec: PipeSpec, one_line: bool) -> PipeSink {
+        PipeSink {
+            spec,
+            one_line,
+            child: None,
+        }
+    }
+
+    fn stdin(&mut self) -> io::Result<&mut ChildStdin> {
+        if let Some((child, _)) = &mut self.child {
+            if !matches!(child.try_wait(), Ok(None)) {
+                self.child = None;
+            }
+        }
+        if self.child.is_none() {
+            let (program, args) =
+                self.spec.command.split_first().ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, "empty pipe command")
+                })?;
+            let mut child = Command::new(program)
+                .args(args)
+                .stdin(Stdio::piped())
+                .spawn()?;
+            let stdin = child.stdin.take().expect("stdin was piped");
+            self.child = Some((child, stdin));
+        }
+        Ok(&mut self.child.as_mut().expect("child was just started").1)
+    }
+}
+
+impl Sink for PipeSink {
+    /// A write that fails because the child went away is tried once more
+    /// against a fresh child.
+    fn write_record(&mut self, record: &Record, layout: &Layout) -> io::Result<()> {
+        let line = match (self.one_line, self.spec.format) {
+            (true, LogFormat::JsonPretty) => LogFormat::Json.encode(record, layout),
+            (true, format) if !format.is_binary() => one_line(&format.encode(record, layout)),
+            (_, format) => format.encode(record, layout),
+        };
+        match self.stdin()?.write_all(&line) {
+            Ok(()) => Ok(()),
+            Err(_) => {
+                self.child = None;
+                self.stdin()?.write_all(&line)
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.child {
+            Some((_, stdin)) => stdin.flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "pipe"
+    }
+}
+
+/// `line` with the newlines inside it escaped as `\n` and exactly one at
+/// the end, so a line based reader gets the record in one piece.
+fn one_line(line: &[u8]) -> Vec<u8> {
+    let body = line.trim_ascii_end();
+    let mut out = Vec::with_capacity(body.len() + 1);
+    for &byte in body {
+        match byte {
+            b'\n' => out.extend_from_slice(b"\\n"),
+            b'\r' => out.extend_from_slice(b"\\r"),
+            byte => out.push(byte),
+        }
+    }
+    out.push(b'\n');
+    out
+}
+
+impl Drop for PipeSink {
+    /// Close stdin and let the child finish what it was sent.
+    fn drop(&mut self) {
+        if let Some((mut child, stdin)) = self.child.take() {
+            drop(stdin);
+            let _ = child.wait();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_one_line_escapes_inner_newlines() {
+        let record = b"[LLOG]::[ERROR] -> query failed\ntimed out\r\n   0: app::db\n";
+        assert_eq!(
+            one_line(record),
+            b"[LLOG]::[ERROR] -> query failed\\ntimed out\\r\\n   0: app::db\n"
+        );
+        assert_eq!(one_line(b"done"), b"done\n");
+    }
+}
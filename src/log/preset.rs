@@ -0,0 +1,77 @@
+use super::testing::Capture;
+use super::{Level, LogFormat, LoggerOpts};
+
+/// A full configuration for a common situation, see LoggerOpts::preset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+    /// Colored console at Debug, no file.
+    Dev,
+    /// JSON to `llog.txt` at Info, rotated at 10 MiB keeping the last 5.
+    Prod,
+    /// Everything from Trace kept in memory with deterministic times,
+    /// read it back through LoggerOpts::capture.
+    Test,
+    /// LogFormat::Gcp on stdout at Info, no file. Containers should leave
+    /// their logs to the platform, and GKE and Cloud Run parse this.
+    Container,
+}
+
+impl LoggerOpts {
+    /// Start from `profile` instead of the defaults. The set methods can
+    /// still change any of it.
+    ///
+    /// ```no_run
+    /// use little_logger::log::{Logger, LoggerOpts, Profile};
+    ///
+    /// let logger = Logger::new(LoggerOpts::preset(Profile::Prod).set_log_label("API"));
+    /// ```
+    ///
+    /// Dev and Container need the `console` feature and Prod the `file`
+    /// feature.
+    pub fn preset(profile: Profile) -> LoggerOpts {
+        let opts = LoggerOpts::new();
+        match profile {
+            Profile::Dev => opts
+                .set_log_type("console")
+                .set_console_format(LogFormat::Colored)
+                .set_level(Level::Debug),
+            Profile::Prod => opts
+                .set_log_type("file")
+                .set_file_format(LogFormat::Json)
+                .set_level(Level::Info)
+                .set_rotate_size(10 * 1024 * 1024)
+                .set_keep_rotated(5),
+            Profile::Test => opts
+                .set_log_type("null")
+                .set_level(Level::Trace)
+                .set_capture(&Capture::new())
+                .deterministic(),
+            Profile::Container => opts
+                .set_log_type("console")
+                .set_console_format(LogFormat::Gcp)
+                .set_dt_format("%Y-%m-%dT%H:%M:%S%.6f%:z")
+                .set_level(Level::Info),
+        }
+    }
+
+    /// The first Capture set on these opts, the one Profile::Test adds.
+    pub fn capture(&self) -> Option<&Capture> {
+        self.captures.first()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::Logger;
+
+    #[test]
+    fn test_test_profile_captures_everything() {
+        let opts = LoggerOpts::preset(Profile::Test);
+        let capture = opts.capture().unwrap().clone();
+        let mut logger = Logger::new(opts);
+        logger.trace("deep detail");
+
+        assert_eq!(capture.snapshot(), "[LLOG]::[TRACE] -> deep detail\n");
+    }
+}
@@ -0,0 +1,86 @@
+use std::time::{Duration, Instant};
+
+/// The state of one `Logger::progress` name between calls.
+#[derive(Debug)]
+pub(crate) struct Progress {
+    name: String,
+    started: Instant,
+    logged: Instant,
+}
+
+/// Whether `name` is due a progress line at `now`, and if so its message
+/// and rate in items per second.
+///
+/// The first call and the one reaching `total` are always due, the rest
+/// only once `interval` has passed since the last line. Reaching `total`
+/// forgets the name so the next run starts over.
+pub(crate) fn update(
+    tracks: &mut Vec<Progress>,
+    name: &str,
+    current: u64,
+    total: u64,
+    interval: Duration,
+    now: Instant,
+) -> Option<(String, String)> {
+    let done = current >= total;
+    let (started, due) = match tracks.iter_mut().position(|t| t.name == name) {
+        Some(i) if done => (tracks.remove(i).started, true),
+        Some(i) => {
+            let track = &mut tracks[i];
+            let due = now.duration_since(track.logged) >= interval;
+            if due {
+                track.logged = now;
+            }
+            (track.started, due)
+        }
+        None => {
+            if !done {
+                tracks.push(Progress {
+                    name: name.to_string(),
+                    started: now,
+                    logged: now,
+                });
+            }
+            (now, true)
+        }
+    };
+    if !due {
+        return None;
+    }
+    let percent = match total {
+        0 => 100.0,
+        _ => current as f64 * 100.0 / total as f64,
+    };
+    let secs = now.duration_since(started).as_secs_f64();
+    let rate = match secs > 0.0 {
+        true => current as f64 / secs,
+        false => 0.0,
+    };
+    let msg = format!("{} {}/{} ({:.1}%)", name, current, total, percent);
+    Some((msg, format!("{:.1}", rate)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_is_throttled() {
+        let mut tracks = Vec::new();
+        let second = Duration::from_secs(1);
+        let start = Instant::now();
+
+        let first = update(&mut tracks, "indexed", 10, 100, second, start);
+        assert_eq!(first.unwrap().0, "indexed 10/100 (10.0%)");
+        let soon = start + Duration::from_millis(500);
+        assert!(update(&mut tracks, "indexed", 20, 100, second, soon).is_none());
+        let later = start + Duration::from_secs(2);
+        let (msg, rate) = update(&mut tracks, "indexed", 50, 100, second, later).unwrap();
+        assert_eq!(
+            (msg.as_str(), rate.as_str()),
+            ("indexed 50/100 (50.0%)", "25.0")
+        );
+        assert!(update(&mut tracks, "indexed", 100, 100, second, later).is_some());
+        assert!(tracks.is_empty());
+    }
+}
@@ -0,0 +1,1032 @@
+//! Read back logs written in the structured formats.
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::iter::Peekable;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str::Chars;
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+
+use super::format::push_json_str;
+use super::header::{self, Header};
+use super::{cbor, msgpack, LogFormat};
+
+/// One record read back from a log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    fields: Vec<(String, String)>,
+}
+
+impl Entry {
+    /// The value of a field such as `"message"` or `"level"`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Every field in the order it was written.
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.fields.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+}
+
+/// A longer length prefix is taken for a corrupt one rather than
+/// allocated.
+const MAX_FRAME: usize = 16 * 1024 * 1024;
+
+/// Iterates over the records of a LogFormat::MsgPack log.
+///
+/// ```no_run
+/// use std::fs::File;
+/// use little_logger::log::reader::MsgPackReader;
+///
+/// for entry in MsgPackReader::new(File::open("llog.bin").unwrap()) {
+///     let entry = entry.unwrap();
+///     println!("{}", entry.get("message").unwrap_or_default());
+/// }
+/// ```
+#[derive(Debug)]
+pub struct MsgPackReader<R> {
+    inner: R,
+}
+
+impl<R: Read> MsgPackReader<R> {
+    pub fn new(inner: R) -> MsgPackReader<R> {
+        MsgPackReader { inner }
+    }
+
+    fn read_entry(&mut self) -> io::Result<Option<Entry>> {
+        let mut prefix = [0u8; 4];
+        let mut filled = 0;
+        while filled < prefix.len() {
+            match self.inner.read(&mut prefix[filled..])? {
+                0 if filled == 0 => return Ok(None),
+                0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+                n => filled += n,
+            }
+        }
+        let len = u32::from_be_bytes(prefix) as usize;
+        if len > MAX_FRAME {
+            return Err(invalid("MsgPack record too long, the log may be corrupt"));
+        }
+        let mut body = vec![0u8; len];
+        self.inner.read_exact(&mut body)?;
+        let fields = msgpack::decode(&body)?;
+        Ok(Some(Entry { fields }))
+    }
+}
+
+impl<R: Read> Iterator for MsgPackReader<R> {
+    type Item = io::Result<Entry>;
+
+    fn next(&mut self) -> Option<io::Result<Entry>> {
+        self.read_entry().transpose()
+    }
+}
+
+/// Iterates over the records of a LogFormat::Cbor log.
+#[derive(Debug)]
+pub struct CborReader<R> {
+    inner: R,
+}
+
+impl<R: Read> CborReader<R> {
+    pub fn new(inner: R) -> CborReader<R> {
+        CborReader { inner }
+    }
+}
+
+impl<R: Read> Iterator for CborReader<R> {
+    type Item = io::Result<Entry>;
+
+    fn next(&mut self) -> Option<io::Result<Entry>> {
+        cbor::decode(&mut self.inner)
+            .map(|fields| fields.map(|fields| Entry { fields }))
+            .transpose()
+    }
+}
+
+/// Iterates over the records of a LogFormat::Json log.
+///
+/// Each line must hold one flat object, so LogFormat::JsonPretty logs
+/// can't be read back this way. Blank lines and header lines are skipped.
+#[derive(Debug)]
+pub struct JsonReader<R> {
+    lines: io::Lines<R>,
+}
+
+impl<R: BufRead> JsonReader<R> {
+    pub fn new(inner: R) -> JsonReader<R> {
+        JsonReader {
+            lines: inner.lines(),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for JsonReader<R> {
+    type Item = io::Result<Entry>;
+
+    fn next(&mut self) -> Option<io::Result<Entry>> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e)),
+            };
+            if line.trim().is_empty() || line.starts_with(header::PREFIX) {
+                continue;
+            }
+            return Some(parse_json_object(&line).map(|fields| Entry { fields }));
+        }
+    }
+}
+
+/// Reads a Text or Json log line by line and can jump to a point in time.
+///
+/// Only one line is held in memory at a time, so multi-gigabyte logs are
+/// fine. seek_to does a binary search over the file, which assumes the
+/// lines are in time order as a single logger writes them.
+///
+/// Times are parsed with the dt_format from the file header, or the
+/// default one when there is no header. A dt_format without a date is
+/// treated as all on 1970-01-01.
+///
+/// Json lines come back with all their fields. Text lines come back with
+/// a `time` field and the whole line as `line`.
+///
+/// ```no_run
+/// use chrono::NaiveDateTime;
+/// use little_logger::log::reader::SeekableReader;
+///
+/// let mut log = SeekableReader::open("llog.txt").unwrap();
+/// let incident = NaiveDateTime::parse_from_str("2024-05-21 18:37:00", "%Y-%m-%d %H:%M:%S").unwrap();
+/// log.seek_to(incident).unwrap();
+/// for entry in log.take(100) {
+///     println!("{:?}", entry.unwrap().get("time"));
+/// }
+/// ```
+#[derive(Debug)]
+pub struct SeekableReader {
+    src: BufReader<File>,
+    /// Where the first record starts, after any header.
+    start: u64,
+    len: u64,
+    format: LogFormat,
+    dt_format: String,
+}
+
+/// Below this many bytes the binary search gives way to a linear scan.
+const SCAN_WINDOW: u64 = 64 * 1024;
+
+impl SeekableReader {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<SeekableReader> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        let mut src = BufReader::new(file);
+        let mut header = None;
+        if src.fill_buf()?.starts_with(header::PREFIX.as_bytes()) {
+            let mut line = String::new();
+            src.read_line(&mut line)?;
+            header = Header::parse(line.trim_end());
+        }
+        let start = src.stream_position()?;
+        let format = match &header {
+            Some(header) => header.format,
+            None if src.fill_buf()?.first() == Some(&b'{') => LogFormat::Json,
+            None => LogFormat::Text,
+        };
+        if !matches!(format, LogFormat::Json | LogFormat::Text) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("can't seek in {} logs", format.name()),
+            ));
+        }
+        let dt_format = header.map_or_else(
+            || String::from("%Y-%m-%d %H:%M:%S"),
+            |header| header.dt_format,
+        );
+        Ok(SeekableReader {
+            src,
+            start,
+            len,
+            format,
+            dt_format,
+        })
+    }
+
+    /// Move to the first line logged at or after `time`.
+    pub fn seek_to(&mut self, time: NaiveDateTime) -> io::Result<()> {
+        let (mut lo, mut hi) = (self.start, self.len);
+        while hi - lo > SCAN_WINDOW {
+            let mid = lo + (hi - lo) / 2;
+            match self.time_after(mid)? {
+                Some(found) if found < time => lo = mid,
+                _ => hi = mid,
+            }
+        }
+        self.align(lo)?;
+        loop {
+            let here = self.src.stream_position()?;
+            let Some(line) = self.read_line()? else {
+                return Ok(());
+            };
+            if self.line_time(&line).is_some_and(|found| found >= time) {
+                self.src.seek(SeekFrom::Start(here))?;
+                return Ok(());
+            }
+        }
+    }
+
+    /// Move to the start of the first whole line at or after `offset`.
+    fn align(&mut self, offset: u64) -> io::Result<()> {
+        if offset <= self.start {
+            self.src.seek(SeekFrom::Start(self.start))?;
+            return Ok(());
+        }
+        self.src.seek(SeekFrom::Start(offset - 1))?;
+        let mut partial = Vec::new();
+        self.src.read_until(b'\n', &mut partial)?;
+        Ok(())
+    }
+
+    /// The time of the first line with one at or after `offset`.
+    fn time_after(&mut self, offset: u64) -> io::Result<Option<NaiveDateTime>> {
+        self.align(offset)?;
+        while let Some(line) = self.read_line()? {
+            if let Some(time) = self.line_time(&line) {
+                return Ok(Some(time));
+            }
+        }
+        Ok(None)
+    }
+
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        let mut line = String::new();
+        if self.src.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(line.trim_end_matches(['\r', '\n']).to_string()))
+    }
+
+    fn line_time(&self, line: &str) -> Option<NaiveDateTime> {
+        let time = match self.format {
+            LogFormat::Json => {
+                parse_json_object(line)
+                    .ok()?
+                    .into_iter()
+                    .find(|(k, _)| k == "time")?
+                    .1
+            }
+            _ => text_time(line)?.to_string(),
+        };
+        self.parse_time(&time)
+    }
+
+    fn parse_time(&self, time: &str) -> Option<NaiveDateTime> {
+        NaiveDateTime::parse_from_str(time, &self.dt_format)
+            .ok()
+            .or_else(|| {
+                let time = NaiveTime::parse_from_str(time, &self.dt_format).ok()?;
+                Some(NaiveDate::default().and_time(time))
+            })
+    }
+}
+
+/// The second bracketed field of a Text line, `[LLOG]::[this]::[INFO]`.
+fn text_time(line: &str) -> Option<&str> {
+    let rest = &line[line.find(']')? + 1..];
+    let rest = &rest[rest.find('[')? + 1..];
+    Some(&rest[..rest.find(']')?])
+}
+
+impl Iterator for SeekableReader {
+    type Item = io::Result<Entry>;
+
+    fn next(&mut self) -> Option<io::Result<Entry>> {
+        loop {
+            let line = match self.read_line() {
+                Ok(line) => line?,
+                Err(e) => return Some(Err(e)),
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            if self.format == LogFormat::Json {
+                return Some(parse_json_object(&line).map(|fields| Entry { fields }));
+            }
+            let mut fields = Vec::new();
+            if let Some(time) = text_time(&line) {
+                fields.push((String::from("time"), time.to_string()));
+            }
+            fields.push((String::from("line"), line));
+            return Some(Ok(Entry { fields }));
+        }
+    }
+}
+
+/// Where the header of a binary log goes: `llog.bin` gets `llog.bin.meta`.
+pub(crate) fn meta_path(path: &Path) -> PathBuf {
+    let mut meta = path.as_os_str().to_owned();
+    meta.push(".meta");
+    PathBuf::from(meta)
+}
+
+/// Records from any log file that can be read back.
+pub type Entries = Box<dyn Iterator<Item = io::Result<Entry>>>;
+
+/// Open a log and read it with the parser its format needs.
+///
+/// The format comes from the header line or `.meta` file written with
+/// LoggerOpts::set_file_header. Without one it is guessed from the first
+/// byte. Text and pretty JSON logs can't be read back.
+///
+/// ```no_run
+/// use little_logger::log::reader;
+///
+/// for entry in reader::open("llog.txt").unwrap() {
+///     println!("{:?}", entry.unwrap().get("message"));
+/// }
+/// ```
+pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Entries> {
+    let path = path.as_ref();
+    let mut src = BufReader::new(File::open(path)?);
+    let from_meta = fs::read_to_string(meta_path(path))
+        .ok()
+        .and_then(|meta| Header::parse(meta.trim_end()));
+    let format = match from_meta {
+        Some(header) => header.format,
+        None if src.fill_buf()?.starts_with(header::PREFIX.as_bytes()) => {
+            let mut line = String::new();
+            src.read_line(&mut line)?;
+            Header::parse(line.trim_end())
+                .ok_or_else(|| invalid("unreadable log header"))?
+                .format
+        }
+        None => match src.fill_buf()?.first() {
+            Some(b'{') => LogFormat::Json,
+            // The label column of a Text line.
+            Some(b'[') => LogFormat::Text,
+            // The CBOR self-describe tag.
+            Some(0xd9) => LogFormat::Cbor,
+            _ => LogFormat::MsgPack,
+        },
+    };
+    match format {
+        LogFormat::Json => Ok(Box::new(JsonReader::new(src))),
+        LogFormat::MsgPack => Ok(Box::new(MsgPackReader::new(src))),
+        LogFormat::Cbor => Ok(Box::new(CborReader::new(src))),
+        other => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("{} logs can't be read back", other.name()),
+        )),
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Parse a flat JSON object. Values that aren't strings are kept as written.
+pub(crate) fn parse_json_object(line: &str) -> io::Result<Vec<(String, String)>> {
+    let mut chars = line.trim().chars().peekable();
+    if chars.next() != Some('{') {
+        return Err(invalid("expected a JSON object"));
+    }
+    let mut fields = Vec::new();
+    loop {
+        skip_ws(&mut chars);
+        match chars.next() {
+            Some('}') if fields.is_empty() => break,
+            Some('"') => {}
+            _ => return Err(invalid("expected a JSON key")),
+        }
+        let key = parse_json_str(&mut chars)?;
+        skip_ws(&mut chars);
+        if chars.next() != Some(':') {
+            return Err(invalid("expected ':' after a JSON key"));
+        }
+        skip_ws(&mut chars);
+        let value = if chars.peek() == Some(&'"') {
+            chars.next();
+            parse_json_str(&mut chars)?
+        } else {
+            let mut raw = String::new();
+            while let Some(c) = chars.next_if(|c| *c != ',' && *c != '}') {
+                raw.push(c);
+            }
+            raw.trim_end().to_string()
+        };
+        fields.push((key, value));
+        skip_ws(&mut chars);
+        match chars.next() {
+            Some(',') => continue,
+            Some('}') => break,
+            _ => return Err(invalid("expected ',' or '}' in a JSON object")),
+        }
+    }
+    Ok(fields)
+}
+
+fn skip_ws(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+/// Parse the rest of a JSON string, the opening quote is already taken.
+fn parse_json_str(chars: &mut Peekable<Chars>) -> io::Result<String> {
+    let mut out = String::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(out),
+            Some('\\') => match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('r') => out.push('\r'),
+                Some('t') => out.push('\t'),
+                Some('b') => out.push('\u{8}'),
+                Some('f') => out.push('\u{c}'),
+                Some('u') => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    let code = u32::from_str_radix(&hex, 16)
+                        .map_err(|_| invalid("bad \\u escape in JSON string"))?;
+                    out.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                }
+                Some(c) => out.push(c),
+                None => return Err(invalid("unterminated JSON string")),
+            },
+            Some(c) => out.push(c),
+            None => return Err(invalid("unterminated JSON string")),
+        }
+    }
+}
+
+/// Interleaves several logs by their `time` field, see merge_files.
+pub struct Merged {
+    sources: Vec<Entries>,
+    /// The next entry of each source, None once it is used up.
+    heads: Vec<Option<io::Result<Entry>>>,
+}
+
+impl fmt::Debug for Merged {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter
+            .debug_struct("Merged")
+            .field("sources", &self.sources.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Merge logs that are each in time order into one stream in time order.
+///
+/// Times are compared as text, which is right for the default dt_format
+/// and anything else that goes from years down to seconds. Entries with
+/// the same time keep the order of `sources`.
+pub fn merge(sources: Vec<Entries>) -> Merged {
+    let mut sources = sources;
+    let heads = sources.iter_mut().map(|source| source.next()).collect();
+    Merged { sources, heads }
+}
+
+/// Open and merge the files written with LoggerOpts::set_per_thread_files.
+///
+/// ```no_run
+/// use little_logger::log::reader;
+///
+/// let merged = reader::merge_files(&["llog.worker-1.txt", "llog.worker-2.txt"]).unwrap();
+/// for entry in merged {
+///     println!("{:?}", entry.unwrap().get("message"));
+/// }
+/// ```
+///
+/// Text logs are read like SeekableReader does. A file whose first
+/// record doesn't decode in the format guessed for it, like a Text log
+/// written without decorations, is read that way too.
+pub fn merge_files<P: AsRef<Path>>(paths: &[P]) -> io::Result<Merged> {
+    let mut sources = Vec::new();
+    for path in paths {
+        let source = match open(path) {
+            Err(e) if e.kind() == io::ErrorKind::Unsupported => {
+                Box::new(SeekableReader::open(path)?) as Entries
+            }
+            Err(e) => return Err(e),
+            Ok(mut source) => match source.next() {
+                Some(Err(e)) if is_decode_error(&e) => {
+                    Box::new(SeekableReader::open(path)?) as Entries
+                }
+                first => Box::new(first.into_iter().chain(source)),
+            },
+        };
+        sources.push(source);
+    }
+    Ok(merge(sources))
+}
+
+fn is_decode_error(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof
+    )
+}
+
+impl Iterator for Merged {
+    type Item = io::Result<Entry>;
+
+    fn next(&mut self) -> Option<io::Result<Entry>> {
+        let mut pick: Option<(usize, &str)> = None;
+        for (i, head) in self.heads.iter().enumerate() {
+            let time = match head {
+                None => continue,
+                // Hand errors on straight away.
+                Some(Err(_)) => {
+                    pick = Some((i, ""));
+                    break;
+                }
+                Some(Ok(entry)) => entry.get("time").unwrap_or_default(),
+            };
+            if pick.is_none_or(|(_, earliest)| time < earliest) {
+                pick = Some((i, time));
+            }
+        }
+        let (i, _) = pick?;
+        let next = self.sources[i].next();
+        std::mem::replace(&mut self.heads[i], next)
+    }
+}
+
+/// What purge does with the records holding the identifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PurgeAction {
+    /// Drop the whole record.
+    Remove,
+    /// Keep the record with the identifier replaced by `[REDACTED]`.
+    Redact,
+}
+
+/// The records purge should erase, see `purge`.
+///
+/// The id counts where it stands on its own: as a field value, `user=u-42`
+/// or `"user":"u-42"`, or as a word of the message or error, `closed
+/// u-42's account`. It doesn't count inside a longer id, `user=u-421`.
+#[derive(Debug, Clone)]
+pub struct Matcher {
+    id: String,
+    /// How the id looks inside a quoted JSON string.
+    escaped: String,
+    action: PurgeAction,
+}
+
+impl Matcher {
+    /// Remove every record that mentions `id`.
+    pub fn remove(id: &str) -> Matcher {
+        Matcher::new(id, PurgeAction::Remove)
+    }
+
+    /// Redact `id` wherever it is mentioned, keeping the records.
+    pub fn redact(id: &str) -> Matcher {
+        Matcher::new(id, PurgeAction::Redact)
+    }
+
+    fn new(id: &str, action: PurgeAction) -> Matcher {
+        let mut escaped = String::new();
+        push_json_str(&mut escaped, id);
+        Matcher {
+            id: id.to_string(),
+            escaped: escaped[1..escaped.len() - 1].to_string(),
+            action,
+        }
+    }
+
+    /// Where the id is mentioned in `record`, and what to redact it with.
+    fn find(&self, record: &str, format: LogFormat) -> Vec<(Range<usize>, &'static str)> {
+        if format != LogFormat::Json {
+            return words(record, &self.id)
+                .into_iter()
+                .map(|span| (span, REDACTED))
+                .collect();
+        }
+        let mut found = Vec::new();
+        for value in json_values(record) {
+            let text = &record[value.clone()];
+            if !text.starts_with('"') {
+                // A number or the like, which a string has to replace.
+                if text == self.id {
+                    found.push((value, QUOTED_REDACTED));
+                }
+                continue;
+            }
+            for span in words(text, &self.escaped) {
+                found.push((value.start + span.start..value.start + span.end, REDACTED));
+            }
+        }
+        found
+    }
+
+    fn redact_in(&self, record: &str, found: &[(Range<usize>, &str)]) -> String {
+        let mut out = record.to_string();
+        for (span, with) in found.iter().rev() {
+            out.replace_range(span.clone(), with);
+        }
+        out
+    }
+}
+
+/// Where `id` is in `text` with no letter, digit, `-` or `_` right before
+/// or after it, so it isn't part of a longer id. An escape like `\n` in a
+/// JSON string counts as a space.
+fn words(text: &str, id: &str) -> Vec<Range<usize>> {
+    let in_word = |c: char| c.is_alphanumeric() || c == '-' || c == '_';
+    text.match_indices(id)
+        .map(|(at, _)| at..at + id.len())
+        .filter(|span| {
+            let before = &text[..span.start];
+            let escape = before.len() >= 2 && before[..before.len() - 1].ends_with('\\');
+            let before = before.chars().next_back().filter(|_| !escape);
+            let after = text[span.end..].chars().next();
+            !before.is_some_and(in_word) && !after.is_some_and(in_word)
+        })
+        .collect()
+}
+
+/// The spans of the values in a flat JSON object, quotes included.
+fn json_values(line: &str) -> Vec<Range<usize>> {
+    let bytes = line.as_bytes();
+    let mut spans = Vec::new();
+    let mut in_value = false;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => {
+                let start = i;
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+                if std::mem::take(&mut in_value) {
+                    spans.push(start..(i + 1).min(bytes.len()));
+                }
+            }
+            b':' => in_value = true,
+            c if in_value && !c.is_ascii_whitespace() => {
+                let start = i;
+                while i < bytes.len() && !matches!(bytes[i], b',' | b'}') {
+                    i += 1;
+                }
+                spans.push(start..start + line[start..i].trim_end().len());
+                in_value = false;
+                continue;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    spans
+}
+
+const REDACTED: &str = "[REDACTED]";
+const QUOTED_REDACTED: &str = "\"[REDACTED]\"";
+
+/// What purge did to a log.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PurgeSummary {
+    /// Records read, matching or not.
+    pub records: usize,
+    pub removed: usize,
+    pub redacted: usize,
+}
+
+impl fmt::Display for PurgeSummary {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{} records, {} removed, {} redacted",
+            self.records, self.removed, self.redacted
+        )
+    }
+}
+
+/// Rewrite a Json or Text log without a user's records, for erasure
+/// requests.
+///
+/// The purged copy is written next to the log and renamed over it, so
+/// readers see either the old file or the new one. A Text record is its
+/// line plus the error lines under it. Don't purge a file a Logger still
+/// has open, its writes would go to the old file. Rotate first.
+///
+/// ```no_run
+/// use little_logger::log::reader::{self, Matcher};
+///
+/// let summary = reader::purge("llog.txt.1", &Matcher::redact("user-4711")).unwrap();
+/// println!("llog.txt.1: {summary}");
+/// ```
+pub fn purge<P: AsRef<Path>>(path: P, matcher: &Matcher) -> io::Result<PurgeSummary> {
+    let path = path.as_ref();
+    let mut src = BufReader::new(File::open(path)?);
+    let mut header = String::new();
+    if src.fill_buf()?.starts_with(header::PREFIX.as_bytes()) {
+        src.read_line(&mut header)?;
+    }
+    let format = match Header::parse(header.trim_end()) {
+        Some(header) => header.format,
+        None if src.fill_buf()?.first() == Some(&b'{') => LogFormat::Json,
+        None => LogFormat::Text,
+    };
+    if !matches!(format, LogFormat::Json | LogFormat::Text) {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("can't purge {} logs", format.name()),
+        ));
+    }
+
+    let mut staged = path.as_os_str().to_owned();
+    staged.push(".purge");
+    let staged = PathBuf::from(staged);
+    let purged = write_purged(src, &staged, &header, format, matcher).and_then(|summary| {
+        fs::rename(&staged, path)?;
+        Ok(summary)
+    });
+    if purged.is_err() {
+        let _ = fs::remove_file(&staged);
+    }
+    purged
+}
+
+/// Write the records of `src` that survive `matcher` to `staged`.
+fn write_purged(
+    mut src: BufReader<File>,
+    staged: &Path,
+    header: &str,
+    format: LogFormat,
+    matcher: &Matcher,
+) -> io::Result<PurgeSummary> {
+    let permissions = src.get_ref().metadata()?.permissions();
+    let mut out = BufWriter::new(File::create(staged)?);
+    out.write_all(header.as_bytes())?;
+    let mut summary = PurgeSummary::default();
+    let mut record = String::new();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let more = src.read_line(&mut line)? > 0;
+        // Text records carry on over the lines that don't open a new one.
+        let next_record = !more || format == LogFormat::Json || line.starts_with('[');
+        if next_record && !record.is_empty() {
+            summary.records += 1;
+            let found = matcher.find(&record, format);
+            match matcher.action {
+                _ if found.is_empty() => out.write_all(record.as_bytes())?,
+                PurgeAction::Remove => summary.removed += 1,
+                PurgeAction::Redact => {
+                    summary.redacted += 1;
+                    out.write_all(matcher.redact_in(&record, &found).as_bytes())?;
+                }
+            }
+            record.clear();
+        }
+        if !more {
+            break;
+        }
+        record.push_str(&line);
+    }
+    let out = out.into_inner().map_err(|e| e.into_error())?;
+    out.set_permissions(permissions)?;
+    out.sync_all()?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::format::Record;
+    use crate::log::Level;
+
+    #[test]
+    fn test_reads_back_msgpack_records() {
+        let long = "x".repeat(300);
+        let record = Record {
+            level: Level::Warn,
+            level_name: "WARN ",
+            target: Some("my_app::db"),
+            label: "LLOG",
+            date_time: "18:37:22",
+            msg: &long,
+            err: None,
+            err_kind: None,
+            err_code: None,
+            template: None,
+            kv: &[],
+            typed: &[],
+            static_fields: &[],
+            span_id: None,
+            span_depth: 0,
+            event_id: None,
+            session_id: None,
+            raw: false,
+            source: None,
+            change: None,
+        };
+        let mut bytes = msgpack::encode(&record);
+        bytes.extend(msgpack::encode(&record));
+
+        let entries: Vec<Entry> = MsgPackReader::new(&bytes[..])
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].get("level"), Some("WARN"));
+        assert_eq!(entries[0].get("target"), Some("my_app::db"));
+        assert_eq!(entries[1].get("message"), Some(long.as_str()));
+    }
+
+    #[test]
+    fn test_msgpack_length_is_capped() {
+        let mut frame = u32::MAX.to_be_bytes().to_vec();
+        frame.extend_from_slice(b"\x81");
+        let err = MsgPackReader::new(&frame[..]).next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_reads_back_cbor_records() {
+        let record = Record {
+            level: Level::Error,
+            level_name: "ERROR",
+            target: None,
+            label: "LLOG",
+            date_time: "18:37:22",
+            msg: &"y".repeat(70_000),
+            err: Some("disk full"),
+            err_kind: None,
+            err_code: None,
+            template: None,
+            kv: &[],
+            typed: &[],
+            static_fields: &[],
+            span_id: None,
+            span_depth: 0,
+            event_id: None,
+            session_id: None,
+            raw: false,
+            source: None,
+            change: None,
+        };
+        let bytes = cbor::encode(&record);
+        assert_eq!(bytes[..3], [0xd9, 0xd9, 0xf7]);
+
+        let entries: Vec<Entry> = CborReader::new(&bytes[..])
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].get("message").map(str::len), Some(70_000));
+        assert_eq!(entries[0].get("error"), Some("disk full"));
+    }
+
+    #[test]
+    fn test_seek_to_finds_first_line_at_time() {
+        let path = std::env::temp_dir().join("llog_seek_test.txt");
+        let mut log = String::new();
+        for minute in 0..1440 {
+            log.push_str(&format!(
+                "[LLOG]::[2024-05-21 {:02}:{:02}:00]::[INFO] -> tick {}\n",
+                minute / 60,
+                minute % 60,
+                minute
+            ));
+        }
+        fs::write(&path, log).unwrap();
+
+        let mut reader = SeekableReader::open(&path).unwrap();
+        let time = NaiveDateTime::parse_from_str("2024-05-21 20:30:30", "%Y-%m-%d %H:%M:%S");
+        reader.seek_to(time.unwrap()).unwrap();
+        let entry = reader.next().unwrap().unwrap();
+        assert_eq!(entry.get("time"), Some("2024-05-21 20:31:00"));
+        assert!(entry.get("line").unwrap().ends_with("tick 1231"));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_merge_interleaves_by_time() {
+        let entries = |times: &[&str]| -> Entries {
+            let lines: Vec<io::Result<Entry>> = times
+                .iter()
+                .map(|time| {
+                    Ok(Entry {
+                        fields: vec![(String::from("time"), time.to_string())],
+                    })
+                })
+                .collect();
+            Box::new(lines.into_iter())
+        };
+        let merged: Vec<String> = merge(vec![entries(&["01", "04"]), entries(&["02", "03"])])
+            .map(|entry| entry.unwrap().get("time").unwrap().to_string())
+            .collect();
+        assert_eq!(merged, ["01", "02", "03", "04"]);
+    }
+
+    #[test]
+    fn test_merge_files_reads_headerless_text() {
+        let dir = std::env::temp_dir();
+        let decorated = dir.join("llog_merge_text_test.1.txt");
+        let bare = dir.join("llog_merge_text_test.2.txt");
+        fs::write(
+            &decorated,
+            "[LLOG]::[2024-05-21 18:37:22]::[INFO] -> one\n\
+             [LLOG]::[2024-05-21 18:37:24]::[INFO] -> three\n",
+        )
+        .unwrap();
+        fs::write(&bare, "two\n").unwrap();
+
+        let lines: Vec<String> = merge_files(&[&decorated, &bare])
+            .unwrap()
+            .map(|entry| entry.unwrap().get("line").unwrap().to_string())
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines.iter().any(|line| line == "two"));
+        let _ = fs::remove_file(&decorated);
+        let _ = fs::remove_file(&bare);
+    }
+
+    #[test]
+    fn test_reads_back_json_lines() {
+        let log = "{\"label\":\"LLOG\",\"message\":\"say \\\"hi\\\"\\n\",\"count\":3}\n\n{}\n";
+        let entries: Vec<Entry> = JsonReader::new(log.as_bytes())
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].get("message"), Some("say \"hi\"\n"));
+        assert_eq!(entries[0].get("count"), Some("3"));
+        assert_eq!(entries[1].fields().count(), 0);
+    }
+
+    #[test]
+    fn test_purge_removes_or_redacts_a_user() {
+        let path = std::env::temp_dir().join("llog_purge_test.txt");
+        let text = "[LLOG]::[INFO] -> login user=u-42\n\
+                    [LLOG]::[ERROR] -> failed for u-7\n\
+                    token expired for u-42\n\
+                    [LLOG]::[INFO] -> user u-42 closed their account\n\
+                    [LLOG]::[INFO] -> login user=u-9\n";
+        fs::write(&path, text).unwrap();
+        let summary = purge(&path, &Matcher::remove("u-42")).unwrap();
+        assert_eq!(
+            (summary.records, summary.removed, summary.redacted),
+            (4, 3, 0)
+        );
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "[LLOG]::[INFO] -> login user=u-9\n"
+        );
+
+        fs::write(
+            &path,
+            "{\"message\":\"hi\",\"user\":\"\\\"bob\\\"\"}\n{\"message\":\"hi\"}\n",
+        )
+        .unwrap();
+        let summary = purge(&path, &Matcher::redact("\"bob\"")).unwrap();
+        assert_eq!(summary.to_string(), "2 records, 0 removed, 1 redacted");
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "{\"message\":\"hi\",\"user\":\"[REDACTED]\"}\n{\"message\":\"hi\"}\n"
+        );
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_purge_leaves_longer_ids_alone() {
+        let path = std::env::temp_dir().join("llog_purge_prefix_test.txt");
+        let text = "[LLOG]::[INFO] -> login user=user-47\n\
+                    [LLOG]::[INFO] -> login user=user-471\n\
+                    [LLOG]::[INFO] -> user-47 said hi\n";
+        fs::write(&path, text).unwrap();
+        let summary = purge(&path, &Matcher::redact("user-47")).unwrap();
+        assert_eq!(summary.to_string(), "3 records, 0 removed, 2 redacted");
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "[LLOG]::[INFO] -> login user=[REDACTED]\n\
+             [LLOG]::[INFO] -> login user=user-471\n\
+             [LLOG]::[INFO] -> [REDACTED] said hi\n"
+        );
+
+        fs::write(
+            &path,
+            "{\"user\":\"user-471\",\"message\":\"hi\"}\n\
+             {\"message\":\"closed\\nuser-47's account\"}\n",
+        )
+        .unwrap();
+        let summary = purge(&path, &Matcher::redact("user-47")).unwrap();
+        assert_eq!(summary.redacted, 1);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "{\"user\":\"user-471\",\"message\":\"hi\"}\n\
+             {\"message\":\"closed\\n[REDACTED]'s account\"}\n"
+        );
+
+        fs::write(&path, "{\"id\":47}\n{\"id\":470}\n").unwrap();
+        purge(&path, &Matcher::redact("47")).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "{\"id\":\"[REDACTED]\"}\n{\"id\":470}\n"
+        );
+        let _ = fs::remove_file(&path);
+    }
+}
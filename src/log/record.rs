@@ -0,0 +1,91 @@
+use std::fmt;
+use std::sync::Arc;
+
+use super::{Level, Line, Logger};
+
+/// A single record put together a piece at a time, see `Logger::record`.
+#[derive(Debug)]
+#[must_use = "nothing is logged until `log` is called"]
+pub struct RecordBuilder<'l> {
+    logger: &'l mut Logger,
+    level: Level,
+    target: Option<Arc<str>>,
+    fields: Vec<(String, String)>,
+    /// The fields added as numbers or bools.
+    typed: Vec<String>,
+    ts_format: Option<String>,
+}
+
+impl<'l> RecordBuilder<'l> {
+    pub(crate) fn new(logger: &'l mut Logger, level: Level) -> RecordBuilder<'l> {
+        RecordBuilder {
+            logger,
+            level,
+            target: None,
+            fields: Vec::new(),
+            typed: Vec::new(),
+            ts_format: None,
+        }
+    }
+
+    /// Log on behalf of this target.
+    pub fn target(mut self, target: &str) -> Self {
+        self.target = Some(self.logger.strings.intern(target));
+        self
+    }
+
+    /// Attach a field to the record.
+    pub fn field<V: fmt::Display>(mut self, key: &str, value: V) -> Self {
+        self.fields.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Attach a whole number, which JSON writes as a number rather than a
+    /// string so it can be summed and averaged as is.
+    pub fn field_i64(mut self, key: &str, value: i64) -> Self {
+        self.typed.push(key.to_string());
+        self.field(key, value)
+    }
+
+    /// Attach a number, see field_i64. NaN and the infinities have no JSON
+    /// number, they are written as strings.
+    pub fn field_f64(mut self, key: &str, value: f64) -> Self {
+        if value.is_finite() {
+            self.typed.push(key.to_string());
+        }
+        self.field(key, value)
+    }
+
+    /// Attach a bool, which JSON writes as `true` or `false`.
+    pub fn field_bool(mut self, key: &str, value: bool) -> Self {
+        self.typed.push(key.to_string());
+        self.field(key, value)
+    }
+
+    /// Format this record's date/time with its own chrono format, like
+    /// `%H:%M:%S%.9f` for nanoseconds on a perf trace. The other records
+    /// keep the logger's.
+    ///
+    /// Default: the LoggerOpts dt_format
+    pub fn ts_format(mut self, format: &str) -> Self {
+        self.ts_format = Some(format.to_string());
+        self
+    }
+
+    /// Write the record with `msg` as its message.
+    pub fn log<S: Into<String>>(self, msg: S) {
+        let kv: Vec<(&str, &str)> = self
+            .fields
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        let typed: Vec<&str> = self.typed.iter().map(String::as_str).collect();
+        self.logger.write_log_line(Line {
+            target: self.target.as_deref(),
+            kv: &kv,
+            typed: &typed,
+            ts_format: self.ts_format.as_deref(),
+            ..Line::new(self.level, &msg.into())
+        });
+    }
+}
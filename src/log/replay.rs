@@ -0,0 +1,350 @@
+//! Whole records kept in a file and formatted again later, to try a
+//! format change on real traffic, see LoggerOpts::add_replay_sink.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use super::format::{Layout, Record};
+use super::sink::Sink;
+use super::{Level, LogFormat, LoggerOpts};
+
+/// What every replay file starts with. The number goes up if the encoding
+/// below ever changes.
+const MAGIC: &[u8; 8] = b"LLREPLY1";
+
+// Each part of a record is a tag byte, a big endian u32 length and that
+// many bytes. Parts that repeat, like the fields, are written once per
+// value and parts that are None not at all. Readers skip tags they don't
+// know, so new parts can be added.
+const LEVEL: u8 = 1;
+const LEVEL_NAME: u8 = 2;
+const TARGET: u8 = 3;
+const LABEL: u8 = 4;
+const DATE_TIME: u8 = 5;
+const MSG: u8 = 6;
+const ERR: u8 = 7;
+const ERR_KIND: u8 = 8;
+const ERR_CODE: u8 = 9;
+const TEMPLATE: u8 = 10;
+const KV_KEY: u8 = 11;
+const KV_VALUE: u8 = 12;
+const TYPED: u8 = 13;
+const STATIC_KEY: u8 = 14;
+const STATIC_VALUE: u8 = 15;
+const SPAN_ID: u8 = 16;
+const SPAN_DEPTH: u8 = 17;
+const EVENT_ID: u8 = 18;
+const SESSION_ID: u8 = 19;
+const RAW: u8 = 20;
+const OLD: u8 = 21;
+const NEW: u8 = 22;
+const SOURCE_FILE: u8 = 23;
+const SOURCE_LINE: u8 = 24;
+const SOURCE_FN: u8 = 25;
+
+/// Appends every record, whole, to a replay file.
+#[derive(Debug)]
+pub(crate) struct ReplaySink {
+    path: PathBuf,
+    /// Opened on the first record.
+    out: Option<BufWriter<File>>,
+}
+
+impl ReplaySink {
+    pub(crate) fn new(path: PathBuf) -> ReplaySink {
+        ReplaySink { path, out: None }
+    }
+}
+
+impl Sink for ReplaySink {
+    fn write_record(&mut self, record: &Record, _: &Layout) -> io::Result<()> {
+        let out = match &mut self.out {
+            Some(out) => out,
+            None => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)?;
+                let mut out = BufWriter::new(file);
+                if out.get_ref().metadata()?.len() == 0 {
+                    out.write_all(MAGIC)?;
+                }
+                self.out.insert(out)
+            }
+        };
+        let body = encode(record);
+        out.write_all(&(body.len() as u32).to_be_bytes())?;
+        out.write_all(&body)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.out {
+            Some(out) => out.flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "replay"
+    }
+}
+
+fn encode(record: &Record) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut part = |tag: u8, value: &str| {
+        out.push(tag);
+        out.extend_from_slice(&(value.len() as u32).to_be_bytes());
+        out.extend_from_slice(value.as_bytes());
+    };
+    part(LEVEL, record.level.as_str());
+    part(LEVEL_NAME, record.level_name);
+    part(LABEL, record.label);
+    part(DATE_TIME, record.date_time);
+    part(MSG, record.msg);
+    let optional = [
+        (TARGET, record.target),
+        (ERR, record.err),
+        (ERR_KIND, record.err_kind),
+        (ERR_CODE, record.err_code),
+        (TEMPLATE, record.template),
+        (SPAN_ID, record.span_id),
+        (EVENT_ID, record.event_id),
+        (SESSION_ID, record.session_id),
+    ];
+    for (tag, value) in optional {
+        if let Some(value) = value {
+            part(tag, value);
+        }
+    }
+    for (key, value) in record.kv {
+        part(KV_KEY, key);
+        part(KV_VALUE, value);
+    }
+    for key in record.typed {
+        part(TYPED, key);
+    }
+    for (key, value) in record.static_fields {
+        part(STATIC_KEY, key);
+        part(STATIC_VALUE, value);
+    }
+    part(SPAN_DEPTH, &record.span_depth.to_string());
+    if record.raw {
+        part(RAW, "");
+    }
+    if let Some((old, new)) = record.change {
+        part(OLD, old);
+        part(NEW, new);
+    }
+    if let Some((file, line, function)) = record.source {
+        part(SOURCE_FILE, file);
+        part(SOURCE_LINE, &line.to_string());
+        part(SOURCE_FN, function);
+    }
+    out
+}
+
+/// A record read back from a replay file, owning its parts.
+#[derive(Debug, Default)]
+struct Replayed {
+    level: Option<Level>,
+    level_name: String,
+    target: Option<String>,
+    label: String,
+    date_time: String,
+    msg: String,
+    err: Option<String>,
+    err_kind: Option<String>,
+    err_code: Option<String>,
+    template: Option<String>,
+    kv: Vec<(String, String)>,
+    typed: Vec<String>,
+    static_fields: Vec<(String, String)>,
+    span_id: Option<String>,
+    span_depth: usize,
+    event_id: Option<String>,
+    session_id: Option<String>,
+    raw: bool,
+    old: Option<String>,
+    new: Option<String>,
+    source_file: Option<String>,
+    source_line: u32,
+    source_fn: String,
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn decode(mut body: &[u8]) -> io::Result<Replayed> {
+    let mut record = Replayed::default();
+    while let [tag, rest @ ..] = body {
+        let (len, rest) = rest
+            .split_first_chunk::<4>()
+            .ok_or_else(|| invalid("replay record cut short"))?;
+        let len = u32::from_be_bytes(*len) as usize;
+        if rest.len() < len {
+            return Err(invalid("replay record cut short"));
+        }
+        let value = std::str::from_utf8(&rest[..len])
+            .map_err(|_| invalid("replay record isn't UTF-8"))?
+            .to_string();
+        body = &rest[len..];
+        match *tag {
+            LEVEL => {
+                let level = value.parse().map_err(|_| invalid("unknown level"))?;
+                record.level = Some(level);
+            }
+            LEVEL_NAME => record.level_name = value,
+            TARGET => record.target = Some(value),
+            LABEL => record.label = value,
+            DATE_TIME => record.date_time = value,
+            MSG => record.msg = value,
+            ERR => record.err = Some(value),
+            ERR_KIND => record.err_kind = Some(value),
+            ERR_CODE => record.err_code = Some(value),
+            TEMPLATE => record.template = Some(value),
+            KV_KEY => record.kv.push((value, String::new())),
+            KV_VALUE => match record.kv.last_mut() {
+                Some((_, kv_value)) => *kv_value = value,
+                None => return Err(invalid("field value without a key")),
+            },
+            TYPED => record.typed.push(value),
+            STATIC_KEY => record.static_fields.push((value, String::new())),
+            STATIC_VALUE => match record.static_fields.last_mut() {
+                Some((_, static_value)) => *static_value = value,
+                None => return Err(invalid("field value without a key")),
+            },
+            SPAN_ID => record.span_id = Some(value),
+            SPAN_DEPTH => record.span_depth = value.parse().unwrap_or_default(),
+            EVENT_ID => record.event_id = Some(value),
+            SESSION_ID => record.session_id = Some(value),
+            RAW => record.raw = true,
+            OLD => record.old = Some(value),
+            NEW => record.new = Some(value),
+            SOURCE_FILE => record.source_file = Some(value),
+            SOURCE_LINE => record.source_line = value.parse().unwrap_or_default(),
+            SOURCE_FN => record.source_fn = value,
+            _ => {}
+        }
+    }
+    Ok(record)
+}
+
+/// Format every record of a replay file again and write them to `out`, as
+/// a sink in `format` with the separators and severity map of `opts`
+/// would. Returns the number of records.
+///
+/// The times, event ids and fields are the ones the records were logged
+/// with, so the output can be diffed against a known good copy.
+///
+/// ```no_run
+/// use little_logger::log::{replay, LogFormat, LoggerOpts};
+///
+/// let mut out = Vec::new();
+/// let opts = LoggerOpts::new().set_separators(" | ", " | ");
+/// replay::replay("traffic.llreplay", &opts, LogFormat::Json, &mut out).unwrap();
+/// ```
+pub fn replay<P: AsRef<Path>>(
+    path: P,
+    opts: &LoggerOpts,
+    format: LogFormat,
+    out: &mut dyn Write,
+) -> io::Result<usize> {
+    let mut src = BufReader::new(File::open(path)?);
+    let mut magic = [0u8; 8];
+    src.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid("not a replay file"));
+    }
+    let format = opts.sink_format(format);
+    let mut count = 0;
+    let mut len = [0u8; 4];
+    loop {
+        match src.read_exact(&mut len) {
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(count),
+            other => other?,
+        }
+        let mut body = vec![0u8; u32::from_be_bytes(len) as usize];
+        src.read_exact(&mut body)?;
+        let replayed = decode(&body)?;
+        let kv: Vec<(&str, &str)> = replayed
+            .kv
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        let typed: Vec<&str> = replayed.typed.iter().map(String::as_str).collect();
+        let record = Record {
+            level: replayed
+                .level
+                .ok_or_else(|| invalid("record without a level"))?,
+            level_name: &replayed.level_name,
+            target: replayed.target.as_deref(),
+            label: &replayed.label,
+            date_time: &replayed.date_time,
+            msg: &replayed.msg,
+            err: replayed.err.as_deref(),
+            err_kind: replayed.err_kind.as_deref(),
+            err_code: replayed.err_code.as_deref(),
+            template: replayed.template.as_deref(),
+            kv: &kv,
+            typed: &typed,
+            static_fields: &replayed.static_fields,
+            span_id: replayed.span_id.as_deref(),
+            span_depth: replayed.span_depth,
+            event_id: replayed.event_id.as_deref(),
+            session_id: replayed.session_id.as_deref(),
+            raw: replayed.raw,
+            change: replayed.old.as_deref().zip(replayed.new.as_deref()),
+            source: replayed
+                .source_file
+                .as_deref()
+                .map(|file| (file, replayed.source_line, replayed.source_fn.as_str())),
+        };
+        out.write_all(&format.encode(&record, &opts.layout))?;
+        count += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::{Level, Logger};
+
+    #[test]
+    fn test_replay_formats_the_records_again() {
+        let path = std::env::temp_dir().join("llog_replay_test.llreplay");
+        let _ = std::fs::remove_file(&path);
+
+        let opts = LoggerOpts::new()
+            .set_log_type("null")
+            .add_static_field("env", "prod")
+            .add_replay_sink(&path)
+            .deterministic();
+        let mut logger = Logger::new(opts);
+        logger
+            .record(Level::Warn)
+            .target("api")
+            .field_i64("ms", 950)
+            .log("slow");
+        logger.log_change("pool", 4, 8);
+        drop(logger);
+
+        let mut out = Vec::new();
+        let opts = LoggerOpts::new().set_separators(" | ", ": ");
+        let count = replay(&path, &opts, LogFormat::Text, &mut out).unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "[LLOG] | [1970-01-01 00:00:00] | [WARN] | [api]: slow ms=950\n\
+             [LLOG] | [1970-01-01 00:00:00] | [INFO]: pool: 4 -> 8\n"
+        );
+
+        let mut out = Vec::new();
+        replay(&path, &opts, LogFormat::Json, &mut out).unwrap();
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .starts_with(r#"{"schema":"1","label":"LLOG","time":"1970-01-01 00:00:00","#));
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,276 @@
+//! Moving a full log file out of the way and describing what was in it.
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use super::header;
+use super::reader::{self, CborReader, MsgPackReader};
+use super::LogFormat;
+
+/// Called with the live path and where the full file went after each
+/// rotation, see Logger::on_rotate.
+pub(crate) struct RotateHook(pub(crate) Callback);
+
+type Callback = Box<dyn FnMut(&Path, &Path) + Send>;
+
+impl fmt::Debug for RotateHook {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("RotateHook")
+    }
+}
+
+/// Move `path` to `path.N` one past the highest number already there,
+/// so lower numbers are older. With an archive dir it goes there instead,
+/// the dir being created if need be.
+pub(crate) fn rotate(path: &Path, archive: Option<&Path>) -> io::Result<PathBuf> {
+    let base = archived(path, archive)?;
+    let next = rotated_files(&base)?.last().map_or(1, |(n, _)| n + 1);
+    let rotated = numbered(&base, next);
+    move_file(path, &rotated)?;
+    let meta = reader::meta_path(path);
+    if meta.exists() {
+        move_file(&meta, &reader::meta_path(&rotated))?;
+    }
+    Ok(rotated)
+}
+
+/// Delete all but the newest `keep` rotated files, with their sidecars.
+pub(crate) fn prune(path: &Path, archive: Option<&Path>, keep: usize) -> io::Result<()> {
+    let base = archived(path, archive)?;
+    let rotated = rotated_files(&base)?;
+    for (_, old) in &rotated[..rotated.len().saturating_sub(keep)] {
+        fs::remove_file(old)?;
+        let _ = fs::remove_file(manifest_path(old));
+        let _ = fs::remove_file(reader::meta_path(old));
+    }
+    Ok(())
+}
+
+/// The log path as it would be named inside the archive dir.
+fn archived(path: &Path, archive: Option<&Path>) -> io::Result<PathBuf> {
+    match archive {
+        Some(dir) => {
+            fs::create_dir_all(dir)?;
+            Ok(dir.join(path.file_name().unwrap_or_default()))
+        }
+        None => Ok(path.to_path_buf()),
+    }
+}
+
+/// Every `base.N` that exists, oldest first.
+fn rotated_files(base: &Path) -> io::Result<Vec<(u32, PathBuf)>> {
+    let dir = match base.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let prefix = format!(
+        "{}.",
+        base.file_name().unwrap_or_default().to_string_lossy()
+    );
+    let mut found = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let name = entry?.file_name();
+        let number = name
+            .to_str()
+            .and_then(|name| name.strip_prefix(&prefix))
+            .and_then(|n| n.parse().ok());
+        if let Some(n) = number {
+            found.push((n, numbered(base, n)));
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+/// Rename, or copy and delete when the archive is on another filesystem.
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).is_err() {
+        fs::copy(from, to)?;
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+fn numbered(path: &Path, n: u32) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{n}"));
+    PathBuf::from(name)
+}
+
+/// Point `llog.txt.current` at the active file, so `tail -F llog.txt.current`
+/// keeps following whatever the log is called.
+///
+/// A symlink on unix. Elsewhere a hard link, or a copy if even that fails.
+pub(crate) fn link_current(path: &Path) -> io::Result<()> {
+    let link = current_path(path);
+    let mut staged = link.as_os_str().to_owned();
+    staged.push(".tmp");
+    let staged = PathBuf::from(staged);
+    let _ = fs::remove_file(&staged);
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(path.file_name().unwrap_or_default(), &staged)?;
+    #[cfg(not(unix))]
+    if fs::hard_link(path, &staged).is_err() {
+        fs::copy(path, &staged)?;
+    }
+    // Renaming over the old link means tail never sees it missing.
+    fs::rename(&staged, &link)
+}
+
+/// `llog.txt` becomes `llog.txt.current`, so `app.log` and `app.json`
+/// side by side get a link each.
+pub(crate) fn current_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".current");
+    PathBuf::from(name)
+}
+
+/// Where the manifest of a rotated file goes, `llog.txt.1.manifest`.
+pub(crate) fn manifest_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".manifest");
+    PathBuf::from(name)
+}
+
+/// Write the SHA-256 and record count of a finished file next to it.
+///
+/// ```text
+/// file=llog.txt.1
+/// sha256=9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08
+/// records=1200
+/// bytes=96000
+/// ```
+pub(crate) fn write_manifest(path: &Path, format: LogFormat) -> io::Result<()> {
+    let mut hasher = Sha256::new();
+    let mut src = File::open(path)?;
+    let mut buf = [0; 8192];
+    let mut bytes = 0;
+    loop {
+        let read = src.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+        bytes += read as u64;
+    }
+    let hex: String = hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let records = count_records(path, format)?;
+    let mut manifest = File::create(manifest_path(path))?;
+    write!(
+        manifest,
+        "file={name}\nsha256={hex}\nrecords={records}\nbytes={bytes}\n"
+    )
+}
+
+/// How many records the file at `path` holds.
+pub(crate) fn count_records(path: &Path, format: LogFormat) -> io::Result<usize> {
+    let src = BufReader::new(File::open(path)?);
+    match format {
+        LogFormat::MsgPack => Ok(MsgPackReader::new(src).count()),
+        LogFormat::Cbor => Ok(CborReader::new(src).count()),
+        // Every pretty record ends with a closing brace on its own line.
+        LogFormat::JsonPretty => count_lines(src, |line| line == "}"),
+        // The lines after the first of a multi-line message or error don't
+        // start with the label.
+        LogFormat::Text => count_lines(src, |line| line.starts_with('[')),
+        _ => count_lines(src, |line| {
+            !line.trim().is_empty() && !line.starts_with(header::PREFIX)
+        }),
+    }
+}
+
+fn count_lines<R: BufRead>(src: R, is_record: impl Fn(&str) -> bool) -> io::Result<usize> {
+    let mut count = 0;
+    for line in src.lines() {
+        if is_record(&line?) {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotate_picks_next_number() {
+        let path = std::env::temp_dir().join("llog_rotate_test.txt");
+        fs::write(numbered(&path, 1), "older\n").unwrap();
+        fs::write(&path, "[a]\n[b]\n").unwrap();
+
+        let rotated = rotate(&path, None).unwrap();
+        assert_eq!(rotated, numbered(&path, 2));
+        assert!(!path.exists());
+
+        write_manifest(&rotated, LogFormat::Text).unwrap();
+        let manifest = fs::read_to_string(manifest_path(&rotated)).unwrap();
+        assert!(manifest.contains(
+            "sha256=a926d7e138ba943862fa4d87e62740f196e4b7bfc952ac248908179b1b0c33b1\nrecords=2\nbytes=8\n"
+        ));
+        for path in [numbered(&path, 1), rotated.clone(), manifest_path(&rotated)] {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn test_multi_line_text_record_counts_once() {
+        let path = std::env::temp_dir().join("llog_count_records_test.txt");
+        fs::write(
+            &path,
+            "#little_logger version=1 format=text dt_format=\n\
+             [LLOG]::[2024-05-21]::[ERROR] -> query failed\n\
+             \x20 caused by: timeout\n\
+             \n\
+             [LLOG]::[2024-05-21]::[INFO] -> retrying\n",
+        )
+        .unwrap();
+
+        assert_eq!(count_records(&path, LogFormat::Text).unwrap(), 2);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_current_link_follows_file() {
+        let path = std::env::temp_dir().join("llog_current_test.txt");
+        fs::write(&path, "live\n").unwrap();
+        link_current(&path).unwrap();
+        link_current(&path).unwrap();
+
+        let link = current_path(&path);
+        assert_eq!(
+            link,
+            std::env::temp_dir().join("llog_current_test.txt.current")
+        );
+        assert_eq!(fs::read_to_string(&link).unwrap(), "live\n");
+        let _ = fs::remove_file(&link);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_archive_dir_keeps_newest() {
+        let path = std::env::temp_dir().join("llog_archive_test.txt");
+        let archive = std::env::temp_dir().join("llog_archive_test");
+        let _ = fs::remove_dir_all(&archive);
+        for n in 0..3 {
+            fs::write(&path, format!("{n}\n")).unwrap();
+            rotate(&path, Some(&archive)).unwrap();
+        }
+        prune(&path, Some(&archive), 2).unwrap();
+
+        let base = archive.join("llog_archive_test.txt");
+        assert!(!numbered(&base, 1).exists());
+        assert_eq!(fs::read_to_string(numbered(&base, 3)).unwrap(), "2\n");
+        let _ = fs::remove_dir_all(&archive);
+    }
+}
@@ -0,0 +1,196 @@
+//! Uploading rotated log files to an S3-compatible bucket.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use super::internal::Problems;
+use super::rotate;
+use super::sigv4::{self, uri_encode, Credentials, Request};
+
+/// The longest one upload may take before it counts as failed.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Where and how to upload rotated files, see Logger::upload_rotated.
+///
+/// Requests are signed with AWS Signature Version 4, which MinIO, R2 and
+/// the other S3 lookalikes accept too. Objects are addressed path style,
+/// `https://endpoint/bucket/prefix/llog.txt.1`.
+///
+/// ```no_run
+/// use little_logger::log::{Logger, LoggerOpts, S3Upload};
+///
+/// let mut logger = Logger::new(LoggerOpts::new().set_rotate_size(64 << 20));
+/// logger.upload_rotated(
+///     S3Upload::new("https://s3.eu-west-1.amazonaws.com", "my-logs", "eu-west-1")
+///         .prefix("web-1/")
+///         .keep_local(false),
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct S3Upload {
+    endpoint: String,
+    bucket: String,
+    region: String,
+    prefix: String,
+    access_key: String,
+    secret_key: String,
+    session_token: Option<String>,
+    retries: u32,
+    keep_local: bool,
+}
+
+impl S3Upload {
+    /// Credentials come from `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`
+    /// and, for temporary ones, `AWS_SESSION_TOKEN` unless set with
+    /// `credentials`.
+    pub fn new(endpoint: &str, bucket: &str, region: &str) -> S3Upload {
+        S3Upload {
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            bucket: bucket.to_string(),
+            region: region.to_string(),
+            prefix: String::new(),
+            access_key: std::env::var("AWS_ACCESS_KEY_ID").unwrap_or_default(),
+            secret_key: std::env::var("AWS_SECRET_ACCESS_KEY").unwrap_or_default(),
+            session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+            retries: 3,
+            keep_local: true,
+        }
+    }
+    /// Put before every object key.
+    ///
+    /// Default: empty
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+    /// Sign with these keys instead, dropping any session token taken
+    /// from the environment.
+    pub fn credentials(mut self, access_key: &str, secret_key: &str) -> Self {
+        self.access_key = access_key.to_string();
+        self.secret_key = secret_key.to_string();
+        self.session_token = None;
+        self
+    }
+    /// The session token that goes with temporary `credentials`.
+    pub fn session_token(mut self, token: &str) -> Self {
+        self.session_token = Some(token.to_string());
+        self
+    }
+    /// Try this many more times after a failed upload, waiting twice as
+    /// long each time starting from a second.
+    ///
+    /// Default: 3
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+    /// Keep the local copy after a successful upload.
+    ///
+    /// Default: true
+    pub fn keep_local(mut self, keep: bool) -> Self {
+        self.keep_local = keep;
+        self
+    }
+
+    /// Upload a rotated file and its manifest on a thread of its own,
+    /// reporting a failed upload to `problems`.
+    pub(crate) fn spawn(&self, rotated: &Path, problems: Problems) {
+        let upload = self.clone();
+        let rotated = rotated.to_path_buf();
+        thread::spawn(move || upload.run(&rotated, &problems));
+    }
+
+    fn run(&self, rotated: &Path, problems: &Problems) {
+        let manifest = rotate::manifest_path(rotated);
+        let mut files: Vec<PathBuf> = vec![rotated.to_path_buf()];
+        if manifest.exists() {
+            files.push(manifest);
+        }
+        for file in &files {
+            if let Err(e) = self.put_with_retry(file) {
+                problems.report(format!("upload of {} failed: {e}", file.display()));
+                return;
+            }
+        }
+        if !self.keep_local {
+            for file in &files {
+                let _ = fs::remove_file(file);
+            }
+        }
+    }
+
+    fn put_with_retry(&self, file: &Path) -> Result<(), String> {
+        let body = fs::read(file).map_err(|e| e.to_string())?;
+        let name = file.file_name().unwrap_or_default().to_string_lossy();
+        let key = format!("{}{name}", self.prefix);
+        let mut wait = Duration::from_secs(1);
+        let mut attempt = 0;
+        loop {
+            match self.put(&key, &body) {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt >= self.retries => return Err(e),
+                Err(_) => {
+                    thread::sleep(wait);
+                    wait *= 2;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    fn put(&self, key: &str, body: &[u8]) -> Result<(), String> {
+        let (url, headers) = self.sign(key, body);
+        let mut request = ureq::put(&url).timeout(REQUEST_TIMEOUT);
+        for (name, value) in &headers {
+            request = request.set(name, value);
+        }
+        request
+            .send_bytes(body)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    /// The URL to put `key` at and the signed headers to send with it.
+    fn sign(&self, key: &str, body: &[u8]) -> (String, Vec<(String, String)>) {
+        let path = format!("/{}/{}", uri_encode(&self.bucket), uri_encode(key));
+        let credentials = Credentials {
+            access_key: self.access_key.clone(),
+            secret_key: self.secret_key.clone(),
+            token: self.session_token.clone(),
+        };
+        let request = Request {
+            method: "PUT",
+            host: sigv4::host(&self.endpoint),
+            path: &path,
+            headers: &[],
+            body,
+        };
+        let headers = sigv4::sign(&credentials, &self.region, "s3", &request);
+        (format!("{}{path}", self.endpoint), headers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_temporary_credentials_send_the_session_token() {
+        let upload = S3Upload::new("https://s3.example.com/", "logs", "eu-west-1")
+            .credentials("AKID", "SECRET")
+            .session_token("TOKEN");
+        let (url, headers) = upload.sign("llog.txt.1", b"body");
+        assert_eq!(url, "https://s3.example.com/logs/llog.txt.1");
+        assert!(headers
+            .iter()
+            .any(|(name, value)| name == "x-amz-security-token" && value == "TOKEN"));
+
+        let upload = upload.credentials("AKID", "SECRET");
+        let (_, headers) = upload.sign("llog.txt.1", b"body");
+        assert!(!headers
+            .iter()
+            .any(|(name, _)| name == "x-amz-security-token"));
+    }
+}
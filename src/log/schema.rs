@@ -0,0 +1,105 @@
+//! The field names structured records are written with.
+//!
+//! JSON, MessagePack and CBOR records start with a `schema` field holding
+//! VERSION. Within a version fields may be added but never renamed,
+//! removed or given a different meaning, so a parser written against
+//! version 1 keeps working until the number changes. The fields a caller
+//! attaches, key/values and static fields, are theirs and not covered.
+
+/// The version in the `schema` field of every structured record.
+pub const VERSION: &str = "1";
+
+/// Fields every structured record has.
+pub const REQUIRED: &[&str] = &["schema", "label", "time", "level", "message"];
+
+/// Fields a structured record has only when they apply.
+pub const OPTIONAL: &[&str] = &[
+    "event_id",
+    "session_id",
+    "target",
+    "span_id",
+    "old",
+    "new",
+    "error",
+    "error.kind",
+    "error.code",
+    "template",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::format::Record;
+    use crate::log::Level;
+
+    /// Changing this test means breaking parsers, bump VERSION instead.
+    #[test]
+    fn test_version_1_fields_are_frozen() {
+        assert_eq!(VERSION, "1");
+        assert_eq!(REQUIRED, ["schema", "label", "time", "level", "message"]);
+        assert_eq!(
+            OPTIONAL,
+            [
+                "event_id",
+                "session_id",
+                "target",
+                "span_id",
+                "old",
+                "new",
+                "error",
+                "error.kind",
+                "error.code",
+                "template"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_records_only_use_schema_fields() {
+        let every = Record {
+            level: Level::Error,
+            level_name: "ERROR",
+            target: Some("app::db"),
+            label: "LLOG",
+            date_time: "18:37:22",
+            msg: "pool",
+            err: Some("timed out"),
+            err_kind: Some("io::Error"),
+            err_code: Some("E1042"),
+            template: Some("pool {state}"),
+            kv: &[],
+            typed: &[],
+            static_fields: &[],
+            span_id: Some("1"),
+            span_depth: 1,
+            event_id: Some("01J"),
+            session_id: Some("01H"),
+            raw: false,
+            change: Some(("10", "20")),
+            source: Some(("src/main.rs", 7, "app::main")),
+        };
+        let bare = Record {
+            level: Level::Info,
+            level_name: "INFO",
+            target: None,
+            err: None,
+            err_kind: None,
+            err_code: None,
+            template: None,
+            span_id: None,
+            event_id: None,
+            session_id: None,
+            change: None,
+            source: None,
+            ..every
+        };
+        let names: Vec<&str> = every.fields().iter().map(|(key, _)| *key).collect();
+        assert!(names
+            .iter()
+            .all(|n| REQUIRED.contains(n) || OPTIONAL.contains(n)));
+        assert_eq!(names.len(), REQUIRED.len() + OPTIONAL.len());
+        let names: Vec<&str> = bare.fields().iter().map(|(key, _)| *key).collect();
+        assert_eq!(names, REQUIRED);
+        assert_eq!(bare.fields()[0], ("schema", VERSION));
+    }
+}
@@ -0,0 +1,40 @@
+use std::sync::Arc;
+
+use super::{Level, Line, Logger};
+
+/// A Logger borrowed with a different label, see `Logger::with_label`.
+#[derive(Debug)]
+pub struct Labeled<'l> {
+    pub(crate) logger: &'l mut Logger,
+    pub(crate) label: Arc<str>,
+}
+
+impl<'l> Labeled<'l> {
+    /// Log a message at the given level.
+    pub fn log<S: Into<String>>(&mut self, level: Level, msg: S) {
+        self.logger.write_log_line(Line {
+            label: Some(&self.label),
+            ..Line::new(level, &msg.into())
+        });
+    }
+    /// Log a message at Level::Trace.
+    pub fn trace<S: Into<String>>(&mut self, msg: S) {
+        self.log(Level::Trace, msg);
+    }
+    /// Log a message at Level::Debug.
+    pub fn debug<S: Into<String>>(&mut self, msg: S) {
+        self.log(Level::Debug, msg);
+    }
+    /// Log a message at Level::Info.
+    pub fn info<S: Into<String>>(&mut self, msg: S) {
+        self.log(Level::Info, msg);
+    }
+    /// Log a message at Level::Warn.
+    pub fn warn<S: Into<String>>(&mut self, msg: S) {
+        self.log(Level::Warn, msg);
+    }
+    /// Log a message at Level::Error.
+    pub fn error<S: Into<String>>(&mut self, msg: S) {
+        self.log(Level::Error, msg);
+    }
+}
@@ -0,0 +1,73 @@
+use super::Level;
+
+/// What each level is called on an outside system's severity scale.
+///
+/// The Gcp and Datadog formats and the Sentry sink each start from their
+/// own preset. Set another with LoggerOpts::set_severity_map, say to have
+/// Cloud Logging get numbers, or Sentry::severity_map.
+///
+/// ```no_run
+/// use little_logger::log::{Level, LogFormat, LoggerOpts, SeverityMap};
+///
+/// let opts = LoggerOpts::new()
+///     .set_file_format(LogFormat::Gcp)
+///     .set_severity_map(SeverityMap::gcp().set(Level::Error, "CRITICAL"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SeverityMap {
+    /// Indexed by level, Trace first.
+    names: [String; 5],
+}
+
+impl SeverityMap {
+    /// A map with these names for Trace through Error.
+    pub fn new(names: [&str; 5]) -> SeverityMap {
+        SeverityMap {
+            names: names.map(str::to_string),
+        }
+    }
+    /// Syslog severities, `7` debug to `3` error. Syslog has nothing below
+    /// debug so Trace is `7` too.
+    pub fn syslog() -> SeverityMap {
+        SeverityMap::new(["7", "7", "6", "4", "3"])
+    }
+    /// Google Cloud Logging's LogSeverity names.
+    pub fn gcp() -> SeverityMap {
+        SeverityMap::new(["DEBUG", "DEBUG", "INFO", "WARNING", "ERROR"])
+    }
+    /// Datadog's log statuses.
+    pub fn datadog() -> SeverityMap {
+        SeverityMap::new(["debug", "debug", "info", "warning", "error"])
+    }
+    /// Sentry's event levels.
+    pub fn sentry() -> SeverityMap {
+        SeverityMap::new(["debug", "debug", "info", "warning", "error"])
+    }
+    /// Windows event log types, `4` information, `2` warning and `1`
+    /// error.
+    pub fn windows() -> SeverityMap {
+        SeverityMap::new(["4", "4", "4", "2", "1"])
+    }
+    /// Call `level` this instead.
+    pub fn set(mut self, level: Level, name: &str) -> Self {
+        self.names[level as usize] = name.to_string();
+        self
+    }
+    pub fn get(&self, level: Level) -> &str {
+        &self.names[level as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_overrides_one_level() {
+        let map = SeverityMap::syslog().set(Level::Error, "2");
+        assert_eq!(map.get(Level::Error), "2");
+        assert_eq!(map.get(Level::Warn), "4");
+        assert_eq!(SeverityMap::windows().get(Level::Trace), "4");
+    }
+}
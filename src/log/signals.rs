@@ -0,0 +1,145 @@
+//! Flushing the logs when the process is told to stop, see
+//! Logger::flush_on_signals.
+
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::thread;
+
+use super::flush::SharedFile;
+
+const SIGNALS: [libc::c_int; 2] = [libc::SIGINT, libc::SIGTERM];
+
+/// The write end of the pipe the handler passes signals through.
+static PIPE: AtomicI32 = AtomicI32::new(-1);
+static INSTALLED: OnceLock<Result<(), i32>> = OnceLock::new();
+static FILES: Mutex<Vec<Weak<Mutex<io::BufWriter<std::fs::File>>>>> = Mutex::new(Vec::new());
+
+/// Flush and sync every tracked file, along with stdout, when SIGINT or
+/// SIGTERM arrives. The handlers are installed by the first call.
+pub(crate) fn register() -> io::Result<()> {
+    let installed = INSTALLED.get_or_init(|| install().map_err(|e| e.raw_os_error().unwrap_or(0)));
+    match installed {
+        Ok(()) => Ok(()),
+        Err(code) => Err(io::Error::from_raw_os_error(*code)),
+    }
+}
+
+/// Flush `file` too when a signal arrives. Every log file is tracked as
+/// it's opened, so files opened after a rotation or by a file or tenant
+/// sink are covered as well.
+pub(crate) fn track(file: &SharedFile) {
+    let mut files = FILES.lock().unwrap_or_else(|e| e.into_inner());
+    files.retain(|file| file.strong_count() > 0);
+    files.push(Arc::downgrade(file));
+}
+
+/// A handler may do next to nothing, so it only writes the signal to a
+/// pipe. A thread reading the other end does the flushing, then puts the
+/// default action back and raises the signal again.
+fn install() -> io::Result<()> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let [read_fd, write_fd] = fds;
+    PIPE.store(write_fd, Ordering::SeqCst);
+    thread::Builder::new()
+        .name(String::from("little_logger-signals"))
+        .spawn(move || loop {
+            let mut signal = 0u8;
+            let read = unsafe { libc::read(read_fd, (&mut signal as *mut u8).cast(), 1) };
+            if read != 1 {
+                continue;
+            }
+            flush_all();
+            unsafe {
+                libc::signal(libc::c_int::from(signal), libc::SIG_DFL);
+                libc::raise(libc::c_int::from(signal));
+            }
+        })?;
+    for signal in SIGNALS {
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+            if libc::sigaction(signal, &action, std::ptr::null_mut()) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+    }
+    Ok(())
+}
+
+extern "C" fn on_signal(signal: libc::c_int) {
+    let byte = signal as u8;
+    unsafe {
+        libc::write(PIPE.load(Ordering::SeqCst), (&byte as *const u8).cast(), 1);
+    }
+}
+
+/// Everything registered and still alive, out to the disk.
+fn flush_all() {
+    let files = FILES.lock().unwrap_or_else(|e| e.into_inner());
+    for file in files.iter().filter_map(Weak::upgrade) {
+        let mut out = file.lock().unwrap_or_else(|e| e.into_inner());
+        if out.flush().is_ok() {
+            let _ = out.get_ref().sync_data();
+        }
+    }
+    let _ = io::stdout().flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    #[test]
+    fn test_registered_file_is_flushed() {
+        let path = std::env::temp_dir().join("llog_signals_test.txt");
+        let file = super::super::flush::share(File::create(&path).unwrap(), 4096, None);
+        register().unwrap();
+
+        file.lock().unwrap().write_all(b"last words\n").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+        flush_all();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "last words\n");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_rotated_and_sink_files_are_flushed() {
+        use crate::log::{LogFormat, Logger, LoggerOpts};
+
+        let dir = std::env::temp_dir().join("llog_signals_rotate_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let main = dir.join("llog.txt");
+        let extra = dir.join("sink.json");
+
+        let opts = LoggerOpts::new()
+            .set_log_type("file")
+            .set_logfile_name(main.to_str().unwrap())
+            .set_buffer_size(4096)
+            .set_rotate_size(200)
+            .add_file_sink(extra.to_str().unwrap(), LogFormat::Json);
+        let mut logger = Logger::new(opts);
+        logger.flush_on_signals().unwrap();
+        for i in 0..10 {
+            logger.info(format!("before the rotation {i}"));
+        }
+        logger.info("last words");
+        assert!(dir.join("llog.txt.1").exists());
+        flush_all();
+        assert!(std::fs::read_to_string(&main)
+            .unwrap()
+            .contains("last words"));
+        assert!(std::fs::read_to_string(&extra)
+            .unwrap()
+            .contains("last words"));
+        drop(logger);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,303 @@
+//! AWS Signature Version 4, shared by the S3 upload and the CloudWatch
+//! sink.
+
+#[cfg(feature = "aws")]
+use chrono::DateTime;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+/// What requests are signed with.
+#[derive(Debug, Clone)]
+pub(crate) struct Credentials {
+    pub(crate) access_key: String,
+    pub(crate) secret_key: String,
+    /// Set for temporary credentials, from a role or Lambda.
+    pub(crate) token: Option<String>,
+}
+
+#[cfg(feature = "aws")]
+impl Credentials {
+    /// Look for credentials where the AWS SDKs do, in order: the
+    /// `AWS_ACCESS_KEY_ID` environment variables, the `AWS_PROFILE` (or
+    /// default) profile in `~/.aws/credentials`, the ECS container
+    /// endpoint and the EC2 instance metadata service.
+    ///
+    /// Also returns when they expire, for the ones that do.
+    pub(crate) fn default_chain() -> Result<(Credentials, Option<DateTime<Utc>>), String> {
+        if let Some(credentials) = from_env() {
+            return Ok((credentials, None));
+        }
+        if let Some(credentials) = from_profile() {
+            return Ok((credentials, None));
+        }
+        if let Some(url) = container_url() {
+            let mut request = ureq::get(&url).timeout(METADATA_TIMEOUT);
+            if let Ok(token) = std::env::var("AWS_CONTAINER_AUTHORIZATION_TOKEN") {
+                request = request.set("authorization", &token);
+            }
+            return from_json(&fetch(request)?);
+        }
+        from_instance_metadata()
+    }
+}
+
+#[cfg(feature = "aws")]
+const METADATA_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+#[cfg(feature = "aws")]
+fn from_env() -> Option<Credentials> {
+    Some(Credentials {
+        access_key: std::env::var("AWS_ACCESS_KEY_ID").ok()?,
+        secret_key: std::env::var("AWS_SECRET_ACCESS_KEY").ok()?,
+        token: std::env::var("AWS_SESSION_TOKEN").ok(),
+    })
+}
+
+/// Read a profile from the shared credentials file.
+#[cfg(feature = "aws")]
+fn from_profile() -> Option<Credentials> {
+    let path = match std::env::var("AWS_SHARED_CREDENTIALS_FILE") {
+        Ok(path) => std::path::PathBuf::from(path),
+        Err(_) => {
+            let home = std::env::var("HOME").or_else(|_| std::env::var("USERPROFILE"));
+            std::path::Path::new(&home.ok()?)
+                .join(".aws")
+                .join("credentials")
+        }
+    };
+    let profile = std::env::var("AWS_PROFILE").unwrap_or_else(|_| String::from("default"));
+    parse_profile(&std::fs::read_to_string(path).ok()?, &profile)
+}
+
+#[cfg(feature = "aws")]
+fn parse_profile(ini: &str, profile: &str) -> Option<Credentials> {
+    let (mut access_key, mut secret_key, mut token) = (None, None, None);
+    let mut in_profile = false;
+    for line in ini.lines().map(str::trim) {
+        if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            in_profile = section.trim() == profile;
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=').filter(|_| in_profile) else {
+            continue;
+        };
+        let value = Some(value.trim().to_string());
+        match key.trim() {
+            "aws_access_key_id" => access_key = value,
+            "aws_secret_access_key" => secret_key = value,
+            "aws_session_token" => token = value,
+            _ => {}
+        }
+    }
+    Some(Credentials {
+        access_key: access_key?,
+        secret_key: secret_key?,
+        token,
+    })
+}
+
+#[cfg(feature = "aws")]
+fn container_url() -> Option<String> {
+    if let Ok(uri) = std::env::var("AWS_CONTAINER_CREDENTIALS_RELATIVE_URI") {
+        return Some(format!("http://169.254.170.2{uri}"));
+    }
+    std::env::var("AWS_CONTAINER_CREDENTIALS_FULL_URI").ok()
+}
+
+/// The role credentials of an EC2 instance, through IMDSv2.
+#[cfg(feature = "aws")]
+fn from_instance_metadata() -> Result<(Credentials, Option<DateTime<Utc>>), String> {
+    const IMDS: &str = "http://169.254.169.254/latest";
+    let token = fetch(
+        ureq::put(&format!("{IMDS}/api/token"))
+            .timeout(METADATA_TIMEOUT)
+            .set("x-aws-ec2-metadata-token-ttl-seconds", "21600"),
+    )
+    .map_err(|e| format!("no AWS credentials found: {e}"))?;
+    let get = |path: &str| {
+        fetch(
+            ureq::get(&format!("{IMDS}/meta-data/iam/security-credentials/{path}"))
+                .timeout(METADATA_TIMEOUT)
+                .set("x-aws-ec2-metadata-token", &token),
+        )
+    };
+    let roles = get("")?;
+    let role = roles.lines().next().ok_or("the instance has no IAM role")?;
+    from_json(&get(role)?)
+}
+
+#[cfg(feature = "aws")]
+fn fetch(request: ureq::Request) -> Result<String, String> {
+    request
+        .call()
+        .map_err(|e| e.to_string())?
+        .into_string()
+        .map_err(|e| e.to_string())
+}
+
+/// The JSON the container and instance endpoints answer with.
+#[cfg(feature = "aws")]
+fn from_json(body: &str) -> Result<(Credentials, Option<DateTime<Utc>>), String> {
+    let fields = super::reader::parse_json_object(body).map_err(|e| e.to_string())?;
+    let get = |key: &str| {
+        fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.clone())
+    };
+    let credentials = Credentials {
+        access_key: get("AccessKeyId").ok_or("no AccessKeyId in credentials")?,
+        secret_key: get("SecretAccessKey").ok_or("no SecretAccessKey in credentials")?,
+        token: get("Token"),
+    };
+    let expires = get("Expiration")
+        .and_then(|e| DateTime::parse_from_rfc3339(&e).ok())
+        .map(|e| e.with_timezone(&Utc));
+    Ok((credentials, expires))
+}
+
+/// The parts of an HTTP request that get signed.
+pub(crate) struct Request<'r> {
+    pub(crate) method: &'r str,
+    pub(crate) host: &'r str,
+    pub(crate) path: &'r str,
+    /// Any headers besides `host` and the ones sign adds.
+    pub(crate) headers: &'r [(&'r str, &'r str)],
+    pub(crate) body: &'r [u8],
+}
+
+/// The headers to add to `request` so AWS accepts it. `x-amz-date`,
+/// `x-amz-content-sha256` and the session token are added here.
+pub(crate) fn sign(
+    credentials: &Credentials,
+    region: &str,
+    service: &str,
+    request: &Request,
+) -> Vec<(String, String)> {
+    let Request {
+        method,
+        host,
+        path,
+        headers,
+        body,
+    } = request;
+    let now = Utc::now();
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+    let payload_hash = hex(&Sha256::digest(body));
+
+    let mut signed: Vec<(String, String)> = headers
+        .iter()
+        .map(|(name, value)| (name.to_lowercase(), value.trim().to_string()))
+        .collect();
+    signed.push((String::from("host"), host.to_string()));
+    signed.push((String::from("x-amz-date"), amz_date.clone()));
+    signed.push((String::from("x-amz-content-sha256"), payload_hash.clone()));
+    if let Some(token) = &credentials.token {
+        signed.push((String::from("x-amz-security-token"), token.clone()));
+    }
+    signed.sort();
+    let names = signed
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_headers: String = signed
+        .iter()
+        .map(|(name, value)| format!("{name}:{value}\n"))
+        .collect();
+
+    let canonical = format!("{method}\n{path}\n\n{canonical_headers}\n{names}\n{payload_hash}");
+    let scope = format!("{date}/{region}/{service}/aws4_request");
+    let to_sign = format!(
+        "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+        hex(&Sha256::digest(canonical.as_bytes()))
+    );
+    let key = signing_key(&credentials.secret_key, &date, region, service);
+    let signature = hex(&hmac(&key, to_sign.as_bytes()));
+    let authorization = format!(
+        "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={names}, Signature={signature}",
+        credentials.access_key
+    );
+
+    signed.retain(|(name, _)| name != "host");
+    signed.push((String::from("authorization"), authorization));
+    signed
+}
+
+/// The host part of an endpoint URL.
+pub(crate) fn host(endpoint: &str) -> &str {
+    endpoint
+        .split("://")
+        .last()
+        .unwrap_or_default()
+        .split('/')
+        .next()
+        .unwrap_or_default()
+}
+
+fn signing_key(secret: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let key = hmac(format!("AWS4{secret}").as_bytes(), date.as_bytes());
+    let key = hmac(&key, region.as_bytes());
+    let key = hmac(&key, service.as_bytes());
+    hmac(&key, b"aws4_request")
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Percent-encode everything but the unreserved characters and `/`.
+pub(crate) fn uri_encode(s: &str) -> String {
+    let mut encoded = String::new();
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signing_key_matches_aws_example() {
+        let key = signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20120215",
+            "us-east-1",
+            "iam",
+        );
+        assert_eq!(
+            hex(&key),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+        assert_eq!(uri_encode("web 1/llog.txt.1"), "web%201/llog.txt.1");
+    }
+
+    #[cfg(feature = "aws")]
+    #[test]
+    fn test_profile_is_read_from_its_section() {
+        let ini = "[default]\naws_access_key_id = A\naws_secret_access_key = B\n\n\
+                   [ci]\naws_access_key_id=C\naws_secret_access_key=D\naws_session_token=E\n";
+        let ci = parse_profile(ini, "ci").unwrap();
+        assert_eq!(
+            (ci.access_key.as_str(), ci.token.as_deref()),
+            ("C", Some("E"))
+        );
+        assert_eq!(parse_profile(ini, "default").unwrap().secret_key, "B");
+        assert!(parse_profile(ini, "prod").is_none());
+    }
+}
@@ -0,0 +1,370 @@
+use std::fmt;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use super::format::{Layout, LogFormat, Record};
+use super::internal::Problems;
+use super::stats::{Histogram, Stats};
+
+/// Somewhere records go besides the console and the log file.
+pub(crate) trait Sink: fmt::Debug + Send {
+    fn write_record(&mut self, record: &Record, layout: &Layout) -> io::Result<()>;
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// What kind of sink this is, for its write times in Logger::stats.
+    fn name(&self) -> &'static str {
+        "sink"
+    }
+}
+
+/// An Isolated sink shared by a Logger and its LocalHandles, so there is
+/// one of each sink however many handles write to it.
+#[derive(Debug, Clone)]
+pub(crate) struct SharedSink(Arc<Mutex<Isolated>>);
+
+impl SharedSink {
+    pub(crate) fn new(sink: Isolated) -> SharedSink {
+        SharedSink(Arc::new(Mutex::new(sink)))
+    }
+
+    pub(crate) fn lock(&self) -> MutexGuard<'_, Isolated> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Formats every record and throws it away, for measuring what logging
+/// costs without any I/O. See the `null` log type.
+#[derive(Debug)]
+pub(crate) struct NullSink {
+    pub(crate) format: LogFormat,
+}
+
+impl Sink for NullSink {
+    fn write_record(&mut self, record: &Record, layout: &Layout) -> io::Result<()> {
+        std::hint::black_box(self.format.encode(record, layout));
+        Ok(())
+    }
+}
+
+/// Wait up to `limit` for the thread of a sink being dropped to send
+/// what's left, then leave it to finish on its own.
+pub(crate) fn join_within(worker: JoinHandle<()>, limit: Duration) {
+    let deadline = Instant::now() + limit;
+    while !worker.is_finished() {
+        if Instant::now() >= deadline {
+            return;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    let _ = worker.join();
+}
+
+/// The first wait after a sink fails, doubled on every failed retry.
+const FIRST_RETRY: Duration = Duration::from_secs(1);
+const MAX_RETRY: Duration = Duration::from_secs(60);
+
+/// When a failing sink is tried again.
+///
+/// After a failed write the sink is degraded: records meant for it are
+/// dropped until the retry time, then the next record is tried again.
+/// Failing and recovering are reported once each, not per record.
+#[derive(Debug)]
+pub(crate) struct Backoff {
+    retry_at: Option<Instant>,
+    backoff: Duration,
+    /// Dropped since the sink last failed.
+    dropped: u64,
+}
+
+impl Default for Backoff {
+    fn default() -> Backoff {
+        Backoff {
+            retry_at: None,
+            backoff: FIRST_RETRY,
+            dropped: 0,
+        }
+    }
+}
+
+impl Backoff {
+    pub(crate) fn is_degraded(&self) -> bool {
+        self.retry_at.is_some()
+    }
+
+    /// Whether the next write should be tried. If not, the record counts
+    /// as dropped.
+    pub(crate) fn is_due(&mut self) -> bool {
+        if self.retry_at.is_some_and(|at| Instant::now() < at) {
+            self.dropped += 1;
+            return false;
+        }
+        true
+    }
+
+    /// Take in how the write that was tried went.
+    pub(crate) fn settle(&mut self, name: &str, result: &io::Result<()>, problems: &Problems) {
+        match (result, self.retry_at) {
+            (Ok(()), None) => {}
+            (Ok(()), Some(_)) => {
+                problems.report(format!(
+                    "sink {name} recovered, {} records were dropped",
+                    self.dropped
+                ));
+                *self = Backoff::default();
+            }
+            (Err(e), retry_at) => {
+                if retry_at.is_none() {
+                    problems.report(format!(
+                        "sink {name} failed, degraded until it recovers: {e}"
+                    ));
+                } else {
+                    self.backoff = (self.backoff * 2).min(MAX_RETRY);
+                }
+                self.dropped += 1;
+                self.retry_at = Some(Instant::now() + self.backoff);
+            }
+        }
+    }
+
+    /// Make one `write` to the console or log file named `name`, timed
+    /// in `stats`, unless it is still waiting to retry.
+    pub(crate) fn write(
+        &mut self,
+        name: &'static str,
+        stats: &mut Stats,
+        problems: &Problems,
+        write: impl FnOnce() -> io::Result<()>,
+    ) {
+        if !self.is_due() {
+            stats.count_dropped(name, 1);
+            return;
+        }
+        let start = Instant::now();
+        let written = write();
+        stats.observe_write(name, start, &written);
+        self.settle(name, &written, problems);
+    }
+}
+
+/// Keeps a failing sink from holding up the others, see Backoff.
+#[derive(Debug)]
+pub(crate) struct Isolated {
+    sink: Box<dyn Sink>,
+    backoff: Backoff,
+    /// Dropped over the sink's life, see Logger::stats.
+    dropped_total: u64,
+    /// Where failing and recovering are reported.
+    problems: Problems,
+    write_seconds: Histogram,
+}
+
+impl Isolated {
+    pub(crate) fn new(sink: Box<dyn Sink>, problems: Problems) -> Isolated {
+        Isolated {
+            sink,
+            backoff: Backoff::default(),
+            dropped_total: 0,
+            problems,
+            write_seconds: Histogram::write_seconds(),
+        }
+    }
+
+    pub(crate) fn name(&self) -> &'static str {
+        self.sink.name()
+    }
+
+    pub(crate) fn dropped_total(&self) -> u64 {
+        self.dropped_total
+    }
+
+    /// How long the writes that were tried took.
+    pub(crate) fn write_seconds(&self) -> &Histogram {
+        &self.write_seconds
+    }
+
+    pub(crate) fn is_degraded(&self) -> bool {
+        self.backoff.is_degraded()
+    }
+
+    pub(crate) fn write_record(&mut self, record: &Record, layout: &Layout) {
+        if !self.backoff.is_due() {
+            self.dropped_total += 1;
+            return;
+        }
+        let start = Instant::now();
+        let result = self.sink.write_record(record, layout);
+        self.write_seconds.observe(start.elapsed().as_secs_f64());
+        self.settle(result);
+    }
+
+    pub(crate) fn flush(&mut self) -> io::Result<()> {
+        if self.is_degraded() {
+            return Ok(());
+        }
+        let result = self.sink.flush();
+        self.settle(result);
+        Ok(())
+    }
+
+    fn settle(&mut self, result: io::Result<()>) {
+        if result.is_err() {
+            self.dropped_total += 1;
+        }
+        self.backoff
+            .settle(self.sink.name(), &result, &self.problems);
+    }
+}
+
+/// Caps the bytes a sink's sending thread has yet to pick up, so a slow
+/// or unreachable remote end can't make the queue grow without bound.
+/// Clones share the count.
+#[derive(Debug, Clone)]
+pub(crate) struct QueueLimit {
+    queued: Arc<AtomicUsize>,
+    max: usize,
+}
+
+impl QueueLimit {
+    pub(crate) fn new(max: usize) -> QueueLimit {
+        QueueLimit {
+            queued: Arc::new(AtomicUsize::new(0)),
+            max,
+        }
+    }
+
+    /// Make room for `bytes`, or fail when the queue is full. A failed
+    /// write degrades the sink, which gives the queue time to drain.
+    pub(crate) fn reserve(&self, bytes: usize) -> io::Result<()> {
+        let reserved = self
+            .queued
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |queued| {
+                let total = queued.saturating_add(bytes);
+                (total <= self.max).then_some(total)
+            });
+        match reserved {
+            Ok(_) => Ok(()),
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                format!("queue is over its {} bytes", self.max),
+            )),
+        }
+    }
+
+    /// The sending thread picked `bytes` up.
+    pub(crate) fn release(&self, bytes: usize) {
+        self.queued.fetch_sub(bytes, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::Level;
+
+    #[derive(Debug)]
+    struct Broken {
+        attempts: usize,
+        /// Cleared to let writes through again.
+        failing: Arc<std::sync::atomic::AtomicBool>,
+    }
+
+    impl Sink for Broken {
+        fn write_record(&mut self, _: &Record, _: &Layout) -> io::Result<()> {
+            self.attempts += 1;
+            if self.failing.load(Ordering::SeqCst) {
+                return Err(io::Error::other("disk full"));
+            }
+            Ok(())
+        }
+
+        fn name(&self) -> &'static str {
+            "broken"
+        }
+    }
+
+    #[test]
+    fn test_failing_sink_is_not_retried_until_backoff() {
+        let record = Record {
+            level: Level::Info,
+            level_name: "INFO",
+            target: None,
+            label: "LLOG",
+            date_time: "",
+            msg: "hi",
+            err: None,
+            err_kind: None,
+            err_code: None,
+            template: None,
+            kv: &[],
+            typed: &[],
+            static_fields: &[],
+            span_id: None,
+            span_depth: 0,
+            event_id: None,
+            session_id: None,
+            raw: false,
+            source: None,
+            change: None,
+        };
+        let problems = Problems::default();
+        let failing = Arc::new(std::sync::atomic::AtomicBool::new(true));
+        let broken = Broken {
+            attempts: 0,
+            failing: failing.clone(),
+        };
+        let mut sink = Isolated::new(Box::new(broken), problems.clone());
+        for _ in 0..5 {
+            sink.write_record(&record, &Layout::default());
+        }
+        assert!(sink.is_degraded());
+        assert_eq!(sink.backoff.dropped, 5);
+        assert!(format!("{:?}", sink.sink).contains("attempts: 1"));
+        assert_eq!(
+            problems.take(),
+            ["sink broken failed, degraded until it recovers: disk full"]
+        );
+        assert_eq!(sink.dropped_total(), 5);
+
+        failing.store(false, Ordering::SeqCst);
+        sink.backoff.retry_at = Some(Instant::now());
+        sink.write_record(&record, &Layout::default());
+        assert!(!sink.is_degraded());
+        assert_eq!(
+            problems.take(),
+            ["sink broken recovered, 5 records were dropped"]
+        );
+    }
+
+    #[test]
+    fn test_local_handles_share_the_sinks() {
+        use crate::log::testing::Capture;
+        use crate::log::{Logger, LoggerOpts};
+
+        let capture = Capture::new();
+        let opts = LoggerOpts::new().set_log_type("null").set_capture(&capture);
+        let mut logger = Logger::new(opts);
+        let mut handle = logger.thread_local_handle();
+        assert_eq!(handle.sinks.len(), 1);
+        assert!(Arc::ptr_eq(&logger.sinks[0].0, &handle.sinks[0].0));
+        assert_eq!(handle.effective_config().captures.len(), 1);
+
+        handle.info("from the handle");
+        logger.info("from the logger");
+        assert_eq!(capture.records().len(), 2);
+    }
+
+    #[test]
+    fn test_queue_limit_refuses_past_max() {
+        let limit = QueueLimit::new(100);
+        limit.reserve(60).unwrap();
+        assert!(limit.clone().reserve(60).is_err());
+        limit.release(60);
+        limit.reserve(100).unwrap();
+    }
+}
@@ -0,0 +1,134 @@
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::time::Instant;
+
+use super::{Level, Line, Logger};
+
+/// A span that hasn't been entered yet, see `Logger::span`.
+#[derive(Debug)]
+pub struct SpanBuilder<'l> {
+    logger: &'l mut Logger,
+    name: String,
+    level: Level,
+    fields: Vec<(String, String)>,
+}
+
+impl<'l> SpanBuilder<'l> {
+    pub(crate) fn new(logger: &'l mut Logger, name: &str) -> SpanBuilder<'l> {
+        SpanBuilder {
+            logger,
+            name: name.to_string(),
+            level: Level::Info,
+            fields: Vec::new(),
+        }
+    }
+
+    /// Attach a field to the enter and exit lines.
+    pub fn field<V: fmt::Display>(mut self, key: &str, value: V) -> Self {
+        self.fields.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Log the enter and exit lines at this level.
+    ///
+    /// Default: Level::Info
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Log the enter line and start the clock.
+    pub fn enter(self) -> Span<'l> {
+        let SpanBuilder {
+            logger,
+            name,
+            level,
+            fields,
+        } = self;
+        logger.next_span_id += 1;
+        let id = logger.next_span_id;
+        let parent = logger.spans.last().map(|parent| parent.to_string());
+        logger.spans.push(id);
+
+        let mut kv = borrow_kv(&fields);
+        if let Some(parent) = &parent {
+            kv.push(("parent_span_id", parent));
+        }
+        let msg = format!("{} {}", logger.opts.localization.get("enter"), name);
+        logger.write_log_line(Line {
+            kv: &kv,
+            span_edge: true,
+            ..Line::new(level, &msg)
+        });
+        Span {
+            logger,
+            name,
+            level,
+            fields,
+            start: Instant::now(),
+        }
+    }
+}
+
+/// An entered span. Logs the exit line with the elapsed time when dropped.
+///
+/// Log through the span while it is alive, everything logged gets its
+/// `span_id` and is indented in the text formats. Spans entered through a
+/// span nest.
+#[derive(Debug)]
+pub struct Span<'l> {
+    logger: &'l mut Logger,
+    name: String,
+    level: Level,
+    fields: Vec<(String, String)>,
+    start: Instant,
+}
+
+fn borrow_kv(fields: &[(String, String)]) -> Vec<(&str, &str)> {
+    fields
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.as_str()))
+        .collect()
+}
+
+/// Milliseconds since `start` to three places, zero when deterministic.
+pub(crate) fn elapsed_ms(logger: &Logger, start: Instant) -> String {
+    match logger.opts.deterministic {
+        true => String::from("0.000"),
+        false => format!("{:.3}", start.elapsed().as_secs_f64() * 1000.0),
+    }
+}
+
+impl Deref for Span<'_> {
+    type Target = Logger;
+
+    fn deref(&self) -> &Logger {
+        self.logger
+    }
+}
+
+impl DerefMut for Span<'_> {
+    fn deref_mut(&mut self) -> &mut Logger {
+        self.logger
+    }
+}
+
+impl Drop for Span<'_> {
+    fn drop(&mut self) {
+        let elapsed = elapsed_ms(self.logger, self.start);
+        let msg = format!(
+            "{} {}",
+            self.logger.opts.localization.get("exit"),
+            self.name
+        );
+        let fields = std::mem::take(&mut self.fields);
+        let mut kv = borrow_kv(&fields);
+        kv.push(("elapsed_ms", &elapsed));
+        self.logger.write_log_line(Line {
+            kv: &kv,
+            span_edge: true,
+            ..Line::new(self.level, &msg)
+        });
+        self.logger.spans.pop();
+    }
+}
@@ -0,0 +1,309 @@
+use std::collections::VecDeque;
+use std::fmt::{self, Write};
+use std::io;
+use std::time::{Duration, Instant};
+
+use super::Level;
+
+/// Calls back when too many lines at a level show up within a window.
+pub(crate) struct Threshold {
+    level: Level,
+    count: usize,
+    window: Duration,
+    /// When each line counted in the current window was written.
+    hits: VecDeque<Instant>,
+    callback: Box<dyn FnMut(usize) + Send>,
+}
+
+impl fmt::Debug for Threshold {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter
+            .debug_struct("Threshold")
+            .field("level", &self.level)
+            .field("count", &self.count)
+            .field("window", &self.window)
+            .field("hits", &self.hits.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Threshold {
+    pub(crate) fn new<F>(level: Level, count: usize, window: Duration, callback: F) -> Threshold
+    where
+        F: FnMut(usize) + Send + 'static,
+    {
+        Threshold {
+            level,
+            count: count.max(1),
+            window,
+            hits: VecDeque::new(),
+            callback: Box::new(callback),
+        }
+    }
+
+    /// Count a line written at `level`.
+    ///
+    /// The window starts over once the callback has run, so one burst
+    /// only raises one alert.
+    pub(crate) fn record(&mut self, level: Level, now: Instant) {
+        if level < self.level {
+            return;
+        }
+        while let Some(first) = self.hits.front() {
+            if now.duration_since(*first) < self.window {
+                break;
+            }
+            self.hits.pop_front();
+        }
+        self.hits.push_back(now);
+        if self.hits.len() >= self.count {
+            (self.callback)(self.hits.len());
+            self.hits.clear();
+        }
+    }
+}
+
+/// Upper bounds of the write latency buckets, in seconds.
+const WRITE_SECONDS: &[f64] = &[0.000_01, 0.000_1, 0.001, 0.01, 0.1, 1.0];
+/// Upper bounds of the record size buckets, in bytes.
+const RECORD_BYTES: &[f64] = &[64.0, 128.0, 256.0, 512.0, 1024.0, 4096.0, 16384.0];
+
+/// How many values fell at or below each bound, like a Prometheus
+/// histogram.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    bounds: &'static [f64],
+    /// One per bound, plus one for everything above the last.
+    counts: Vec<u64>,
+    sum: f64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Histogram {
+        Histogram {
+            bounds,
+            counts: vec![0; bounds.len() + 1],
+            sum: 0.0,
+        }
+    }
+
+    pub(crate) fn write_seconds() -> Histogram {
+        Histogram::new(WRITE_SECONDS)
+    }
+
+    pub(crate) fn record_bytes() -> Histogram {
+        Histogram::new(RECORD_BYTES)
+    }
+
+    pub(crate) fn observe(&mut self, value: f64) {
+        let bucket = self.bounds.partition_point(|bound| *bound < value);
+        self.counts[bucket] += 1;
+        self.sum += value;
+    }
+
+    fn merge(&mut self, other: &Histogram) {
+        for (count, more) in self.counts.iter_mut().zip(&other.counts) {
+            *count += more;
+        }
+        self.sum += other.sum;
+    }
+
+    /// Each upper bound with how many values were at or below it, the
+    /// last being infinity.
+    pub fn buckets(&self) -> impl Iterator<Item = (f64, u64)> + '_ {
+        let bounds = self.bounds.iter().copied().chain([f64::INFINITY]);
+        bounds.zip(self.counts.iter().scan(0, |total, count| {
+            *total += count;
+            Some(*total)
+        }))
+    }
+
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    pub fn sum(&self) -> f64 {
+        self.sum
+    }
+}
+
+/// How long writes take and how big records are, see Logger::stats.
+///
+/// Writes are timed per kind of sink: `file`, `console`, `null`, and for
+/// extra sinks `file`, `pipe`, `tenant`, `capture`, `cloudwatch` or
+/// `sentry`. A slow disk or remote end shows up as its writes moving into
+/// the higher buckets.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stats {
+    record_bytes: Histogram,
+    write_seconds: Vec<(&'static str, Histogram)>,
+    dropped: Vec<(&'static str, u64)>,
+}
+
+impl Default for Stats {
+    fn default() -> Stats {
+        Stats {
+            record_bytes: Histogram::record_bytes(),
+            write_seconds: Vec::new(),
+            dropped: Vec::new(),
+        }
+    }
+}
+
+impl Stats {
+    pub(crate) fn observe_record(&mut self, bytes: usize) {
+        self.record_bytes.observe(bytes as f64);
+    }
+
+    /// Time a write that started at `start`, counting it as dropped if
+    /// it failed.
+    pub(crate) fn observe_write(
+        &mut self,
+        sink: &'static str,
+        start: Instant,
+        written: &io::Result<()>,
+    ) {
+        let mut one = Histogram::write_seconds();
+        one.observe(start.elapsed().as_secs_f64());
+        self.merge_writes(sink, &one);
+        if written.is_err() {
+            self.count_dropped(sink, 1);
+        }
+    }
+
+    pub(crate) fn count_dropped(&mut self, sink: &'static str, count: u64) {
+        if count == 0 {
+            return;
+        }
+        match self.dropped.iter_mut().find(|(name, _)| *name == sink) {
+            Some((_, dropped)) => *dropped += count,
+            None => self.dropped.push((sink, count)),
+        }
+    }
+
+    pub(crate) fn merge_writes(&mut self, sink: &'static str, histogram: &Histogram) {
+        match self
+            .write_seconds
+            .iter_mut()
+            .find(|(name, _)| *name == sink)
+        {
+            Some((_, writes)) => writes.merge(histogram),
+            None => self.write_seconds.push((sink, histogram.clone())),
+        }
+    }
+
+    /// The size of each record as written to the console or log file.
+    pub fn record_bytes(&self) -> &Histogram {
+        &self.record_bytes
+    }
+
+    /// How long writes to one kind of sink took, in seconds.
+    pub fn write_seconds(&self, sink: &str) -> Option<&Histogram> {
+        self.write_seconds
+            .iter()
+            .find(|(name, _)| *name == sink)
+            .map(|(_, writes)| writes)
+    }
+
+    /// Records one kind of sink failed to write or had no room for, see
+    /// LoggerOpts::set_max_queue_bytes.
+    pub fn dropped(&self, sink: &str) -> u64 {
+        self.dropped
+            .iter()
+            .find(|(name, _)| *name == sink)
+            .map_or(0, |(_, dropped)| *dropped)
+    }
+
+    /// The kinds of sink written to so far.
+    pub fn sinks(&self) -> impl Iterator<Item = &str> {
+        self.write_seconds.iter().map(|(name, _)| *name)
+    }
+
+    /// The histograms in the Prometheus text format, to serve from a
+    /// `/metrics` endpoint as `llog_write_seconds` and `llog_record_bytes`,
+    /// with the drops as `llog_dropped_total`.
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::from("# TYPE llog_write_seconds histogram\n");
+        for (sink, writes) in &self.write_seconds {
+            push_histogram(
+                &mut out,
+                "llog_write_seconds",
+                &format!("sink=\"{sink}\","),
+                writes,
+            );
+        }
+        out.push_str("# TYPE llog_record_bytes histogram\n");
+        push_histogram(&mut out, "llog_record_bytes", "", &self.record_bytes);
+        out.push_str("# TYPE llog_dropped_total counter\n");
+        for (sink, dropped) in &self.dropped {
+            let _ = writeln!(out, "llog_dropped_total{{sink=\"{sink}\"}} {dropped}");
+        }
+        out
+    }
+}
+
+fn push_histogram(out: &mut String, name: &str, labels: &str, histogram: &Histogram) {
+    for (bound, count) in histogram.buckets() {
+        let le = match bound.is_finite() {
+            true => bound.to_string(),
+            false => String::from("+Inf"),
+        };
+        let _ = writeln!(out, "{name}_bucket{{{labels}le=\"{le}\"}} {count}");
+    }
+    let labels = match labels.trim_end_matches(',') {
+        "" => String::new(),
+        labels => format!("{{{labels}}}"),
+    };
+    let _ = writeln!(out, "{name}_sum{labels} {}", histogram.sum());
+    let _ = writeln!(out, "{name}_count{labels} {}", histogram.count());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_threshold_fires_once_per_burst() {
+        let fired = Arc::new(AtomicUsize::new(0));
+        let counter = fired.clone();
+        let mut threshold = Threshold::new(Level::Warn, 3, Duration::from_secs(60), move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        let start = Instant::now();
+        threshold.record(Level::Error, start);
+        threshold.record(Level::Info, start);
+        threshold.record(Level::Warn, start + Duration::from_secs(61));
+        threshold.record(Level::Warn, start + Duration::from_secs(62));
+        assert_eq!(fired.load(Ordering::SeqCst), 0);
+
+        threshold.record(Level::Error, start + Duration::from_secs(63));
+        assert_eq!(fired.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_histogram_buckets_are_cumulative() {
+        let mut stats = Stats::default();
+        for bytes in [10, 100, 100, 100_000] {
+            stats.observe_record(bytes);
+        }
+        let start = Instant::now() - Duration::from_millis(5);
+        stats.observe_write("file", start, &Err(io::Error::other("disk full")));
+
+        let buckets: Vec<(f64, u64)> = stats.record_bytes().buckets().collect();
+        assert_eq!(buckets[0], (64.0, 1));
+        assert_eq!(buckets[1], (128.0, 3));
+        assert_eq!(buckets[7], (f64::INFINITY, 4));
+        assert_eq!(stats.write_seconds("file").unwrap().count(), 1);
+
+        let text = stats.to_prometheus();
+        assert!(text.contains("llog_write_seconds_bucket{sink=\"file\",le=\"0.001\"} 0\n"));
+        assert!(text.contains("llog_write_seconds_bucket{sink=\"file\",le=\"0.01\"} 1\n"));
+        assert!(text.contains("llog_write_seconds_count{sink=\"file\"} 1\n"));
+        assert!(text.contains("llog_record_bytes_bucket{le=\"+Inf\"} 4\n"));
+        assert!(text.contains("llog_record_bytes_sum 100210\n"));
+        assert!(text.contains("llog_dropped_total{sink=\"file\"} 1\n"));
+    }
+}
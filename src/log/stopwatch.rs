@@ -0,0 +1,79 @@
+use std::ops::{Deref, DerefMut};
+use std::time::Instant;
+
+use super::span::elapsed_ms;
+use super::{Level, Line, Logger};
+
+/// Times an operation in stages, see `Logger::stopwatch`.
+///
+/// Each lap logs how long it took since the previous one, and the total is
+/// logged on finish or when the stopwatch is dropped.
+#[derive(Debug)]
+pub struct Stopwatch<'l> {
+    logger: &'l mut Logger,
+    name: String,
+    level: Level,
+    start: Instant,
+    lap_start: Instant,
+}
+
+impl<'l> Stopwatch<'l> {
+    pub(crate) fn new(logger: &'l mut Logger, name: &str) -> Stopwatch<'l> {
+        let now = Instant::now();
+        Stopwatch {
+            logger,
+            name: name.to_string(),
+            level: Level::Info,
+            start: now,
+            lap_start: now,
+        }
+    }
+
+    /// Log the laps and total at this level.
+    ///
+    /// Default: Level::Info
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Log `stage` with the time since the last lap, or the start.
+    pub fn lap(&mut self, stage: &str) {
+        let lap_ms = elapsed_ms(self.logger, self.lap_start);
+        let total_ms = elapsed_ms(self.logger, self.start);
+        let msg = format!("{} {}", self.name, stage);
+        self.logger.write_log_line(Line {
+            kv: &[("lap_ms", &lap_ms), ("total_ms", &total_ms)],
+            ..Line::new(self.level, &msg)
+        });
+        self.lap_start = Instant::now();
+    }
+
+    /// Log the total time. Same as dropping the stopwatch.
+    pub fn finish(self) {}
+}
+
+impl Deref for Stopwatch<'_> {
+    type Target = Logger;
+
+    fn deref(&self) -> &Logger {
+        self.logger
+    }
+}
+
+impl DerefMut for Stopwatch<'_> {
+    fn deref_mut(&mut self) -> &mut Logger {
+        self.logger
+    }
+}
+
+impl Drop for Stopwatch<'_> {
+    fn drop(&mut self) {
+        let total_ms = elapsed_ms(self.logger, self.start);
+        let msg = format!("{} finished", self.name);
+        self.logger.write_log_line(Line {
+            kv: &[("total_ms", &total_ms)],
+            ..Line::new(self.level, &msg)
+        });
+    }
+}
@@ -0,0 +1,115 @@
+//! Writing each tenant's records to a file of its own.
+
+use std::io;
+
+use super::format::{Layout, Record};
+use super::sink::Sink;
+use super::{LogFile, LogFormat, LoggerOpts};
+
+/// Where tenant files go, see LoggerOpts::add_tenant_sink.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct TenantSpec {
+    /// The record field naming the tenant.
+    pub(crate) field: String,
+    /// With `{tenant}` in it, e.g. `logs/{tenant}/app.log`.
+    pub(crate) path: String,
+    pub(crate) format: LogFormat,
+    pub(crate) max_open: usize,
+}
+
+/// Keeps the most recently used tenant files open, closing the least
+/// recently used one when a new tenant needs a slot.
+#[derive(Debug)]
+pub(crate) struct TenantSink {
+    spec: TenantSpec,
+    /// Buffering, headers and rotation for the files come from these.
+    opts: LoggerOpts,
+    /// Least recently used first.
+    open: Vec<(String, Box<LogFile>)>,
+}
+
+impl TenantSink {
+    pub(crate) fn new(spec: TenantSpec, opts: &LoggerOpts) -> TenantSink {
+        TenantSink {
+            spec,
+            opts: opts.clone(),
+            open: Vec::new(),
+        }
+    }
+
+    fn file(&mut self, tenant: &str) -> io::Result<&mut LogFile> {
+        match self.open.iter().position(|(open, _)| open == tenant) {
+            Some(i) => {
+                let entry = self.open.remove(i);
+                self.open.push(entry);
+            }
+            None => {
+                if self.open.len() >= self.spec.max_open.max(1) {
+                    let (_, mut oldest) = self.open.remove(0);
+                    oldest.flush()?;
+                }
+                let path = self.spec.path.replace("{tenant}", &safe_name(tenant));
+                if let Some(dir) = std::path::Path::new(&path).parent() {
+                    std::fs::create_dir_all(dir)?;
+                }
+                let format = self.opts.sink_format(self.spec.format);
+                let file = LogFile::at(&self.opts, &path, format);
+                self.open.push((tenant.to_string(), file));
+            }
+        }
+        let (_, file) = self.open.last_mut().expect("tenant file was just pushed");
+        Ok(file)
+    }
+}
+
+/// Keep a tenant id from reaching outside its own directory.
+fn safe_name(tenant: &str) -> String {
+    let name: String = tenant
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '_',
+        })
+        .collect();
+    match name.trim_start_matches('.') {
+        "" => format!("_{name}"),
+        _ if name.starts_with('.') => format!("_{name}"),
+        _ => name,
+    }
+}
+
+impl Sink for TenantSink {
+    /// Records without the tenant field are left to the other sinks.
+    fn write_record(&mut self, record: &Record, layout: &Layout) -> io::Result<()> {
+        let fields = record.fields();
+        let Some((_, tenant)) = fields.iter().find(|(key, _)| *key == self.spec.field) else {
+            return Ok(());
+        };
+        let file = self.file(tenant)?;
+        file.write_all(&file.format.encode(record, layout))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        for (_, file) in &mut self.open {
+            file.flush()?;
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "tenant"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_safe_name_stays_in_its_dir() {
+        assert_eq!(safe_name("acme-01"), "acme-01");
+        assert_eq!(safe_name("../etc"), "_.._etc");
+        assert_eq!(safe_name("a/b"), "a_b");
+    }
+}
@@ -0,0 +1,160 @@
+//! Checking what an application logs from its tests.
+//!
+//! ```no_run
+//! use little_logger::assert_log;
+//! use little_logger::log::testing::Capture;
+//! use little_logger::log::{Level, Logger, LoggerOpts};
+//!
+//! let capture = Capture::new();
+//! let mut logger = Logger::new(LoggerOpts::new().set_log_type("console").set_capture(&capture));
+//! logger.warn("upstream timed out, retrying");
+//! assert_log!(capture, Level::Warn, contains "retrying");
+//! assert_eq!(capture.snapshot(), "[LLOG]::[WARN] -> upstream timed out, retrying\n");
+//! ```
+
+use std::fmt;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use super::format::{push_kv, Layout, Record};
+use super::sink::Sink;
+use super::Level;
+
+/// Collects the records a Logger writes, see LoggerOpts::set_capture.
+///
+/// Clones share the same records, so keep one and hand the other to the
+/// opts.
+#[derive(Clone, Default)]
+pub struct Capture {
+    records: Arc<Mutex<Vec<Captured>>>,
+}
+
+/// One record as it was logged, minus anything that changes between runs
+/// like the time and event ids.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Captured {
+    pub level: Level,
+    pub label: String,
+    pub target: Option<String>,
+    pub message: String,
+    pub fields: Vec<(String, String)>,
+    /// The message before its fields were filled in, see Logger::log_t.
+    pub template: Option<String>,
+    pub error: Option<String>,
+}
+
+impl fmt::Debug for Capture {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter
+            .debug_struct("Capture")
+            .field("records", &self.lock().len())
+            .finish()
+    }
+}
+
+impl Capture {
+    pub fn new() -> Capture {
+        Capture::default()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Captured>> {
+        self.records.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Everything captured so far, oldest first.
+    pub fn records(&self) -> Vec<Captured> {
+        self.lock().clone()
+    }
+
+    /// Whether a record at `level` has `needle` in its message.
+    pub fn contains(&self, level: Level, needle: &str) -> bool {
+        self.lock()
+            .iter()
+            .any(|record| record.level == level && record.message.contains(needle))
+    }
+
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// The captured records as Text lines without the date/time, stable
+    /// enough to compare against a golden file.
+    ///
+    /// `[LLOG]::[WARN]::[my_app::db] -> retrying attempt=2`
+    pub fn snapshot(&self) -> String {
+        let mut out = String::new();
+        for record in self.lock().iter() {
+            out.push_str(&format!("[{}]::[{}]", record.label, record.level));
+            if let Some(target) = &record.target {
+                out.push_str(&format!("::[{target}]"));
+            }
+            out.push_str(" -> ");
+            out.push_str(&record.message);
+            // As in Text, a template's fields are in the message already.
+            if record.template.is_none() {
+                let fields: Vec<(&str, &str)> = record
+                    .fields
+                    .iter()
+                    .map(|(k, v)| (k.as_str(), v.as_str()))
+                    .collect();
+                push_kv(&mut out, &fields);
+            }
+            out.push('\n');
+            if let Some(error) = &record.error {
+                out.push_str(error);
+                out.push('\n');
+            }
+        }
+        out
+    }
+}
+
+impl Sink for Capture {
+    fn write_record(&mut self, record: &Record, _: &Layout) -> io::Result<()> {
+        self.lock().push(Captured {
+            level: record.level,
+            label: record.label.to_string(),
+            target: record.target.map(str::to_string),
+            message: record.msg.to_string(),
+            fields: record
+                .kv
+                .iter()
+                .chain(
+                    record
+                        .change
+                        .map(|(old, new)| [("old", old), ("new", new)])
+                        .iter()
+                        .flatten(),
+                )
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            template: record.template.map(str::to_string),
+            error: record.err.map(str::to_string),
+        });
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "capture"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::{Logger, LoggerOpts};
+
+    #[test]
+    fn test_snapshot_has_no_time() {
+        let capture = Capture::new();
+        let opts = LoggerOpts::new()
+            .set_log_type("console")
+            .set_event_ids(true)
+            .set_capture(&capture);
+        let mut logger = Logger::new(opts);
+        logger.log_kv(Level::Warn, "retrying", &[("attempt", "2")]);
+        crate::assert_log!(capture, Level::Warn, contains "retry");
+
+        assert_eq!(capture.snapshot(), "[LLOG]::[WARN] -> retrying attempt=2\n");
+    }
+}
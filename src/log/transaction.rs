@@ -0,0 +1,171 @@
+use std::ops::{Deref, DerefMut};
+use std::panic::Location;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+
+use super::intern::Interner;
+use super::{Level, Line, Logger};
+
+/// Holds back everything logged through it until `commit`, see
+/// `Logger::transaction`.
+///
+/// Dropping it without committing throws the lines away. A transaction
+/// started inside another only decides about its own lines, committing
+/// hands them to the outer one.
+#[derive(Debug)]
+pub struct Transaction<'l> {
+    logger: &'l mut Logger,
+    /// Where this transaction's lines start in the held back ones.
+    start: usize,
+    outer: bool,
+    committed: bool,
+}
+
+impl<'l> Transaction<'l> {
+    pub(crate) fn new(logger: &'l mut Logger) -> Transaction<'l> {
+        let outer = logger.held.is_none();
+        let start = logger.held.get_or_insert_with(Vec::new).len();
+        Transaction {
+            logger,
+            start,
+            outer,
+            committed: false,
+        }
+    }
+
+    /// Write the lines, stamped with the time they were logged at.
+    pub fn commit(mut self) {
+        self.committed = true;
+    }
+
+    /// Throw the lines away. Same as dropping the transaction.
+    pub fn abort(self) {}
+}
+
+impl Deref for Transaction<'_> {
+    type Target = Logger;
+
+    fn deref(&self) -> &Logger {
+        self.logger
+    }
+}
+
+impl DerefMut for Transaction<'_> {
+    fn deref_mut(&mut self) -> &mut Logger {
+        self.logger
+    }
+}
+
+impl Drop for Transaction<'_> {
+    fn drop(&mut self) {
+        if !self.committed {
+            if let Some(held) = &mut self.logger.held {
+                held.truncate(self.start);
+            }
+        }
+        if self.outer {
+            let held = self.logger.held.take().unwrap_or_default();
+            if self.committed {
+                for line in &held {
+                    line.replay(self.logger);
+                }
+            }
+        }
+    }
+}
+
+/// A Line that owns its parts, to be written later.
+#[derive(Debug)]
+pub(crate) struct HeldLine {
+    level: Level,
+    target: Option<Arc<str>>,
+    label: Option<Arc<str>>,
+    msg: String,
+    err: Option<String>,
+    err_kind: Option<String>,
+    err_code: Option<String>,
+    template: Option<String>,
+    kv: Vec<(String, String)>,
+    typed: Vec<String>,
+    span_edge: bool,
+    function: Option<Arc<str>>,
+    raw: bool,
+    change: Option<(String, String)>,
+    location: Option<&'static Location<'static>>,
+    ts_format: Option<String>,
+    /// When it was logged, the wall clock and the time since the Logger
+    /// started.
+    at: DateTime<Local>,
+    elapsed: Duration,
+}
+
+impl HeldLine {
+    /// Labels, targets and function names come from `strings`, as they
+    /// repeat from one held line to the next.
+    pub(crate) fn new(line: &Line, elapsed: Duration, strings: &mut Interner) -> HeldLine {
+        let owned = |s: Option<&str>| s.map(str::to_string);
+        HeldLine {
+            level: line.level,
+            target: line.target.map(|target| strings.intern(target)),
+            label: line.label.map(|label| strings.intern(label)),
+            msg: line.msg.to_string(),
+            err: owned(line.err),
+            err_kind: owned(line.err_kind),
+            err_code: owned(line.err_code),
+            template: owned(line.template),
+            kv: line
+                .kv
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            typed: line.typed.iter().map(|k| k.to_string()).collect(),
+            span_edge: line.span_edge,
+            function: line.function.map(|function| strings.intern(function)),
+            raw: line.raw,
+            change: line.change.map(|(o, n)| (o.to_string(), n.to_string())),
+            location: line.location,
+            ts_format: owned(line.ts_format),
+            at: Local::now(),
+            elapsed,
+        }
+    }
+
+    /// Write the line stamped with the time it was logged at.
+    pub(crate) fn replay(&self, logger: &mut Logger) {
+        let kv = self.kv();
+        let typed: Vec<&str> = self.typed.iter().map(String::as_str).collect();
+        let outer = logger.replay_time.replace((self.at, self.elapsed));
+        logger.write_log_line(self.as_line(&kv, &typed));
+        logger.replay_time = outer;
+    }
+
+    fn kv(&self) -> Vec<(&str, &str)> {
+        self.kv
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect()
+    }
+
+    fn as_line<'h>(&'h self, kv: &'h [(&'h str, &'h str)], typed: &'h [&'h str]) -> Line<'h> {
+        Line {
+            level: self.level,
+            target: self.target.as_deref(),
+            label: self.label.as_deref(),
+            msg: &self.msg,
+            err: self.err.as_deref(),
+            err_kind: self.err_kind.as_deref(),
+            err_code: self.err_code.as_deref(),
+            template: self.template.as_deref(),
+            kv,
+            typed,
+            span_edge: self.span_edge,
+            function: self.function.as_deref(),
+            raw: self.raw,
+            change: self.change.as_ref().map(|(o, n)| (o.as_str(), n.as_str())),
+            location: self.location,
+            ts_format: self.ts_format.as_deref(),
+        }
+    }
+}
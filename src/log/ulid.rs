@@ -0,0 +1,93 @@
+//! Minimal ULIDs: 48 bits of milliseconds and 80 random bits, written as
+//! 26 characters of Crockford base32 that sort by time.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const RANDOM_MASK: u128 = (1 << 80) - 1;
+
+/// Hands out ULIDs that keep increasing, even within one millisecond.
+#[derive(Debug)]
+pub(crate) struct UlidGenerator {
+    last_ms: u64,
+    last_random: u128,
+    state: u64,
+}
+
+impl Default for UlidGenerator {
+    fn default() -> UlidGenerator {
+        let seed = RandomState::new().build_hasher().finish();
+        UlidGenerator {
+            last_ms: 0,
+            last_random: 0,
+            state: seed | 1,
+        }
+    }
+}
+
+impl UlidGenerator {
+    pub(crate) fn next(&mut self) -> String {
+        encode(self.next_value())
+    }
+
+    /// The next id as 32 lowercase hex digits, the way UUIDs are written
+    /// without dashes.
+    pub(crate) fn next_hex(&mut self) -> String {
+        format!("{:032x}", self.next_value())
+    }
+
+    fn next_value(&mut self) -> u128 {
+        let ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64);
+        if ms > self.last_ms {
+            self.last_ms = ms;
+            self.last_random =
+                (u128::from(self.rand()) << 64 | u128::from(self.rand())) & RANDOM_MASK;
+        } else {
+            // Same millisecond or a clock that went back, count up instead.
+            self.last_random = (self.last_random + 1) & RANDOM_MASK;
+        }
+        u128::from(self.last_ms) << 80 | self.last_random
+    }
+
+    /// xorshift64*, plenty for ids that only have to be unique.
+    fn rand(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+}
+
+/// A ULID made the first time it is asked for and kept for the rest of the
+/// process.
+pub(crate) fn session_id() -> &'static str {
+    static SESSION: OnceLock<String> = OnceLock::new();
+    SESSION.get_or_init(|| UlidGenerator::default().next())
+}
+
+fn encode(value: u128) -> String {
+    (0..26)
+        .rev()
+        .map(|i| ALPHABET[((value >> (i * 5)) & 0x1f) as usize] as char)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ulids_sort_in_order() {
+        let mut ids = UlidGenerator::default();
+        let first = ids.next();
+        let second = ids.next();
+        assert_eq!(first.len(), 26);
+        assert!(first < second);
+        assert_eq!(encode(u128::MAX), "7ZZZZZZZZZZZZZZZZZZZZZZZZZ");
+    }
+}
@@ -0,0 +1,72 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+
+use super::internal::Problems;
+use super::LoggerOpts;
+
+/// Keeps an eye on a TOML config file and holds on to the latest good
+/// version of it until the Logger picks it up.
+pub(crate) struct ConfigWatch {
+    /// Dropping the watcher stops it.
+    _watcher: RecommendedWatcher,
+    pending: Arc<Mutex<Option<LoggerOpts>>>,
+}
+
+impl fmt::Debug for ConfigWatch {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter
+            .debug_struct("ConfigWatch")
+            .finish_non_exhaustive()
+    }
+}
+
+impl ConfigWatch {
+    /// The directory is watched rather than the file, so editors that save
+    /// by replacing the file are noticed too. A config that fails to load
+    /// is reported to `problems`.
+    pub(crate) fn new(path: &Path, problems: Problems) -> io::Result<ConfigWatch> {
+        let path = path.canonicalize()?;
+        let dir = path.parent().unwrap_or(Path::new("/")).to_path_buf();
+        let pending = Arc::new(Mutex::new(None));
+
+        let config: PathBuf = path.clone();
+        let slot = pending.clone();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let Ok(event) = event else {
+                return;
+            };
+            if !event.paths.iter().any(|changed| changed == &config) {
+                return;
+            }
+            if event.kind.is_access() || event.kind.is_remove() {
+                return;
+            }
+            match LoggerOpts::from_toml_file(&config) {
+                Ok(opts) => *slot.lock().unwrap_or_else(|e| e.into_inner()) = Some(opts),
+                Err(e) => problems.report(format!(
+                    "keeping the old config, {} failed to load: {}",
+                    config.display(),
+                    e
+                )),
+            }
+        })
+        .map_err(io::Error::other)?;
+        watcher
+            .watch(&dir, RecursiveMode::NonRecursive)
+            .map_err(io::Error::other)?;
+
+        Ok(ConfigWatch {
+            _watcher: watcher,
+            pending,
+        })
+    }
+
+    /// The config written since the last call, if any. Never blocks.
+    pub(crate) fn take(&self) -> Option<LoggerOpts> {
+        self.pending.try_lock().ok()?.take()
+    }
+}
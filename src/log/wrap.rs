@@ -0,0 +1,89 @@
+use std::env;
+
+/// Continuation lines are indented by this much.
+const INDENT: &str = "    ";
+/// Used when the width of the terminal can't be found.
+const FALLBACK_WIDTH: usize = 80;
+
+/// The width of the terminal stdout is attached to.
+///
+/// `COLUMNS` wins when it is set, otherwise the terminal is asked directly.
+pub(crate) fn terminal_width() -> usize {
+    env::var("COLUMNS")
+        .ok()
+        .and_then(|cols| cols.trim().parse().ok())
+        .filter(|cols: &usize| *cols > 0)
+        .or_else(ioctl_width)
+        .unwrap_or(FALLBACK_WIDTH)
+}
+
+#[cfg(unix)]
+fn ioctl_width() -> Option<usize> {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    // SAFETY: TIOCGWINSZ only writes into the winsize we hand it.
+    let ret = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    (ret == 0 && size.ws_col > 0).then_some(size.ws_col as usize)
+}
+
+#[cfg(not(unix))]
+fn ioctl_width() -> Option<usize> {
+    None
+}
+
+/// Soft wrap every line of `text` at `width` columns.
+///
+/// Lines are only broken at spaces and continuation lines get a hanging
+/// indent. ANSI color codes don't count towards the width.
+pub(crate) fn wrap(text: &str, width: usize) -> String {
+    let mut out = String::with_capacity(text.len());
+    for line in text.lines() {
+        wrap_line(line, width, &mut out);
+        out.push('\n');
+    }
+    out
+}
+
+fn wrap_line(line: &str, width: usize, out: &mut String) {
+    let mut col = 0;
+    let mut at_start = true;
+    for word in line.split(' ') {
+        let word_width = visible_width(word);
+        if !at_start && col + 1 + word_width > width {
+            out.push('\n');
+            out.push_str(INDENT);
+            col = INDENT.len();
+        } else if !at_start {
+            out.push(' ');
+            col += 1;
+        }
+        out.push_str(word);
+        col += word_width;
+        at_start = false;
+    }
+}
+
+/// The number of characters that end up on screen, skipping `ESC[...m`.
+fn visible_width(word: &str) -> usize {
+    let mut width = 0;
+    let mut in_escape = false;
+    for c in word.chars() {
+        match c {
+            '\x1b' => in_escape = true,
+            'm' if in_escape => in_escape = false,
+            _ if in_escape => {}
+            _ => width += 1,
+        }
+    }
+    width
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_hangs_and_ignores_colors() {
+        let wrapped = wrap("\x1b[32m[INFO]\x1b[0m one two three four\n", 16);
+        assert_eq!(wrapped, "\x1b[32m[INFO]\x1b[0m one two\n    three four\n");
+    }
+}